@@ -0,0 +1,280 @@
+//! `#[derive(SqliteRow)]` for structs that are stored as table rows with sqlighters.
+//! Use it through the `derive` feature of sqlighters.
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{
+    parse_macro_input, Data, DeriveInput, Field, Fields, GenericArgument, Ident, LitStr,
+    PathArguments, Type,
+};
+
+/// Implements `sqlighters::SqliteRow`.
+///
+/// On the struct, `#[sqlite(table = "name")]` sets the table name.
+/// On fields, `#[sqlite(rename = "name")]` sets the column name, `#[sqlite(type = "TYPE")]`
+/// the column type and `#[sqlite(primary_key)]` makes the column (part of) the primary key.
+#[proc_macro_derive(SqliteRow, attributes(sqlite))]
+pub fn derive_sqlite_row(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+struct Column {
+    field: Ident,
+    name: String,
+    sql_type: Option<String>,
+    nullable: bool,
+    primary_key: bool,
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let mut table_name = snake_case(&input.ident.to_string());
+    for attribute in input.attrs.iter().filter(|a| a.path().is_ident("sqlite")) {
+        attribute.parse_nested_meta(|meta| {
+            if meta.path.is_ident("table") {
+                table_name = meta.value()?.parse::<LitStr>()?.value();
+                Ok(())
+            } else {
+                Err(meta.error("expected `table`"))
+            }
+        })?;
+    }
+
+    let Data::Struct(data) = &input.data else {
+        return Err(syn::Error::new_spanned(
+            &input.ident,
+            "SqliteRow can only be derived for structs",
+        ));
+    };
+    let Fields::Named(fields) = &data.fields else {
+        return Err(syn::Error::new_spanned(
+            &input.ident,
+            "SqliteRow can only be derived for structs with named fields",
+        ));
+    };
+    let columns = fields
+        .named
+        .iter()
+        .map(column)
+        .collect::<syn::Result<Vec<_>>>()?;
+
+    let create_table = create_table(&table_name, &columns);
+    let rowid_alias = rowid_alias(&columns);
+    let values = columns.iter().map(|column| {
+        let field = &column.field;
+        if rowid_alias == Some(field) {
+            // eg. a u64 can be too large for a rowid
+            let message = format!("{} is out of range for a rowid", column.name);
+            let rowid = |value| {
+                quote!(::std::convert::TryFrom::try_from(#value).map_err(|_| {
+                    ::sqlighters::Error::Serialize(::std::string::String::from(#message))
+                })?)
+            };
+            let set_rowid = if column.nullable {
+                let rowid = rowid(quote!(rowid));
+                quote!(if let Some(rowid) = self.#field {
+                    record.rowid = #rowid;
+                })
+            } else {
+                let rowid = rowid(quote!(self.#field));
+                quote!(record.rowid = #rowid;)
+            };
+            quote! {
+                #set_rowid
+                record.add_value(::sqlighters::values::null());
+            }
+        } else {
+            quote!(record.add_value(::sqlighters::Value::from(self.#field));)
+        }
+    });
+
+    let ident = &input.ident;
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::sqlighters::SqliteRow for #ident #type_generics #where_clause {
+            fn table_name() -> &'static str {
+                #table_name
+            }
+
+            fn create_table() -> ::std::string::String {
+                ::std::string::String::from(#create_table)
+            }
+
+            fn into_record(
+                self,
+                rowid: i64,
+            ) -> ::std::result::Result<::sqlighters::Record, ::sqlighters::Error> {
+                let mut record = ::sqlighters::Record::new(rowid);
+                #(#values)*
+                ::std::result::Result::Ok(record)
+            }
+        }
+    })
+}
+
+fn column(field: &Field) -> syn::Result<Column> {
+    let ident = field.ident.clone().expect("named fields have a name");
+    let (sql_type, nullable) = sql_type(&field.ty);
+    let mut column = Column {
+        name: ident.to_string(),
+        field: ident,
+        sql_type: sql_type.map(str::to_owned),
+        nullable,
+        primary_key: false,
+    };
+    for attribute in field.attrs.iter().filter(|a| a.path().is_ident("sqlite")) {
+        attribute.parse_nested_meta(|meta| {
+            if meta.path.is_ident("rename") {
+                column.name = meta.value()?.parse::<LitStr>()?.value();
+            } else if meta.path.is_ident("type") {
+                column.sql_type = Some(meta.value()?.parse::<LitStr>()?.value());
+            } else if meta.path.is_ident("primary_key") {
+                column.primary_key = true;
+            } else {
+                return Err(meta.error("expected `rename`, `type` or `primary_key`"));
+            }
+            Ok(())
+        })?;
+    }
+    Ok(column)
+}
+
+/// The column type for the Rust type, and whether it is an `Option`
+fn sql_type(ty: &Type) -> (Option<&'static str>, bool) {
+    match ty {
+        Type::Reference(reference) => match &*reference.elem {
+            Type::Path(path) if path.path.is_ident("str") => (Some("TEXT"), false),
+            Type::Slice(slice) if is_type(&slice.elem, "u8") => (Some("BLOB"), false),
+            _ => (None, false),
+        },
+        Type::Path(path) => {
+            let Some(segment) = path.path.segments.last() else {
+                return (None, false);
+            };
+            match segment.ident.to_string().as_str() {
+                "Option" => (generic_argument(segment).and_then(|t| sql_type(t).0), true),
+                "Vec" if generic_argument(segment).is_some_and(|t| is_type(t, "u8")) => {
+                    (Some("BLOB"), false)
+                }
+                "i8" | "i16" | "i32" | "i64" | "u8" | "u16" | "u32" | "bool" => {
+                    (Some("INTEGER"), false)
+                }
+                "f32" | "f64" => (Some("REAL"), false),
+                "String" => (Some("TEXT"), false),
+                _ => (None, false),
+            }
+        }
+        _ => (None, false),
+    }
+}
+
+fn generic_argument(segment: &syn::PathSegment) -> Option<&Type> {
+    let PathArguments::AngleBracketed(arguments) = &segment.arguments else {
+        return None;
+    };
+    arguments.args.iter().find_map(|argument| match argument {
+        GenericArgument::Type(ty) => Some(ty),
+        _ => None,
+    })
+}
+
+fn is_type(ty: &Type, name: &str) -> bool {
+    matches!(ty, Type::Path(path) if path.path.is_ident(name))
+}
+
+/// A single INTEGER primary key is the rowid
+fn rowid_alias(columns: &[Column]) -> Option<&Ident> {
+    let mut primary_key = columns.iter().filter(|column| column.primary_key);
+    match (primary_key.next(), primary_key.next()) {
+        (Some(column), None)
+            if column
+                .sql_type
+                .as_deref()
+                .is_some_and(|t| t.eq_ignore_ascii_case("INTEGER")) =>
+        {
+            Some(&column.field)
+        }
+        _ => None,
+    }
+}
+
+fn create_table(table_name: &str, columns: &[Column]) -> String {
+    let primary_key: Vec<&Column> = columns.iter().filter(|c| c.primary_key).collect();
+    let mut definitions: Vec<String> = columns
+        .iter()
+        .map(|column| {
+            let mut definition = quote_identifier(&column.name);
+            if let Some(sql_type) = &column.sql_type {
+                definition.push(' ');
+                definition.push_str(sql_type);
+            }
+            if column.primary_key && primary_key.len() == 1 {
+                definition.push_str(" PRIMARY KEY");
+            }
+            if !column.nullable {
+                definition.push_str(" NOT NULL");
+            }
+            definition
+        })
+        .collect();
+    if primary_key.len() > 1 {
+        let names: Vec<String> = primary_key
+            .iter()
+            .map(|column| quote_identifier(&column.name))
+            .collect();
+        definitions.push(format!("PRIMARY KEY({})", names.join(", ")));
+    }
+    format!(
+        "CREATE TABLE {}({})",
+        quote_identifier(table_name),
+        definitions.join(", ")
+    )
+}
+
+fn quote_identifier(identifier: &str) -> String {
+    format!("\"{}\"", identifier.replace('"', "\"\""))
+}
+
+/// `PersonDTO` becomes `person_dto`
+fn snake_case(name: &str) -> String {
+    let chars: Vec<char> = name.chars().collect();
+    let mut snake_case = String::new();
+    for (index, c) in chars.iter().enumerate() {
+        if c.is_uppercase() && index > 0 {
+            let previous = chars[index - 1];
+            let next_is_lowercase = chars.get(index + 1).is_some_and(|n| n.is_lowercase());
+            if previous.is_lowercase()
+                || previous.is_ascii_digit()
+                || (previous.is_uppercase() && next_is_lowercase)
+            {
+                snake_case.push('_');
+            }
+        }
+        snake_case.extend(c.to_lowercase());
+    }
+    snake_case
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snake_case() {
+        assert_eq!(snake_case("Person"), "person");
+        assert_eq!(snake_case("PersonDTO"), "person_dto");
+        assert_eq!(snake_case("HTTPRequest2Log"), "http_request2_log");
+    }
+
+    #[test]
+    fn test_sql_type() {
+        let sql_type = |ty: &str| sql_type(&syn::parse_str(ty).unwrap());
+        assert_eq!(sql_type("i64"), (Some("INTEGER"), false));
+        assert_eq!(sql_type("Option<String>"), (Some("TEXT"), true));
+        assert_eq!(sql_type("std::vec::Vec<u8>"), (Some("BLOB"), false));
+        assert_eq!(sql_type("&'a str"), (Some("TEXT"), false));
+        assert_eq!(sql_type("Vec<String>"), (None, false));
+    }
+}
@@ -0,0 +1,379 @@
+use crate::builder::TableBuilder;
+use crate::database::{check_page_count, group_child_pages, interior_page, set_overflow_refs};
+use crate::error::Error;
+use crate::page::Page;
+use crate::reader::{corrupt, DatabaseReader};
+use crate::record::Record;
+use crate::row::SqliteRow;
+use byteorder::{BigEndian, ByteOrder};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::io::{Seek, SeekFrom, Write};
+
+/// Appends records to the tables of an existing file, instead of writing the whole file again.
+/// Because rowids only grow, new records go to the rightmost leaf page of the table.
+/// That page and the interior pages above it are rewritten, and split when they are full.
+/// New pages go after the end of the file and all other pages stay the same.
+///
+/// Tables with indexes can't be appended to, because new index entries can go anywhere in the index.
+/// Neither can files with auto-vacuum or with reserved bytes at the end of each page.
+pub struct DatabaseAppender<'a> {
+    reader: DatabaseReader<'a>,
+    bytes: &'a [u8],
+    changes: Changes,
+    /// the names of the tables that were selected
+    selected: Vec<String>,
+    current_table: Option<AppendedTable>,
+}
+
+/// The pages that are rewritten or added, by page number
+struct Changes {
+    pages: BTreeMap<u32, Vec<u8>>,
+    page_count: u32,
+    page_size: usize,
+}
+
+struct AppendedTable {
+    builder: TableBuilder,
+    path: RightmostPath,
+    n_records: usize,
+}
+
+/// The pages of a table b-tree that change when records are appended
+struct RightmostPath {
+    root_page: u32,
+    /// the interior pages from the root down, with the key and left child of each of their cells
+    interior_pages: Vec<(u32, Vec<(i64, u32)>)>,
+    leaf_page: u32,
+    /// the leaf pages that replace the rightmost leaf page
+    leaf_refs: Vec<(i64, u32)>,
+}
+
+impl<'a> DatabaseAppender<'a> {
+    /// Reads the file header
+    pub fn new(bytes: &'a [u8]) -> Result<Self, Error> {
+        let reader = DatabaseReader::new(bytes)?;
+        if reader.usable_size() != reader.page_size() {
+            return Err(Error::Input(
+                "a file with reserved bytes in its pages can't be appended to".to_owned(),
+            ));
+        }
+        if BigEndian::read_u32(&bytes[52..]) != 0 {
+            return Err(Error::Input(
+                "a file with auto-vacuum can't be appended to".to_owned(),
+            ));
+        }
+        Ok(Self {
+            changes: Changes {
+                pages: BTreeMap::new(),
+                page_count: reader.page_count(),
+                page_size: reader.page_size(),
+            },
+            reader,
+            bytes,
+            selected: Vec::new(),
+            current_table: None,
+        })
+    }
+
+    /// Completes the current table and selects the table that records are appended to.
+    /// Every table can be selected once.
+    pub fn table(&mut self, table_name: &str) -> Result<(), Error> {
+        if self
+            .selected
+            .iter()
+            .any(|name| name.eq_ignore_ascii_case(table_name))
+        {
+            return Err(Error::InvalidSchema(format!(
+                "table {table_name} is selected twice"
+            )));
+        }
+        let schema = self.reader.schema()?;
+        let table = schema
+            .iter()
+            .find(|entry| {
+                entry.object_type == "table" && entry.name.eq_ignore_ascii_case(table_name)
+            })
+            .ok_or_else(|| Error::InvalidSchema(format!("no table {table_name}")))?;
+        if let Some(index) = schema.iter().find(|entry| {
+            entry.object_type == "index" && entry.table_name.eq_ignore_ascii_case(table_name)
+        }) {
+            return Err(Error::InvalidSchema(format!(
+                "table {table_name} has index {}, so records can't be appended",
+                index.name
+            )));
+        }
+
+        let sql = table.sql.as_deref().unwrap_or_default();
+        let mut builder = TableBuilder::new(&table.name, sql, self.reader.page_size())?;
+        let path = self.load_rightmost_path(&mut builder, &table.name, table.root_page)?;
+        self.finish_table()?;
+        self.selected.push(table.name.clone());
+        self.current_table = Some(AppendedTable {
+            builder,
+            path,
+            n_records: 0,
+        });
+        Ok(())
+    }
+
+    /// Appends the record to the selected table.
+    /// Its rowid has to be greater than the rowids that are in the table.
+    pub fn add_record(&mut self, mut record: Record) -> Result<(), Error> {
+        record.encode_text(self.reader.encoding());
+        let table = self.current_table.as_mut().ok_or_else(no_table_selected)?;
+        let full_page = table.builder.add_record(record)?;
+        table.n_records += 1;
+        if let Some(full_page) = full_page {
+            table
+                .path
+                .add_leaf_page(full_page, false, &mut self.changes)?;
+        }
+        Ok(())
+    }
+
+    /// Appends the row to the selected table, with the rowid after the last one
+    pub fn add_row<T: SqliteRow>(&mut self, row: T) -> Result<(), Error> {
+        let table = self.current_table.as_ref().ok_or_else(no_table_selected)?;
+        self.add_record(row.into_record(table.builder.next_rowid())?)
+    }
+
+    /// Completes the last table and returns the new file
+    pub fn into_bytes(self) -> Result<Vec<u8>, Error> {
+        let bytes = self.bytes;
+        let original_len = self.changes.page_count as usize * self.changes.page_size;
+        let changes = self.finish()?;
+        let page_size = changes.page_size;
+
+        let mut file = bytes[..original_len.min(bytes.len())].to_vec();
+        file.resize(changes.page_count as usize * page_size, 0);
+        for (page_number, page) in changes.pages {
+            let start = (page_number as usize - 1) * page_size;
+            file[start..start + page_size].copy_from_slice(&page);
+        }
+        Ok(file)
+    }
+
+    /// Completes the last table and writes the pages that changed, and the new ones, to the writer.
+    /// The writer holds the original file, which stays the same otherwise.
+    pub fn write_changes<W: Write + Seek>(self, mut writer: W) -> Result<(), Error> {
+        let changes = self.finish()?;
+        for (page_number, page) in changes.pages {
+            let start = (u64::from(page_number) - 1) * changes.page_size as u64;
+            writer.seek(SeekFrom::Start(start))?;
+            writer.write_all(&page)?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Completes the last table and updates the header, when anything changed
+    fn finish(mut self) -> Result<Changes, Error> {
+        self.finish_table()?;
+        if !self.changes.pages.is_empty() {
+            let mut header_page = self.reader.page(1)?.to_vec();
+            BigEndian::write_u32(&mut header_page[28..], self.changes.page_count);
+            // SQLite only trusts the page count when the version-valid-for number is the change counter
+            let is_valid = header_page[24..28] == header_page[92..96];
+            let change_counter = BigEndian::read_u32(&header_page[24..]).wrapping_add(1);
+            BigEndian::write_u32(&mut header_page[24..], change_counter);
+            if is_valid {
+                BigEndian::write_u32(&mut header_page[92..], change_counter);
+            }
+            self.changes.pages.insert(1, header_page);
+        }
+        Ok(self.changes)
+    }
+
+    fn finish_table(&mut self) -> Result<(), Error> {
+        let Some(table) = self.current_table.take() else {
+            return Ok(());
+        };
+        if table.n_records == 0 {
+            return Ok(());
+        }
+        let AppendedTable {
+            builder, mut path, ..
+        } = table;
+        let (_, leaf_pages, _) = builder.finish();
+        for leaf_page in leaf_pages {
+            path.add_leaf_page(leaf_page, true, &mut self.changes)?;
+        }
+        path.finish(&mut self.changes)
+    }
+
+    /// Follows the rightmost pointers from the root to the last leaf page,
+    /// whose cells are copied to the page that the builder fills next
+    fn load_rightmost_path(
+        &self,
+        builder: &mut TableBuilder,
+        table_name: &str,
+        root_page: u32,
+    ) -> Result<RightmostPath, Error> {
+        let mut interior_pages: Vec<(u32, Vec<(i64, u32)>)> = Vec::new();
+        let mut page_number = root_page;
+        loop {
+            if page_number == 1 {
+                return Err(corrupt(
+                    1,
+                    &format!("table {table_name} is on the schema page"),
+                ));
+            }
+            let (page, header_offset, header) = self.reader.btree_page(page_number)?;
+            if !header.is_table() {
+                return Err(if page_number == root_page {
+                    Error::InvalidSchema(format!("table {table_name} is WITHOUT ROWID"))
+                } else {
+                    corrupt(page_number, "not a table b-tree page")
+                });
+            }
+            let cells = self
+                .reader
+                .cells(page_number, page, header_offset, &header)?;
+
+            let Some(rightmost_pointer) = header.rightmost_pointer else {
+                let pointers = header
+                    .cell_pointers(page, header_offset)
+                    .unwrap_or_default(); // the cells were read
+                for (offset, cell) in pointers.iter().zip(&cells) {
+                    builder
+                        .current_page
+                        .put_cell(&page[*offset..*offset + cell.len], Vec::new());
+                }
+                builder.n_records_on_current_page = cells.len() as u16;
+                // an empty leaf page can only be the root, but the keys above are an upper bound anyway
+                builder.last_rowid = cells.last().and_then(|cell| cell.rowid).or_else(|| {
+                    interior_pages
+                        .iter()
+                        .rev()
+                        .find_map(|(_, cells)| cells.last().map(|(key, _)| *key))
+                });
+                builder.current_page.key = builder.last_rowid.unwrap_or_default();
+                return Ok(RightmostPath {
+                    root_page,
+                    interior_pages,
+                    leaf_page: page_number,
+                    leaf_refs: Vec::new(),
+                });
+            };
+
+            let child_refs = cells
+                .iter()
+                .map(|cell| {
+                    (
+                        cell.rowid.unwrap_or_default(),      // table cells have a key
+                        cell.left_child.unwrap_or_default(), // interior cells have a child
+                    )
+                })
+                .collect();
+            interior_pages.push((page_number, child_refs));
+            if interior_pages
+                .iter()
+                .any(|(page_number, _)| *page_number == rightmost_pointer)
+            {
+                return Err(corrupt(page_number, "the rightmost pointer is a loop"));
+            }
+            page_number = rightmost_pointer;
+        }
+    }
+}
+
+impl Changes {
+    /// The number of the next page at the end of the file
+    fn new_page_number(&mut self) -> Result<u32, Error> {
+        self.page_count = check_page_count(u64::from(self.page_count) + 1)?;
+        Ok(self.page_count)
+    }
+}
+
+impl RightmostPath {
+    /// The first leaf page replaces the rightmost one, unless that is the root, which becomes
+    /// an interior page when there is more than one leaf page. The others are new.
+    fn add_leaf_page(
+        &mut self,
+        page: Page,
+        is_last: bool,
+        changes: &mut Changes,
+    ) -> Result<(), Error> {
+        let replaces_leaf =
+            self.leaf_refs.is_empty() && (is_last || self.leaf_page != self.root_page);
+        let page_number = if replaces_leaf {
+            self.leaf_page
+        } else {
+            changes.new_page_number()?
+        };
+
+        // overflow pages directly follow the new pages
+        let mut page_counter = changes.page_count + 1;
+        let mut data = Cow::Borrowed(&page.data[..]);
+        let mut overflow_pages = Vec::new();
+        set_overflow_refs(&page, &mut data, &mut page_counter, &mut overflow_pages);
+        for (overflow_page, next_page) in overflow_pages {
+            let mut overflow_data = overflow_page.data.clone();
+            BigEndian::write_u32(&mut overflow_data, next_page);
+            let overflow_page_number = changes.new_page_number()?;
+            changes.pages.insert(overflow_page_number, overflow_data);
+        }
+        self.leaf_refs.push((page.key, page_number));
+        changes.pages.insert(page_number, data.into_owned());
+        Ok(())
+    }
+
+    /// Rewrites the interior pages from the leaf up, as long as the pages below them split
+    fn finish(self, changes: &mut Changes) -> Result<(), Error> {
+        let mut child_refs = self.leaf_refs;
+        let mut rightmost_child = self.leaf_page;
+        for (page_number, cells) in self.interior_pages.into_iter().rev() {
+            if child_refs.len() == 1 && child_refs[0].1 == rightmost_child {
+                // the page refers to the same rightmost child, without a key
+                return Ok(());
+            }
+            let refs: Vec<(i64, u32)> = cells.into_iter().chain(child_refs).collect();
+            child_refs = write_interior_pages(&refs, Some(page_number), changes)?;
+            rightmost_child = page_number;
+        }
+
+        // the root page keeps its number, so its content moves when it splits
+        if child_refs.len() > 1 && child_refs[0].1 == self.root_page {
+            let new_page_number = changes.new_page_number()?;
+            let page = changes.pages.remove(&self.root_page).unwrap_or_default(); // it was just written
+            changes.pages.insert(new_page_number, page);
+            child_refs[0].1 = new_page_number;
+        }
+        while child_refs.len() > 1 {
+            let keys: Vec<i64> = child_refs.iter().map(|(key, _)| *key).collect();
+            let is_root = group_child_pages(&keys, changes.page_size).len() == 1;
+            child_refs =
+                write_interior_pages(&child_refs, is_root.then_some(self.root_page), changes)?;
+        }
+        Ok(())
+    }
+}
+
+/// Creates the interior pages for the children. The first one gets the page number, if there is one,
+/// and the others are new. Returns the key and page number of each interior page.
+fn write_interior_pages(
+    child_refs: &[(i64, u32)],
+    first_page_number: Option<u32>,
+    changes: &mut Changes,
+) -> Result<Vec<(i64, u32)>, Error> {
+    let keys: Vec<i64> = child_refs.iter().map(|(key, _)| *key).collect();
+    let mut start = 0;
+    let mut interior_refs = Vec::new();
+    for n_children in group_child_pages(&keys, changes.page_size) {
+        let group = &child_refs[start..start + n_children];
+        start += n_children;
+        let page_number = match first_page_number.filter(|_| interior_refs.is_empty()) {
+            Some(page_number) => page_number,
+            None => changes.new_page_number()?,
+        };
+        let page = interior_page(group, changes.page_size);
+        changes.pages.insert(page_number, page.data);
+        interior_refs.push((group[n_children - 1].0, page_number));
+    }
+    Ok(interior_refs)
+}
+
+fn no_table_selected() -> Error {
+    Error::InvalidSchema("records can only be appended after a table is selected".to_owned())
+}
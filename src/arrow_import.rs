@@ -0,0 +1,242 @@
+use crate::error::Error;
+use crate::record::Record;
+use crate::sql::quote_identifier;
+use crate::values::{self, Value};
+use arrow_array::cast::AsArray;
+use arrow_array::temporal_conversions::{as_date, as_datetime};
+use arrow_array::types::{
+    ArrowPrimitiveType, Date32Type, Date64Type, Decimal128Type, Decimal256Type, DecimalType,
+    Float16Type, Float32Type, Float64Type, Int16Type, Int32Type, Int64Type, Int8Type,
+    TimestampMicrosecondType, TimestampMillisecondType, TimestampNanosecondType,
+    TimestampSecondType, UInt16Type, UInt32Type, UInt64Type, UInt8Type,
+};
+use arrow_array::{Array, RecordBatch};
+use arrow_schema::{DataType, Schema, TimeUnit};
+
+/// The `CREATE TABLE` statement for the Arrow schema. Fields that aren't nullable are `NOT NULL`.
+pub(crate) fn create_table(table_name: &str, schema: &Schema) -> Result<String, Error> {
+    let columns = schema
+        .fields()
+        .iter()
+        .map(|field| {
+            let mut column = quote_identifier(field.name());
+            if let Some(sql_type) = sql_type(field.data_type(), field.name())? {
+                column.push(' ');
+                column.push_str(sql_type);
+            }
+            if !field.is_nullable() {
+                column.push_str(" NOT NULL");
+            }
+            Ok(column)
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+    Ok(format!(
+        "CREATE TABLE {}({})",
+        quote_identifier(table_name),
+        columns.join(", ")
+    ))
+}
+
+/// Dates and timestamps are stored as text, like `2024-01-31` and `2024-01-31 12:00:00.123`, in UTC.
+/// Decimals are stored as REAL.
+fn sql_type(data_type: &DataType, column_name: &str) -> Result<Option<&'static str>, Error> {
+    Ok(match data_type {
+        DataType::Null => None,
+        DataType::Boolean
+        | DataType::Int8
+        | DataType::Int16
+        | DataType::Int32
+        | DataType::Int64
+        | DataType::UInt8
+        | DataType::UInt16
+        | DataType::UInt32
+        | DataType::UInt64 => Some("INTEGER"),
+        DataType::Float16
+        | DataType::Float32
+        | DataType::Float64
+        | DataType::Decimal128(_, _)
+        | DataType::Decimal256(_, _) => Some("REAL"),
+        DataType::Utf8
+        | DataType::LargeUtf8
+        | DataType::Utf8View
+        | DataType::Date32
+        | DataType::Date64
+        | DataType::Timestamp(_, _) => Some("TEXT"),
+        DataType::Binary
+        | DataType::LargeBinary
+        | DataType::BinaryView
+        | DataType::FixedSizeBinary(_) => Some("BLOB"),
+        DataType::Dictionary(_, value_type) => sql_type(value_type, column_name)?,
+        _ => return Err(unsupported(data_type, column_name)),
+    })
+}
+
+fn unsupported(data_type: &DataType, column_name: &str) -> Error {
+    Error::Input(format!(
+        "column {column_name} has arrow type {data_type}, which is not supported"
+    ))
+}
+
+/// The rows of the batch, with rowids counting up from `first_rowid`
+pub(crate) fn records(
+    batch: &RecordBatch,
+    first_rowid: i64,
+) -> Result<impl Iterator<Item = Record>, Error> {
+    let mut columns = batch
+        .schema()
+        .fields()
+        .iter()
+        .zip(batch.columns())
+        .map(|(field, column)| column_values(column.as_ref(), field.name()).map(Vec::into_iter))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok((0..batch.num_rows() as i64).map(move |row| {
+        // every column has a value for every row
+        let values = columns.iter_mut().map(|c| c.next().unwrap()).collect();
+        // rowids beyond the largest one are rejected when the record is added
+        Record::with_values(first_rowid.saturating_add(row), values)
+    }))
+}
+
+/// Converts a whole column at once, because dictionaries are resolved per column
+fn column_values(array: &dyn Array, column_name: &str) -> Result<Vec<Value>, Error> {
+    Ok(match array.data_type() {
+        DataType::Null => vec![values::null(); array.len()],
+        DataType::Boolean => to_values(array.as_boolean().iter()),
+        DataType::Int8 => to_values(array.as_primitive::<Int8Type>().iter()),
+        DataType::Int16 => to_values(array.as_primitive::<Int16Type>().iter()),
+        DataType::Int32 => to_values(array.as_primitive::<Int32Type>().iter()),
+        DataType::Int64 => to_values(array.as_primitive::<Int64Type>().iter()),
+        DataType::UInt8 => to_values(array.as_primitive::<UInt8Type>().iter()),
+        DataType::UInt16 => to_values(array.as_primitive::<UInt16Type>().iter()),
+        DataType::UInt32 => to_values(array.as_primitive::<UInt32Type>().iter()),
+        DataType::UInt64 => array
+            .as_primitive::<UInt64Type>()
+            .iter()
+            .map(|value| match value.map(i64::try_from) {
+                Some(Ok(value)) => Ok(values::integer(value)),
+                Some(Err(_)) => Err(Error::Input(format!(
+                    "column {column_name} has a value that does not fit in an integer"
+                ))),
+                None => Ok(values::null()),
+            })
+            .collect::<Result<_, _>>()?,
+        DataType::Float16 => to_values(
+            array
+                .as_primitive::<Float16Type>()
+                .iter()
+                .map(|value| value.map(|value| value.to_f64())),
+        ),
+        DataType::Float32 => to_values(array.as_primitive::<Float32Type>().iter()),
+        DataType::Float64 => to_values(array.as_primitive::<Float64Type>().iter()),
+        DataType::Decimal128(_, _) => decimals::<Decimal128Type>(array, column_name)?,
+        DataType::Decimal256(_, _) => decimals::<Decimal256Type>(array, column_name)?,
+        DataType::Utf8 => to_values(array.as_string::<i32>().iter()),
+        DataType::LargeUtf8 => to_values(array.as_string::<i64>().iter()),
+        DataType::Utf8View => to_values(array.as_string_view().iter()),
+        DataType::Binary => to_values(array.as_binary::<i32>().iter()),
+        DataType::LargeBinary => to_values(array.as_binary::<i64>().iter()),
+        DataType::BinaryView => to_values(array.as_binary_view().iter()),
+        DataType::FixedSizeBinary(_) => to_values(array.as_fixed_size_binary().iter()),
+        DataType::Date32 => to_values(array.as_primitive::<Date32Type>().iter().map(|value| {
+            value
+                .and_then(|value| as_date::<Date32Type>(i64::from(value)))
+                .map(|date| date.to_string())
+        })),
+        DataType::Date64 => to_values(array.as_primitive::<Date64Type>().iter().map(|value| {
+            value
+                .and_then(as_date::<Date64Type>)
+                .map(|date| date.to_string())
+        })),
+        DataType::Timestamp(TimeUnit::Second, _) => timestamps::<TimestampSecondType>(array),
+        DataType::Timestamp(TimeUnit::Millisecond, _) => {
+            timestamps::<TimestampMillisecondType>(array)
+        }
+        DataType::Timestamp(TimeUnit::Microsecond, _) => {
+            timestamps::<TimestampMicrosecondType>(array)
+        }
+        DataType::Timestamp(TimeUnit::Nanosecond, _) => {
+            timestamps::<TimestampNanosecondType>(array)
+        }
+        DataType::Dictionary(_, _) => {
+            let dictionary = array.as_any_dictionary();
+            let values = column_values(dictionary.values().as_ref(), column_name)?;
+            dictionary
+                .normalized_keys()
+                .into_iter()
+                .enumerate()
+                .map(|(row, key)| {
+                    if array.is_null(row) {
+                        values::null()
+                    } else {
+                        values[key].clone()
+                    }
+                })
+                .collect()
+        }
+        data_type => return Err(unsupported(data_type, column_name)),
+    })
+}
+
+/// NULL for None
+fn to_values<T: Into<Value>>(values: impl Iterator<Item = Option<T>>) -> Vec<Value> {
+    values.map(Value::from).collect()
+}
+
+fn decimals<T: DecimalType>(array: &dyn Array, column_name: &str) -> Result<Vec<Value>, Error> {
+    let array = array.as_primitive::<T>();
+    (0..array.len())
+        .map(|row| {
+            if array.is_null(row) {
+                return Ok(values::null());
+            }
+            // the string representation is exact, the float as close as it gets
+            let decimal = array.value_as_string(row);
+            decimal.parse().map(values::float).map_err(|_| {
+                Error::Input(format!(
+                    "column {column_name} has a decimal that is not a number: {decimal}"
+                ))
+            })
+        })
+        .collect()
+}
+
+fn timestamps<T: ArrowPrimitiveType<Native = i64>>(array: &dyn Array) -> Vec<Value> {
+    to_values(array.as_primitive::<T>().iter().map(|value| {
+        value
+            .and_then(as_datetime::<T>)
+            .map(|datetime| datetime.to_string())
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow_schema::Field;
+
+    #[test]
+    fn test_create_table() -> Result<(), Error> {
+        let schema = Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("name", DataType::Utf8, true),
+            Field::new(
+                "category",
+                DataType::Dictionary(Box::new(DataType::Int8), Box::new(DataType::Utf8)),
+                true,
+            ),
+            Field::new("price", DataType::Decimal128(10, 2), true),
+        ]);
+        assert_eq!(
+            create_table("products", &schema)?,
+            "CREATE TABLE \"products\"(\"id\" INTEGER NOT NULL, \"name\" TEXT, \"category\" TEXT, \"price\" REAL)"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_unsupported_type() {
+        let list = DataType::List(Field::new("item", DataType::Int32, true).into());
+        let schema = Schema::new(vec![Field::new("list", list, true)]);
+        assert!(matches!(create_table("foo", &schema), Err(Error::Input(_))));
+    }
+}
@@ -1,17 +1,41 @@
-use crate::database::SchemaRecord;
+#[cfg(feature = "csv")]
+use crate::csv_import::{self, CsvOptions};
+use crate::database::{Database, SchemaRecord};
+use crate::error::Error;
+use crate::index::Index;
+#[cfg(feature = "json")]
+use crate::json_import;
 use crate::page::{self, Page};
-use crate::record::Record;
+use crate::record::{Record, MAX_PAYLOAD_SIZE};
+use crate::row::SqliteRow;
+use crate::sql::{self, IndexDefinition};
+use crate::table_sink::{self, Settings, TableSink};
+use crate::values::{self, TextEncoding, Value};
+use crate::varint;
 use std::mem;
 
+/// Collects tables and their records, to be turned into a [`Database`](crate::Database)
 pub struct DatabaseBuilder {
+    pub(crate) tables: Vec<TableBuilder>,
+    pub(crate) settings: Settings,
+}
+
+/// Fills the leaf pages for a single table
+pub(crate) struct TableBuilder {
+    pub schema: SchemaRecord,
     pub current_page: Page,
     pub n_records_on_current_page: u16,
+    pub last_rowid: Option<i64>,
+    /// full pages, when they are kept in memory
     pub leaf_pages: Vec<Page>,
-    pub schema: Option<SchemaRecord>,
+    pub indexes: Vec<Index>,
+    pub page_size: usize,
+    /// the position of the INTEGER PRIMARY KEY column
+    pub rowid_alias: Option<usize>,
 }
 
-fn new_page() -> Page {
-    let mut page = Page::new_leaf();
+fn new_page(page_size: usize) -> Page {
+    let mut page = Page::new_leaf(page_size);
     page.fw_position = 8;
     page
 }
@@ -19,39 +43,379 @@ fn new_page() -> Page {
 impl DatabaseBuilder {
     pub fn new() -> Self {
         Self {
-            current_page: new_page(),
+            tables: Vec::new(),
+            settings: Settings::default(),
+        }
+    }
+
+    /// Sets the encoding for all text in the database. The default is UTF-8.
+    /// It has to be set before tables are declared.
+    pub fn encoding(&mut self, encoding: TextEncoding) -> Result<(), Error> {
+        table_sink::set_encoding(self, encoding)
+    }
+
+    /// Sets the page size, a power of two between 512 and 65536. The default is 4096.
+    /// It has to be set before tables are declared.
+    pub fn page_size(&mut self, page_size: u32) -> Result<(), Error> {
+        table_sink::set_page_size(self, page_size)
+    }
+
+    /// Numbers the records of every table 1, 2, 3 and so on, in the order they are added,
+    /// instead of using the rowids of the records. It can be switched on and off at any time.
+    pub fn auto_rowid(&mut self, auto_rowid: bool) {
+        self.settings.auto_rowid = auto_rowid;
+    }
+
+    /// Adds the record to the table that was declared last.
+    /// Rowids have to be strictly increasing within a table, unless they are assigned automatically.
+    pub fn add_record(&mut self, record: Record) -> Result<(), Error> {
+        table_sink::add_record(self, record)
+    }
+
+    /// Declares a new table. All records added after this call end up in this table.
+    pub fn schema(&mut self, table_name: &str, sql: &str) -> Result<(), Error> {
+        table_sink::schema(self, table_name, sql)
+    }
+
+    /// Declares a table and fills it with the rows, which have to serialize as structs or maps.
+    /// The columns are named after the fields of the first row, and get the type of the first value
+    /// that isn't NULL. Rowids start at 1.
+    #[cfg(feature = "serde")]
+    pub fn add_rows<T: serde::Serialize>(
+        &mut self,
+        table_name: &str,
+        rows: impl IntoIterator<Item = T>,
+    ) -> Result<(), Error> {
+        table_sink::add_rows(self, table_name, rows)
+    }
+
+    /// Declares a table for the CSV and adds its rows. The column types are inferred from the cells:
+    /// INTEGER, REAL or TEXT, and empty cells are NULL.
+    #[cfg(feature = "csv")]
+    pub fn add_csv(
+        &mut self,
+        table_name: &str,
+        reader: impl std::io::Read,
+        options: &CsvOptions,
+    ) -> Result<(), Error> {
+        csv_import::add_csv(self, table_name, reader, options)
+    }
+
+    /// Declares a table for the JSON and adds its rows. The JSON is an array of objects,
+    /// or a stream of objects such as NDJSON. Every key becomes a column, and keys that are missing
+    /// from an object are NULL. Nested objects and arrays are stored as JSON text.
+    #[cfg(feature = "json")]
+    pub fn add_json(&mut self, table_name: &str, reader: impl std::io::Read) -> Result<(), Error> {
+        json_import::add_json(self, table_name, reader)
+    }
+
+    /// Declares the table for the Arrow schema.
+    /// Integers and booleans are INTEGER, floats and decimals REAL, strings, dates and timestamps
+    /// TEXT and binaries BLOB. Fields that aren't nullable are `NOT NULL`.
+    #[cfg(feature = "arrow")]
+    pub fn schema_from_arrow(
+        &mut self,
+        table_name: &str,
+        schema: &arrow_schema::Schema,
+    ) -> Result<(), Error> {
+        table_sink::schema_from_arrow(self, table_name, schema)
+    }
+
+    /// Adds the rows of the batch to the table that was declared last, with rowids after the previous one.
+    /// Nulls are stored as NULL.
+    #[cfg(feature = "arrow")]
+    pub fn add_record_batch(&mut self, batch: &arrow_array::RecordBatch) -> Result<(), Error> {
+        table_sink::add_record_batch(self, batch)
+    }
+
+    /// Declares the table for rows of type `T`
+    pub fn schema_for<T: SqliteRow>(&mut self) -> Result<(), Error> {
+        self.schema(T::table_name(), &T::create_table())
+    }
+
+    /// Adds the row to the table that was declared last, with the rowid after the previous one
+    pub fn add_row<T: SqliteRow>(&mut self, row: T) -> Result<(), Error> {
+        table_sink::add_row(self, row)
+    }
+
+    /// Declares an index as a `CREATE INDEX` statement, on a table that was declared before.
+    /// It has to be declared before any records are added to the table.
+    pub fn index(&mut self, sql: &str) -> Result<(), Error> {
+        let definition = sql::parse_create_index(sql).map_err(Error::InvalidSchema)?;
+        if self
+            .tables
+            .iter()
+            .any(|table| table.declares(&definition.name))
+        {
+            return Err(already_declared(&definition.name));
+        }
+        let table = self
+            .tables
+            .iter_mut()
+            .find(|table| table.is_named(&definition.table_name))
+            .ok_or_else(|| {
+                Error::InvalidSchema(format!("table {} is not declared", definition.table_name))
+            })?;
+        table.add_index(definition, Some(sql))
+    }
+
+    /// Lays out the b-trees of the database. Fails when a unique index has duplicate keys,
+    /// so that a [`Database`] can always be written.
+    pub fn build(self) -> Result<Database, Error> {
+        Database::try_from(self)
+    }
+}
+
+impl Default for DatabaseBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Full leaf pages stay in memory, with their table
+impl TableSink for DatabaseBuilder {
+    fn settings(&mut self) -> &mut Settings {
+        &mut self.settings
+    }
+
+    fn has_tables(&self) -> bool {
+        !self.tables.is_empty()
+    }
+
+    fn declares(&self, name: &str) -> bool {
+        self.tables.iter().any(|table| table.declares(name))
+    }
+
+    fn push_table(&mut self, table: TableBuilder) -> Result<(), Error> {
+        self.tables.push(table);
+        Ok(())
+    }
+
+    fn current_table(&mut self) -> Option<&mut TableBuilder> {
+        self.tables.last_mut()
+    }
+
+    fn full_page(&mut self, page: Page) -> Result<(), Error> {
+        if let Some(table) = self.tables.last_mut() {
+            table.leaf_pages.push(page);
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn no_table_declared() -> Error {
+    Error::InvalidSchema("records can only be added after a table is declared".to_owned())
+}
+
+#[cfg(any(feature = "serde", feature = "json"))]
+pub(crate) fn no_rows(table_name: &str) -> Error {
+    Error::InvalidSchema(format!(
+        "table {table_name} needs at least one row to derive its columns"
+    ))
+}
+
+pub(crate) fn already_declared(name: &str) -> Error {
+    Error::InvalidSchema(format!("{name} is declared twice"))
+}
+
+/// The values with the rowid in the position of the INTEGER PRIMARY KEY, which may be missing
+fn with_rowid(values: &[Value], position: usize, rowid: i64) -> Vec<Value> {
+    let mut values = values.to_vec();
+    if values.len() <= position {
+        values.resize_with(position + 1, values::null);
+    }
+    values[position] = values::integer(rowid);
+    values
+}
+
+impl TableBuilder {
+    pub(crate) fn new(table_name: &str, sql: &str, page_size: usize) -> Result<Self, Error> {
+        if table_name.is_empty() || sql::column_names(sql).is_empty() {
+            return Err(Error::InvalidSchema(format!(
+                "table {table_name} has no name or no columns: {sql}"
+            )));
+        }
+        if sql::is_without_rowid(sql) {
+            return Err(Error::InvalidSchema(format!(
+                "table {table_name} is a WITHOUT ROWID table, which is not supported"
+            )));
+        }
+        let mut table = Self {
+            schema: SchemaRecord::new_table(table_name, sql),
+            current_page: new_page(page_size),
             n_records_on_current_page: 0,
+            last_rowid: None,
             leaf_pages: Vec::new(),
-            schema: None,
+            indexes: Vec::new(),
+            page_size,
+            rowid_alias: sql::rowid_alias(sql).map_err(Error::InvalidSchema)?,
+        };
+        for definition in sql::autoindexes(table_name, sql).map_err(Error::InvalidSchema)? {
+            table.add_index(definition, None)?;
+        }
+        Ok(table)
+    }
+
+    pub(crate) fn is_named(&self, table_name: &str) -> bool {
+        self.schema.name.eq_ignore_ascii_case(table_name)
+    }
+
+    /// After the largest rowid there is no next one, and adding it again is an error
+    pub(crate) fn next_rowid(&self) -> i64 {
+        self.last_rowid.map_or(1, |rowid| rowid.saturating_add(1))
+    }
+
+    /// tables and indexes share their names
+    pub(crate) fn declares(&self, name: &str) -> bool {
+        self.is_named(name)
+            || self
+                .indexes
+                .iter()
+                .any(|index| index.schema.name.eq_ignore_ascii_case(name))
+    }
+
+    pub(crate) fn add_index(
+        &mut self,
+        definition: IndexDefinition,
+        sql: Option<&str>,
+    ) -> Result<(), Error> {
+        if self.last_rowid.is_some() {
+            return Err(Error::InvalidSchema(format!(
+                "index {} is declared after records were added to table {}",
+                definition.name, definition.table_name
+            )));
         }
+
+        let table_sql = self.schema.sql.as_deref().unwrap_or_default();
+        let column_names = sql::column_names(table_sql);
+        let column_positions = definition
+            .columns
+            .iter()
+            .map(|column| {
+                column_names
+                    .iter()
+                    .position(|name| name.eq_ignore_ascii_case(&column.name))
+                    .ok_or_else(|| {
+                        Error::InvalidSchema(format!(
+                            "no column {} in table {}",
+                            column.name, definition.table_name
+                        ))
+                    })
+            })
+            .collect::<Result<_, _>>()?;
+        let descending = definition.columns.iter().map(|c| c.descending).collect();
+        let collations =
+            sql::index_collations(&definition, table_sql).map_err(Error::InvalidSchema)?;
+
+        self.indexes.push(Index::new(
+            SchemaRecord::new_index(&definition.name, &self.schema.name, sql),
+            column_positions,
+            descending,
+            collations,
+            definition.unique,
+        ));
+        Ok(())
     }
 
-    pub fn add_record(&mut self, record: Record) {
-        if self.current_page_is_full(&record) {
+    /// Returns the previous page when it is full
+    pub(crate) fn add_record(&mut self, mut record: Record) -> Result<Option<Page>, Error> {
+        if let Some(position) = self.rowid_alias {
+            self.take_rowid_alias(&mut record, position)?;
+        }
+        let rowid = record.rowid;
+        if let Some(previous) = self.last_rowid.filter(|previous| rowid <= *previous) {
+            return Err(Error::NonMonotonicRowid {
+                table: self.schema.name.clone(),
+                previous,
+                rowid,
+            });
+        }
+        if record.bytes_len() > MAX_PAYLOAD_SIZE {
+            return Err(Error::RecordTooLarge {
+                rowid,
+                size: record.bytes_len(),
+            });
+        }
+        if record.values().is_empty() {
+            // SQLite considers a record without any columns corrupt
+            record.add_value(values::null());
+        }
+        self.last_rowid = Some(rowid);
+        let alias_values;
+        let index_values = match self.rowid_alias {
+            // indexes contain the value of the alias, which is the rowid
+            Some(position) if !self.indexes.is_empty() => {
+                alias_values = with_rowid(record.values(), position, rowid);
+                &alias_values[..]
+            }
+            _ => record.values(),
+        };
+        for index in &mut self.indexes {
+            index.add_entry(index_values, rowid);
+        }
+
+        let payload = record.into_payload();
+
+        let mut cell = varint::write(payload.len() as u64);
+        cell.append(&mut varint::write_signed(rowid));
+        let overflow_pages = page::append_payload(
+            &mut cell,
+            &payload,
+            page::max_local_table_leaf(self.page_size),
+            self.page_size,
+        );
+
+        let mut full_page = None;
+        if self.current_page_is_full(cell.len()) {
             self.finish_current_page();
-            self.leaf_pages
-                .push(mem::replace(&mut self.current_page, new_page()));
+            full_page = Some(mem::replace(
+                &mut self.current_page,
+                new_page(self.page_size),
+            ));
             self.n_records_on_current_page = 0;
         }
 
-        self.current_page.key = record.rowid; //clone?
-        let bytes: Vec<u8> = record.into();
-        self.current_page.put_bytes_bw(&bytes);
-        self.current_page.put_u16(self.current_page.bw_position);
+        self.current_page.key = rowid;
+        self.current_page.put_cell(&cell, overflow_pages);
         self.n_records_on_current_page += 1;
+        Ok(full_page)
     }
 
-    pub fn schema(&mut self, table_name: &str, sql: &str) {
-        self.schema = Some(SchemaRecord::new(1, table_name, 2, sql));
+    /// An integer in the INTEGER PRIMARY KEY column becomes the rowid, and the column is stored as NULL.
+    /// When the column is NULL or missing, the rowid of the record is kept, like SQLite assigns one.
+    fn take_rowid_alias(&self, record: &mut Record, position: usize) -> Result<(), Error> {
+        let Some(value) = record.value_mut(position) else {
+            return Ok(());
+        };
+        if value.is_null() {
+            return Ok(());
+        }
+        let rowid = value.as_integer().ok_or_else(|| {
+            Error::Input(format!(
+                "the INTEGER PRIMARY KEY of table {} has to be an integer",
+                self.schema.name
+            ))
+        })?;
+        *value = values::null();
+        record.rowid = rowid;
+        Ok(())
     }
 
-    fn current_page_is_full(&self, record: &Record) -> bool {
-        self.current_page.bw_position - record.bytes_len() <= self.current_page.fw_position + 5
+    /// there must be room for the cell and its pointer
+    fn current_page_is_full(&self, cell_len: usize) -> bool {
+        self.current_page.bw_position - self.current_page.fw_position < cell_len + 2
     }
 
     fn finish_current_page(&mut self) {
         self.current_page.fw_position = page::POSITION_CELL_COUNT;
         self.current_page.put_u16(self.n_records_on_current_page);
-        self.current_page.put_u16(self.current_page.bw_position);
+        self.current_page.put_u16(self.current_page.content_start());
+    }
+
+    /// Writes the cell count and content area of the last page and returns all leaf pages
+    pub(crate) fn finish(mut self) -> (SchemaRecord, Vec<Page>, Vec<Index>) {
+        self.finish_current_page();
+        self.leaf_pages.push(self.current_page);
+        (self.schema, self.leaf_pages, self.indexes)
     }
 }
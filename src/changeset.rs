@@ -0,0 +1,165 @@
+use crate::error::Error;
+use crate::record::Record;
+use crate::sql;
+use crate::values::{self, Value};
+use crate::varint;
+use std::cmp::Ordering;
+
+const TABLE_HEADER: u8 = b'T';
+const INSERT: u8 = 18;
+const DELETE: u8 = 9;
+const UPDATE: u8 = 23;
+/// the columns that an update doesn't change, except the primary key
+const UNDEFINED: u8 = 0;
+
+/// A record with all columns of the table, and its primary key
+struct Row {
+    key: Vec<Value>,
+    values: Vec<Value>,
+}
+
+/// Describes how the old records of a table became the new ones, as a `SQLite` session changeset
+/// that `sqlite3changeset_apply` applies to a copy of the old table.
+/// Records are matched by their PRIMARY KEY, which is the rowid when it is an INTEGER PRIMARY KEY,
+/// and the ones that differ become inserts, updates and deletes.
+///
+/// Like the session extension, records with NULL in their primary key are left out,
+/// and tables without a PRIMARY KEY can't be described. Changesets of several tables can be concatenated.
+pub fn create_changeset(
+    table_name: &str,
+    sql: &str,
+    old: &[Record],
+    new: &[Record],
+) -> Result<Vec<u8>, Error> {
+    let n_columns = sql::column_names(sql).len();
+    let primary_key = sql::primary_key(sql).map_err(Error::InvalidSchema)?;
+    if primary_key.is_empty() {
+        return Err(Error::InvalidSchema(format!(
+            "table {table_name} has no PRIMARY KEY, which a changeset needs"
+        )));
+    }
+    let rowid_alias = sql::rowid_alias(sql).map_err(Error::InvalidSchema)?;
+    let rows = |records| rows(table_name, records, n_columns, rowid_alias, &primary_key);
+    let (old, new) = (rows(old)?, rows(new)?);
+
+    // the position of each column in the primary key, counting from 1, or 0 for other columns
+    let mut bytes = vec![TABLE_HEADER];
+    bytes.append(&mut varint::write(n_columns as u64));
+    bytes.extend((0..n_columns).map(|column| {
+        primary_key
+            .iter()
+            .position(|key_column| *key_column == column)
+            .map_or(0, |position| position as u8 + 1)
+    }));
+    bytes.extend_from_slice(table_name.as_bytes());
+    bytes.push(0);
+
+    let (mut old, mut new) = (old.iter().peekable(), new.iter().peekable());
+    loop {
+        let ordering = match (old.peek(), new.peek()) {
+            (Some(old), Some(new)) => compare_keys(&old.key, &new.key),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => break,
+        };
+        match ordering {
+            Ordering::Less => {
+                let row = old.next().unwrap(); // there is one to compare
+                bytes.extend_from_slice(&[DELETE, 0]);
+                for value in &row.values {
+                    values::write_changeset_value(value, &mut bytes);
+                }
+            }
+            Ordering::Greater => {
+                let row = new.next().unwrap(); // there is one to compare
+                bytes.extend_from_slice(&[INSERT, 0]);
+                for value in &row.values {
+                    values::write_changeset_value(value, &mut bytes);
+                }
+            }
+            Ordering::Equal => {
+                let (old_row, new_row) = (old.next().unwrap(), new.next().unwrap()); // both are there
+                if old_row.values == new_row.values {
+                    continue;
+                }
+                // the old values of the key and of the columns that change, and the new values of the latter
+                bytes.extend_from_slice(&[UPDATE, 0]);
+                let columns = || old_row.values.iter().zip(&new_row.values).enumerate();
+                for (column, (old_value, new_value)) in columns() {
+                    if primary_key.contains(&column) || old_value != new_value {
+                        values::write_changeset_value(old_value, &mut bytes);
+                    } else {
+                        bytes.push(UNDEFINED);
+                    }
+                }
+                for (_, (old_value, new_value)) in columns() {
+                    if old_value != new_value {
+                        values::write_changeset_value(new_value, &mut bytes);
+                    } else {
+                        bytes.push(UNDEFINED);
+                    }
+                }
+            }
+        }
+    }
+    Ok(bytes)
+}
+
+/// The records with a value for every column, in the order of their key.
+/// Missing columns are NULL, and an INTEGER PRIMARY KEY that is NULL is the rowid.
+fn rows(
+    table_name: &str,
+    records: &[Record],
+    n_columns: usize,
+    rowid_alias: Option<usize>,
+    primary_key: &[usize],
+) -> Result<Vec<Row>, Error> {
+    let mut rows = Vec::with_capacity(records.len());
+    for record in records {
+        if record.values().len() > n_columns {
+            return Err(Error::Input(format!(
+                "record {} has more values than table {table_name} has columns",
+                record.rowid
+            )));
+        }
+        let mut values = record.values().to_vec();
+        values.resize_with(n_columns, values::null);
+        if let Some(position) = rowid_alias {
+            let value = &mut values[position];
+            if value.is_null() {
+                *value = values::integer(record.rowid);
+            } else if value.as_integer().is_none() {
+                return Err(Error::Input(format!(
+                    "the INTEGER PRIMARY KEY of table {table_name} has to be an integer"
+                )));
+            }
+        }
+        let key: Vec<Value> = primary_key
+            .iter()
+            .map(|column| values[*column].clone())
+            .collect();
+        if !key.iter().any(Value::is_null) {
+            rows.push(Row { key, values });
+        }
+    }
+
+    rows.sort_by(|left, right| compare_keys(&left.key, &right.key));
+    if let Some(duplicates) = rows
+        .windows(2)
+        .find(|rows| compare_keys(&rows[0].key, &rows[1].key).is_eq())
+    {
+        return Err(Error::Input(format!(
+            "table {table_name} has more than one record with the primary key {:?}",
+            duplicates[0].key
+        )));
+    }
+    Ok(rows)
+}
+
+fn compare_keys(left: &[Value], right: &[Value]) -> Ordering {
+    left.iter()
+        .zip(right)
+        .map(|(left, right)| values::compare(left, right))
+        .find(|ordering| ordering.is_ne())
+        .unwrap_or(Ordering::Equal)
+}
@@ -0,0 +1,264 @@
+use crate::error::Error;
+use crate::record::Record;
+use crate::sql::quote_identifier;
+use crate::table_sink::{self, TableSink};
+use crate::values::{self, Value};
+use std::collections::HashMap;
+use std::io::Read;
+
+/// The type of a column that is read from CSV
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ColumnType {
+    Integer,
+    Real,
+    Text,
+}
+
+/// Which rows determine the column types
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Inference {
+    /// The first rows. Later cells that don't match the type of their column are stored as text.
+    Sample(usize),
+    /// All rows, which are kept in memory until the types are known
+    FullPass,
+}
+
+/// How to read a CSV file
+pub struct CsvOptions {
+    has_headers: bool,
+    delimiter: u8,
+    quote: u8,
+    inference: Inference,
+    column_types: HashMap<String, ColumnType>,
+}
+
+impl CsvOptions {
+    /// Comma separated, double quoted, with a header row. Types are inferred from the first 1000 rows.
+    pub fn new() -> Self {
+        Self {
+            has_headers: true,
+            delimiter: b',',
+            quote: b'"',
+            inference: Inference::Sample(1000),
+            column_types: HashMap::new(),
+        }
+    }
+
+    /// Without a header row, the columns are named `column1`, `column2` etc.
+    pub fn has_headers(&mut self, has_headers: bool) -> &mut Self {
+        self.has_headers = has_headers;
+        self
+    }
+
+    pub fn delimiter(&mut self, delimiter: u8) -> &mut Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    pub fn quote(&mut self, quote: u8) -> &mut Self {
+        self.quote = quote;
+        self
+    }
+
+    pub fn inference(&mut self, inference: Inference) -> &mut Self {
+        self.inference = inference;
+        self
+    }
+
+    /// Sets the type of a column instead of inferring it
+    pub fn column_type(&mut self, column_name: &str, column_type: ColumnType) -> &mut Self {
+        self.column_types
+            .insert(column_name.to_owned(), column_type);
+        self
+    }
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ColumnType {
+    fn sql(self) -> &'static str {
+        match self {
+            ColumnType::Integer => "INTEGER",
+            ColumnType::Real => "REAL",
+            ColumnType::Text => "TEXT",
+        }
+    }
+
+    /// The narrowest type for a cell, None for an empty cell.
+    /// Numbers with leading zeros are text, so that eg. zip codes keep them.
+    fn of(cell: &str) -> Option<ColumnType> {
+        if cell.is_empty() {
+            None
+        } else if parse_integer(cell).is_some() {
+            Some(ColumnType::Integer)
+        } else if parse_real(cell).is_some() {
+            Some(ColumnType::Real)
+        } else {
+            Some(ColumnType::Text)
+        }
+    }
+
+    /// Cells that don't match the type of their column are stored as text
+    fn value(self, cell: &str) -> Value {
+        let number = match self {
+            _ if cell.is_empty() => return values::null(),
+            ColumnType::Integer => parse_integer(cell)
+                .map(values::integer)
+                .or_else(|| parse_real(cell).map(values::float)),
+            ColumnType::Real => parse_real(cell).map(values::float),
+            ColumnType::Text => None,
+        };
+        number.unwrap_or_else(|| values::string(cell))
+    }
+}
+
+fn has_leading_zero(cell: &str) -> bool {
+    let digits = cell.trim_start_matches(['-', '+']);
+    digits.len() > 1 && digits.starts_with('0') && !digits.starts_with("0.")
+}
+
+fn parse_integer(cell: &str) -> Option<i64> {
+    cell.parse().ok().filter(|_| !has_leading_zero(cell))
+}
+
+/// infinity and NaN are text
+fn parse_real(cell: &str) -> Option<f64> {
+    cell.parse::<f64>()
+        .ok()
+        .filter(|real| real.is_finite() && !has_leading_zero(cell))
+}
+
+/// Declares the table with the inferred types and adds all rows.
+/// Missing cells are NULL and cells without a column are ignored.
+pub(crate) fn add_csv<R: Read>(
+    sink: &mut impl TableSink,
+    table_name: &str,
+    reader: R,
+    options: &CsvOptions,
+) -> Result<(), Error> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(options.has_headers)
+        .delimiter(options.delimiter)
+        .quote(options.quote)
+        .flexible(true)
+        .from_reader(reader);
+    let headers = reader.headers()?.clone();
+    let mut rows = reader.into_records();
+
+    let n_sample_rows = match options.inference {
+        Inference::Sample(n_rows) => n_rows,
+        Inference::FullPass => usize::MAX,
+    };
+    let sample = rows
+        .by_ref()
+        .take(n_sample_rows)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let column_names = if options.has_headers {
+        column_names(headers.iter())
+    } else {
+        let n_columns = sample.iter().map(|row| row.len()).max().unwrap_or(0);
+        column_names((0..n_columns).map(|_| ""))
+    };
+    let column_types: Vec<ColumnType> = column_names
+        .iter()
+        .enumerate()
+        .map(|(position, name)| {
+            options.column_types.get(name).copied().unwrap_or_else(|| {
+                sample
+                    .iter()
+                    .filter_map(|row| ColumnType::of(row.get(position).unwrap_or_default()))
+                    .max()
+                    .unwrap_or(ColumnType::Text)
+            })
+        })
+        .collect();
+
+    let columns: Vec<String> = column_names
+        .iter()
+        .zip(&column_types)
+        .map(|(name, column_type)| format!("{} {}", quote_identifier(name), column_type.sql()))
+        .collect();
+    table_sink::schema(
+        sink,
+        table_name,
+        &format!(
+            "CREATE TABLE {}({})",
+            quote_identifier(table_name),
+            columns.join(", ")
+        ),
+    )?;
+
+    for (rowid, row) in (1..).zip(sample.into_iter().map(Ok).chain(rows)) {
+        let row = row?;
+        let values = column_types
+            .iter()
+            .enumerate()
+            .map(|(position, column_type)| column_type.value(row.get(position).unwrap_or_default()))
+            .collect();
+        table_sink::add_record(sink, Record::with_values(rowid, values))?;
+    }
+    Ok(())
+}
+
+/// Empty names become `column<n>` and names that occur more than once get a suffix
+fn column_names<'a>(headers: impl Iterator<Item = &'a str>) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for (position, header) in headers.enumerate() {
+        let name = if header.is_empty() {
+            format!("column{}", position + 1)
+        } else {
+            header.to_owned()
+        };
+        let is_taken = |name: &str| names.iter().any(|taken| taken.eq_ignore_ascii_case(name));
+        let mut unique_name = name.clone();
+        let mut suffix = 2;
+        while is_taken(&unique_name) {
+            unique_name = format!("{name}_{suffix}");
+            suffix += 1;
+        }
+        names.push(unique_name);
+    }
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_column_type_of() {
+        assert_eq!(ColumnType::of(""), None);
+        assert_eq!(ColumnType::of("-42"), Some(ColumnType::Integer));
+        assert_eq!(ColumnType::of("0"), Some(ColumnType::Integer));
+        assert_eq!(ColumnType::of("0.5"), Some(ColumnType::Real));
+        assert_eq!(ColumnType::of("1e3"), Some(ColumnType::Real));
+        assert_eq!(
+            ColumnType::of("99999999999999999999"),
+            Some(ColumnType::Real)
+        );
+        assert_eq!(ColumnType::of("01234"), Some(ColumnType::Text));
+        assert_eq!(ColumnType::of("NaN"), Some(ColumnType::Text));
+        assert_eq!(ColumnType::of("foo"), Some(ColumnType::Text));
+    }
+
+    #[test]
+    fn test_value_that_does_not_match_its_column() {
+        assert!(ColumnType::Integer.value("").is_null());
+        assert_eq!(ColumnType::Integer.value("1.5").sql_type(), Some("real"));
+        assert_eq!(ColumnType::Real.value("foo").sql_type(), Some("text"));
+        assert_eq!(ColumnType::Text.value("1").sql_type(), Some("text"));
+    }
+
+    #[test]
+    fn test_column_names() {
+        assert_eq!(
+            column_names(["id", "", "name", "NAME"].into_iter()),
+            vec!["id", "column2", "name", "NAME_2"]
+        );
+    }
+}
@@ -1,147 +1,614 @@
-use crate::builder::DatabaseBuilder;
+use crate::builder::{DatabaseBuilder, TableBuilder};
+use crate::error::Error;
 use crate::page;
 use crate::page::{Page, PageType};
+use crate::reader::FILE_HEADER_SIZE;
 use crate::record::Record;
+use crate::values::TextEncoding;
 use crate::varint;
-use std::io::{BufWriter, Error, Write};
-use std::mem;
+use byteorder::{BigEndian, ByteOrder};
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::io::Write;
 
+/// A complete database, ready to be written with [`write_sqlite`].
+/// Its b-trees are laid out already, so the size of the file is known up front.
 pub struct Database {
+    btrees: Vec<BTree>,
+    /// the file header and the root page of the schema
+    first_page: Vec<u8>,
+    /// the other pages of the schema, which go last
+    schema_pages: Vec<Vec<u8>>,
+    page_size: usize,
+    n_pages: u32,
+}
+
+/// The root page of a table or index, with its schema and the number of pages in the tree
+struct BTree {
     schema: SchemaRecord,
-    leaf_pages: Vec<Page>,
+    root_page: Page,
+    n_pages: usize,
+}
+
+impl TryFrom<DatabaseBuilder> for Database {
+    type Error = Error;
+
+    /// Fails when a unique index has duplicate keys, or when the file has more pages than `SQLite` allows
+    fn try_from(dbb: DatabaseBuilder) -> Result<Self, Error> {
+        let page_size = dbb.settings.page_size;
+        // every table is followed by its indexes
+        let mut btrees = Vec::new();
+        for table in dbb.tables {
+            let (schema, leaf_pages, indexes) = table.finish();
+            let mut current_top_layer = leaf_pages;
+            let mut n_table_pages = current_top_layer.len();
+            while current_top_layer.len() > 1 {
+                // table needs interior pages?
+                current_top_layer = create_interior_pages(current_top_layer, page_size);
+                n_table_pages += current_top_layer.len();
+            }
+            btrees.push(BTree {
+                schema,
+                // assert(won't panic): a table always has at least one leaf page
+                root_page: current_top_layer.remove(0),
+                n_pages: n_table_pages,
+            });
+
+            for index in indexes {
+                let (schema, root_page, n_pages) =
+                    index.into_btree(page_size, dbb.settings.encoding)?;
+                btrees.push(BTree {
+                    schema,
+                    root_page,
+                    n_pages,
+                });
+            }
+        }
+
+        let mut page_counter: u32 = 1; // the first page
+        let mut schema = Vec::with_capacity(btrees.len());
+        for btree in &btrees {
+            schema.push(SchemaRecord {
+                root_page: page_counter + 1,
+                ..btree.schema.clone()
+            });
+            page_counter += btree.n_pages as u32;
+        }
+        let n_pages = u64::from(page_counter)
+            + btrees
+                .iter()
+                .map(|btree| count_overflow_pages(&btree.root_page) as u64)
+                .sum::<u64>();
+        let first_schema_page = check_page_count(n_pages)? + 1;
+        let (first_page, schema_pages) =
+            create_schema_pages(schema, first_schema_page, dbb.settings.encoding, page_size)?;
+        Ok(Self {
+            btrees,
+            first_page,
+            n_pages: first_schema_page - 1 + schema_pages.len() as u32,
+            schema_pages,
+            page_size,
+        })
+    }
 }
 
 impl Database {
-    pub fn new(schema: SchemaRecord, leaf_pages: Vec<Page>) -> Self {
-        Self { schema, leaf_pages }
+    /// The exact size of the file in bytes, for instance for a `Content-Length` header
+    pub fn size(&self) -> u64 {
+        u64::from(self.n_pages) * self.page_size as u64
+    }
+
+    /// The file as bytes
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        let mut bytes = Vec::with_capacity(self.size() as usize);
+        self.write(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    /// The file as `bytes::Bytes`, for instance for a response body
+    #[cfg(feature = "bytes")]
+    pub fn to_bytes_bytes(&self) -> Result<bytes::Bytes, Error> {
+        self.to_bytes().map(bytes::Bytes::from)
+    }
+
+    fn write<W: Write>(&self, writer: &mut W) -> Result<(), Error> {
+        let mut pages = self.pages();
+        while let Some(page) = pages.next(self) {
+            writer.write_all(&page)?;
+        }
+        Ok(())
+    }
+
+    /// The pages of the file, which are produced one at a time
+    pub(crate) fn pages(&self) -> FilePages {
+        let n_btree_pages: u32 = self.btrees.iter().map(|btree| btree.n_pages as u32).sum();
+        FilePages {
+            first_page_done: false,
+            // overflow pages go after all b-tree pages
+            btree_pages: BTreePages::new(2, 2 + n_btree_pages),
+            next_schema_page: 0,
+        }
     }
 }
 
-impl From<DatabaseBuilder> for Database {
-    fn from(mut dbb: DatabaseBuilder) -> Self {
-        dbb.current_page.fw_position = page::POSITION_CELL_COUNT;
-        dbb.current_page.put_u16(dbb.n_records_on_current_page);
+/// The pages of a [`Database`] in the order of the file: the first page, the b-trees, their overflow pages
+/// and the rest of the schema.
+/// They borrow from the database, which is passed to [`FilePages::next`],
+/// so that a response body can own both.
+pub(crate) struct FilePages {
+    first_page_done: bool,
+    btree_pages: BTreePages,
+    next_schema_page: usize,
+}
 
-        if dbb.n_records_on_current_page > 0 {
-            dbb.current_page.put_u16(dbb.current_page.bw_position);
-        } else {
-            dbb.current_page.put_u16(dbb.current_page.bw_position - 1);
+impl FilePages {
+    pub(crate) fn next<'a>(&mut self, database: &'a Database) -> Option<Cow<'a, [u8]>> {
+        if !self.first_page_done {
+            self.first_page_done = true;
+            return Some(Cow::Borrowed(&database.first_page));
         }
+        if let Some(page) = self
+            .btree_pages
+            .next(|btree| database.btrees.get(btree).map(|btree| &btree.root_page))
+        {
+            return Some(page);
+        }
+        let page = database.schema_pages.get(self.next_schema_page)?;
+        self.next_schema_page += 1;
+        Some(Cow::Borrowed(page))
+    }
+}
+
+#[cfg(feature = "bytes")]
+impl TryFrom<&Database> for bytes::Bytes {
+    type Error = Error;
 
-        dbb.leaf_pages.push(dbb.current_page);
-        Database::new(dbb.schema.unwrap_or_default(), dbb.leaf_pages)
+    fn try_from(database: &Database) -> Result<Self, Error> {
+        database.to_bytes_bytes()
     }
 }
 
-pub fn write_sqlite<W: Write>(database: Database, mut writer: BufWriter<W>) -> Result<(), Error> {
-    let mut current_top_layer = database.leaf_pages;
-    let mut n_pages = current_top_layer.len();
-    while current_top_layer.len() > 1 {
-        // db needs interior pages?
-        current_top_layer = create_interior_pages(current_top_layer);
-        n_pages += current_top_layer.len();
+/// Writes the database in the `SQLite` file format
+pub fn write_sqlite<W: Write>(database: Database, mut writer: W) -> Result<(), Error> {
+    database.write(&mut writer)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Writes the database in the `SQLite` file format to an async writer, one page at a time,
+/// so that each page waits until the writer is ready for it
+#[cfg(feature = "tokio")]
+pub async fn write_sqlite_async<W: tokio::io::AsyncWrite + Unpin>(
+    database: Database,
+    mut writer: W,
+) -> Result<(), Error> {
+    use tokio::io::AsyncWriteExt;
+
+    let mut pages = database.pages();
+    while let Some(page) = pages.next(&database) {
+        writer.write_all(&page).await?;
+    }
+    writer.flush().await?;
+    Ok(())
+}
+
+/// Page sizes are powers of two between 512 and 65536
+pub(crate) fn check_page_size(page_size: u32) -> Result<usize, Error> {
+    let page_size = page_size as usize;
+    if page_size.is_power_of_two() && (MIN_PAGE_SIZE..=MAX_PAGE_SIZE).contains(&page_size) {
+        Ok(page_size)
+    } else {
+        Err(Error::InvalidPageSize(page_size as u32))
     }
+}
 
-    // assert(won't panic)
-    let table_root_page = current_top_layer.get_mut(0).unwrap();
-    writer.write_all(&create_header_page((n_pages + 1) as u32, database.schema).data)?; // 1 for header page
+/// Page numbers are 32 bits, but `SQLite` doesn't allow the largest one
+pub(crate) const MAX_PAGE_COUNT: u64 = 4_294_967_294;
 
-    set_childrefs_write(table_root_page, &mut writer, 3)
+pub(crate) fn check_page_count(n_pages: u64) -> Result<u32, Error> {
+    if n_pages > MAX_PAGE_COUNT {
+        Err(Error::Capacity(format!(
+            "{n_pages} pages, the maximum is {MAX_PAGE_COUNT}"
+        )))
+    } else {
+        Ok(n_pages as u32)
+    }
 }
 
-fn set_childrefs_write<W: Write>(
-    page: &mut Page,
-    writer: &mut BufWriter<W>,
-    mut page_counter: u32,
+/// Writes the b-tree, followed by its overflow pages, which are numbered from `overflow_page_counter`
+pub(crate) fn set_childrefs_write<W: Write>(
+    root: &Page,
+    writer: &mut W,
+    root_page_number: u32,
+    overflow_page_counter: &mut u32,
 ) -> Result<(), Error> {
-    if let PageType::Interior = page.page_type {
-        page.fw_position = page::POSITION_CELL_COUNT;
-        page.put_u16((page.children.len() - 1) as u16);
+    let mut pages = BTreePages::new(root_page_number, *overflow_page_counter);
+    while let Some(page) = pages.next(|btree| (btree == 0).then_some(root)) {
+        writer.write_all(&page)?;
+    }
+    *overflow_page_counter = pages.overflow_page_counter;
+    Ok(())
+}
+
+/// Produces the pages of consecutive b-trees one at a time. Each b-tree is walked breadth first,
+/// so that page numbers can be assigned to the children of an interior page before the page itself is produced.
+/// The page numbers are set in a copy of the page, so pages without any are not copied.
+/// The overflow pages of all b-trees follow, in a second walk in the same order.
+pub(crate) struct BTreePages {
+    btree_walk: BreadthFirst,
+    overflow_walk: BreadthFirst,
+    /// the page whose overflow pages are produced, the chain and the position in the chain
+    overflow_position: Option<(Position, usize, usize)>,
+    page_counter: u32,
+    overflow_page_counter: u32,
+    overflow_page_number: u32,
+}
 
-        for index in 0..page.children.len() - 1 {
-            page.fw_position = page::START_OF_INTERIOR_PAGE + (index as u16) * 2;
-            page.fw_position = page.get_u16();
-            page.put_u32(page_counter);
-            page_counter += 1;
+impl BTreePages {
+    pub(crate) fn new(root_page_number: u32, overflow_page_counter: u32) -> Self {
+        Self {
+            btree_walk: BreadthFirst::default(),
+            overflow_walk: BreadthFirst::default(),
+            overflow_position: None,
+            page_counter: root_page_number,
+            overflow_page_counter,
+            overflow_page_number: overflow_page_counter,
         }
+    }
 
-        page.fw_position = page::POSITION_RIGHTMOST_POINTER_LEAFPAGES;
-        page.put_u32(page_counter);
-        page_counter += 1;
+    /// The next page, for the root pages that `roots` returns by the number of their b-tree
+    pub(crate) fn next<'a>(
+        &mut self,
+        roots: impl Fn(usize) -> Option<&'a Page> + Copy,
+    ) -> Option<Cow<'a, [u8]>> {
+        match self.btree_walk.next(roots) {
+            Some(((_, path), page)) => {
+                if path.is_empty() {
+                    self.page_counter += 1; // the root page of the next b-tree
+                }
+                Some(self.set_childrefs(page))
+            }
+            None => self.next_overflow_page(roots),
+        }
     }
 
-    writer.write_all(&page.data)?;
+    fn set_childrefs<'a>(&mut self, page: &'a Page) -> Cow<'a, [u8]> {
+        let mut data = Cow::Borrowed(&page.data[..]);
+        if let PageType::Interior = page.page_type {
+            let data = data.to_mut();
+            let n_cells = page.children.len() - 1;
+            BigEndian::write_u16(&mut data[page::POSITION_CELL_COUNT..], n_cells as u16);
+
+            for index in 0..n_cells {
+                let cell_pointer = page::START_OF_INTERIOR_PAGE + index * 2;
+                let cell_position = BigEndian::read_u16(&data[cell_pointer..]) as usize;
+                BigEndian::write_u32(&mut data[cell_position..], self.page_counter);
+                self.page_counter += 1;
+            }
+
+            BigEndian::write_u32(
+                &mut data[page::POSITION_RIGHTMOST_POINTER_LEAFPAGES..],
+                self.page_counter,
+            );
+            self.page_counter += 1;
+        }
 
-    for child in &mut page.children {
-        set_childrefs_write(child, writer, page_counter)?;
+        for chain in &page.overflow {
+            BigEndian::write_u32(
+                &mut data.to_mut()[chain.pointer_position..],
+                self.overflow_page_counter,
+            );
+            self.overflow_page_counter += chain.pages.len() as u32;
+        }
+        data
+    }
+
+    /// Overflow pages start with the number of the next page in their chain
+    fn next_overflow_page<'a>(
+        &mut self,
+        roots: impl Fn(usize) -> Option<&'a Page> + Copy,
+    ) -> Option<Cow<'a, [u8]>> {
+        loop {
+            if let Some((position, chain, index)) = &mut self.overflow_position {
+                if let Some(chain_pages) = page_at(roots, position)?
+                    .overflow
+                    .get(*chain)
+                    .map(|chain| &chain.pages)
+                {
+                    let Some(overflow_page) = chain_pages.get(*index) else {
+                        *chain += 1;
+                        *index = 0;
+                        continue;
+                    };
+                    *index += 1;
+                    self.overflow_page_number += 1;
+                    // the last page in the chain has no next page
+                    let next_page = if *index < chain_pages.len() {
+                        self.overflow_page_number
+                    } else {
+                        0
+                    };
+                    let mut data = Cow::Borrowed(&overflow_page.data[..]);
+                    if data[..4] != next_page.to_be_bytes() {
+                        BigEndian::write_u32(data.to_mut(), next_page);
+                    }
+                    return Some(data);
+                }
+            }
+            let (position, _) = self.overflow_walk.next(roots)?;
+            self.overflow_position = Some((position, 0, 0));
+        }
+    }
+}
+
+/// A page as the number of its b-tree and the child indexes on the way down from the root page
+type Position = (usize, Vec<usize>);
+
+/// The pages of consecutive b-trees, breadth first
+#[derive(Default)]
+struct BreadthFirst {
+    next_btree: usize,
+    queue: VecDeque<Position>,
+}
+
+impl BreadthFirst {
+    fn next<'a>(
+        &mut self,
+        roots: impl Fn(usize) -> Option<&'a Page> + Copy,
+    ) -> Option<(Position, &'a Page)> {
+        if self.queue.is_empty() {
+            roots(self.next_btree)?;
+            self.queue.push_back((self.next_btree, Vec::new()));
+            self.next_btree += 1;
+        }
+        let position = self.queue.pop_front()?;
+        let page = page_at(roots, &position)?;
+        for child in 0..page.children.len() {
+            let mut path = position.1.clone();
+            path.push(child);
+            self.queue.push_back((position.0, path));
+        }
+        Some((position, page))
+    }
+}
+
+fn page_at<'a>(
+    roots: impl Fn(usize) -> Option<&'a Page>,
+    (btree, path): &Position,
+) -> Option<&'a Page> {
+    path.iter()
+        .try_fold(roots(*btree)?, |page, child| page.children.get(*child))
+}
+
+fn count_overflow_pages(page: &Page) -> usize {
+    let n_overflow_pages: usize = page.overflow.iter().map(|chain| chain.pages.len()).sum();
+    n_overflow_pages
+        + page
+            .children
+            .iter()
+            .map(count_overflow_pages)
+            .sum::<usize>()
+}
+
+/// Assigns page numbers to the overflow chains of the page, in its data,
+/// and collects the overflow pages, so that they can be written after the page
+pub(crate) fn set_overflow_refs<'a>(
+    page: &'a Page,
+    data: &mut Cow<[u8]>,
+    page_counter: &mut u32,
+    overflow_pages: &mut Vec<(&'a Page, u32)>,
+) {
+    for chain in &page.overflow {
+        BigEndian::write_u32(&mut data.to_mut()[chain.pointer_position..], *page_counter);
+        for overflow_page in &chain.pages {
+            *page_counter += 1;
+            overflow_pages.push((overflow_page, *page_counter));
+        }
+        // the last page in the chain has no next page
+        if let Some((_, next_page)) = overflow_pages.last_mut() {
+            *next_page = 0;
+        }
+    }
+}
+
+/// Writes the overflow pages, starting with the number of the next page in their chain
+pub(crate) fn write_overflow_pages<W: Write>(
+    overflow_pages: &[(&Page, u32)],
+    writer: &mut W,
+) -> Result<(), Error> {
+    for (overflow_page, next_page) in overflow_pages {
+        writer.write_all(&next_page.to_be_bytes())?;
+        writer.write_all(&overflow_page.data[4..])?;
     }
     Ok(())
 }
 
-fn create_header_page(n_pages: u32, schema: SchemaRecord) -> Page {
-    let mut header_page = Page::new_root();
-    write_header(&mut header_page, n_pages);
+/// `sqlite_master` is a table b-tree like any other, with its root page on the first page, after the file header.
+/// When the schema doesn't fit there, its other pages and their overflow pages are numbered from
+/// `first_page_number`, to go after all other pages. Returns the first page and those other pages.
+pub(crate) fn create_schema_pages(
+    schema: Vec<SchemaRecord>,
+    first_page_number: u32,
+    encoding: TextEncoding,
+    page_size: usize,
+) -> Result<(Vec<u8>, Vec<Vec<u8>>), Error> {
+    let mut table = TableBuilder::new("sqlite_master", SQLITE_MASTER_SQL, page_size)?;
+    let mut leaf_pages = Vec::new();
+    for (rowid, schema_record) in (1..).zip(schema) {
+        let mut record: Record = SchemaRecord {
+            rowid,
+            ..schema_record
+        }
+        .into();
+        record.encode_text(encoding);
+        leaf_pages.extend(table.add_record(record)?);
+    }
+    let (_, last_leaf_pages, _) = table.finish();
+    leaf_pages.extend(last_leaf_pages);
+
+    let mut top_layer = leaf_pages;
+    let mut n_btree_pages = top_layer.len();
+    // a single child that doesn't fit is referred to by the rightmost pointer of a root page without cells,
+    // which SQLite allows for the first page only
+    while top_layer.len() > 1 || !fits_on_first_page(&top_layer[0]) {
+        top_layer = create_interior_pages(top_layer, page_size);
+        n_btree_pages += top_layer.len();
+    }
+    let root_page = top_layer.remove(0);
+
+    // the root page is numbered just before the other pages, so that they follow it
+    let mut btree_pages = BTreePages::new(
+        first_page_number - 1,
+        first_page_number - 1 + n_btree_pages as u32,
+    );
+    let roots = |btree| (btree == 0).then_some(&root_page);
+    let root_data = btree_pages.next(roots).unwrap(); // a b-tree has a root page
+    let pages: Vec<Vec<u8>> = std::iter::from_fn(|| btree_pages.next(roots))
+        .map(Cow::into_owned)
+        .collect();
+    let n_pages = check_page_count(u64::from(first_page_number) - 1 + pages.len() as u64)?;
+
+    // the page header and the cell pointers move behind the file header, the cells stay where they are
+    let mut first_page = Page::new_root(page_size);
+    let pointers_end = pointers_end(&root_page);
+    first_page.data[FILE_HEADER_SIZE..FILE_HEADER_SIZE + pointers_end]
+        .copy_from_slice(&root_data[..pointers_end]);
+    let cells_start = FILE_HEADER_SIZE + pointers_end;
+    first_page.data[cells_start..].copy_from_slice(&root_data[cells_start..]);
+    write_header(&mut first_page, n_pages, encoding, page_size);
+    Ok((first_page.data, pages))
+}
+
+const SQLITE_MASTER_SQL: &str =
+    "CREATE TABLE sqlite_master(type text, name text, tbl_name text, rootpage integer, sql text)";
+
+/// The end of the page header and the cell pointers of a table b-tree page
+fn pointers_end(page: &Page) -> usize {
+    match page.page_type {
+        PageType::Interior => page::START_OF_INTERIOR_PAGE + 2 * (page.children.len() - 1),
+        _ => 8 + 2 * BigEndian::read_u16(&page.data[page::POSITION_CELL_COUNT..]) as usize,
+    }
+}
+
+/// The first page has room for the file header less
+fn fits_on_first_page(page: &Page) -> bool {
+    FILE_HEADER_SIZE + pointers_end(page) <= page.bw_position
+}
+
+fn create_interior_pages(child_pages: Vec<Page>, page_size: usize) -> Vec<Page> {
+    let keys: Vec<i64> = child_pages.iter().map(|page| page.key).collect();
+    let group_sizes = group_child_pages(&keys, page_size);
+    let mut child_pages = child_pages.into_iter();
+    let mut interior_pages = Vec::with_capacity(group_sizes.len());
 
-    let payload_location_write_location = header_page.fw_position; // mark current position
+    for n_children in group_sizes {
+        let mut interior_page = Page::new_interior(page_size);
+        interior_page.fw_position = page::START_OF_INTERIOR_PAGE;
+        for _ in 0..n_children - 1 {
+            let child_page = child_pages.next().unwrap(); // group sizes add up to the number of children
+            create_cell(&mut interior_page, child_page.key, 0); // child page number is set when the page is written
+            interior_page.add_child(child_page);
+        }
 
-    let payload_location = write_schema(&mut header_page, schema); //write schema payload from the end
-    header_page.fw_position = payload_location_write_location; // go back to marked position
-    header_page.put_u16(payload_location); //payload start
-    header_page.put_u8(0); // the number of fragmented free bytes within the cell content area
-    header_page.put_u16(payload_location); // first cell
-    header_page
+        // the last child is referred to by the rightmost pointer and doesn't need a cell
+        let rightmost_child = child_pages.next().unwrap();
+        interior_page.key = rightmost_child.key;
+        interior_page.fw_position = page::START_OF_CONTENT_AREA;
+        interior_page.put_u16(interior_page.content_start());
+        interior_page.add_child(rightmost_child);
+        interior_pages.push(interior_page);
+    }
+    interior_pages
 }
 
-fn write_schema(root_page: &mut Page, schema_record: SchemaRecord) -> u16 {
-    let record: Record = schema_record.into();
-    let bytes: Vec<u8> = record.into();
-    root_page.put_bytes_bw(&bytes);
-    root_page.bw_position
+/// Creates the interior pages for child pages that were written already, and writes them.
+/// Returns the key and page number of each new interior page.
+pub(crate) fn write_interior_pages<W: Write>(
+    child_refs: &[(i64, u32)],
+    writer: &mut W,
+    page_counter: &mut u32,
+    page_size: usize,
+) -> Result<Vec<(i64, u32)>, Error> {
+    let keys: Vec<i64> = child_refs.iter().map(|(key, _)| *key).collect();
+    let mut start = 0;
+    let mut interior_refs = Vec::new();
+
+    for n_children in group_child_pages(&keys, page_size) {
+        let group = &child_refs[start..start + n_children];
+        start += n_children;
+        writer.write_all(&interior_page(group, page_size).data)?;
+        interior_refs.push((group[n_children - 1].0, *page_counter));
+        *page_counter += 1;
+    }
+    Ok(interior_refs)
 }
 
-fn create_interior_pages(child_pages: Vec<Page>) -> Vec<Page> {
-    let mut interior_pages = Vec::new();
-    let mut interior_page = Page::new_interior();
-    interior_page.key = child_pages.iter().map(|p| p.key).max().unwrap_or(0);
+/// An interior page for child pages with known page numbers.
+/// The last child is referred to by the rightmost pointer.
+pub(crate) fn interior_page(child_refs: &[(i64, u32)], page_size: usize) -> Page {
+    let mut interior_page = Page::new_interior(page_size);
     interior_page.fw_position = page::START_OF_INTERIOR_PAGE;
-    let children_length = child_pages.len();
-    let mut last_leaf: Page = Page::new_leaf(); // have to assign :(
-    for (child_count, leaf_page) in child_pages.into_iter().enumerate() {
-        if child_count < children_length - 1 {
-            if interior_page.bw_position <= interior_page.fw_position + 15 {
-                // 15 is somewhat arbitrary, but safe
-                interior_page.fw_position = page::START_OF_CONTENT_AREA;
-                interior_page.put_u16(interior_page.bw_position);
-                interior_page.fw_position += 5;
-                interior_pages.push(mem::replace(&mut interior_page, Page::new_interior()));
-                interior_page.fw_position = page::START_OF_INTERIOR_PAGE;
-            }
-            create_cell(&mut interior_page, &leaf_page);
-            interior_page.add_child(leaf_page);
+    let (rightmost, cells) = child_refs.split_last().unwrap(); // an interior page has children
+    for (key, child_page_number) in cells {
+        create_cell(&mut interior_page, *key, *child_page_number);
+    }
+
+    interior_page.fw_position = page::POSITION_CELL_COUNT;
+    interior_page.put_u16(cells.len() as u16);
+    interior_page.put_u16(interior_page.content_start());
+    interior_page.fw_position = page::POSITION_RIGHTMOST_POINTER_LEAFPAGES;
+    interior_page.put_u32(rightmost.1);
+    interior_page
+}
+
+/// Determines how many children go in each interior page.
+/// Every interior page gets at least 2 children, so that it never ends up without cells.
+pub(crate) fn group_child_pages(keys: &[i64], page_size: usize) -> Vec<usize> {
+    let capacity = page_size - page::START_OF_INTERIOR_PAGE;
+    let mut group_sizes = Vec::new();
+    let mut n_children = 0;
+    let mut bytes_used = 0;
+
+    for key in keys {
+        let cell_len = 4 + varint::write_signed(*key).len() + 2; // child pointer, key and cell pointer
+        if n_children > 0 && bytes_used + cell_len > capacity {
+            // no room for another cell, so this child becomes the rightmost pointer
+            group_sizes.push(n_children + 1);
+            n_children = 0;
+            bytes_used = 0;
         } else {
-            last_leaf = leaf_page;
+            n_children += 1;
+            bytes_used += cell_len;
         }
     }
+    if n_children > 0 {
+        group_sizes.push(n_children);
+    }
 
-    interior_page.fw_position = page::START_OF_CONTENT_AREA;
-    interior_page.put_u16(interior_page.bw_position);
-    interior_page.fw_position += 5;
-    interior_page.add_child(last_leaf);
-    interior_pages.push(interior_page);
-    interior_pages
+    let n_groups = group_sizes.len();
+    if n_groups > 1 && group_sizes[n_groups - 1] == 1 {
+        group_sizes[n_groups - 2] -= 1;
+        group_sizes[n_groups - 1] += 1;
+    }
+    group_sizes
 }
 
-fn create_cell(interior_page: &mut Page, child_page: &Page) {
-    let mut cell: Vec<u8> = vec![0; 5];
-    cell.append(&mut varint::write(child_page.key));
+fn create_cell(interior_page: &mut Page, key: i64, child_page_number: u32) {
+    let mut cell: Vec<u8> = child_page_number.to_be_bytes().to_vec(); // left child pointer
+    cell.append(&mut varint::write_signed(key));
 
     interior_page.put_bytes_bw(&cell);
-    interior_page.put_u16(interior_page.bw_position);
+    interior_page.put_u16(interior_page.bw_position as u16);
 }
 
-fn write_header(rootpage: &mut Page, n_pages: u32) {
+fn write_header(rootpage: &mut Page, n_pages: u32, encoding: TextEncoding, page_size: usize) {
     rootpage.put_bytes(&MAGIC_HEADER);
-    rootpage.put_u16(DEFAULT_PAGE_SIZE);
+    // 65536 doesn't fit in 2 bytes and is written as 1
+    rootpage.put_u16(if page_size == MAX_PAGE_SIZE {
+        1
+    } else {
+        page_size as u16
+    });
     rootpage.put_u8(FILE_FORMAT_WRITE_VERSION);
     rootpage.put_u8(FILE_FORMAT_READ_VERSION);
     rootpage.put_u8(RESERVED_SIZE);
@@ -156,51 +623,59 @@ fn write_header(rootpage: &mut Page, n_pages: u32) {
     rootpage.put_u32(SQLITE_SCHEMAVERSION);
     rootpage.put_u32(SUGGESTED_CACHESIZE);
     rootpage.put_u32(LARGEST_ROOT_BTREE_PAGE);
-    rootpage.put_u32(ENCODING_UTF8);
+    rootpage.put_u32(encoding.header_value());
     rootpage.put_u32(USER_VERSION);
     rootpage.put_u32(VACUUM_MODE_OFF); // True (non-zero) for incremental-vacuum mode. False (zero) otherwise.
     rootpage.put_u32(APP_ID); // Application ID
     rootpage.put_bytes(&FILLER); // Reserved for expansion. Must be zero.
     rootpage.put_bytes(&VERSION_VALID_FOR); // The version-valid-for number
     rootpage.put_bytes(&SQLITE_VERSION); // SQLITE_VERSION_NUMBER
-    rootpage.put_u8(TABLE_LEAF_PAGE); // leaf table b-tree page for schema
-    rootpage.put_u16(NO_FREE_BLOCKS); // zero if there are no freeblocks
-    rootpage.put_u16(1); // the number of cells on this page
 }
 
-pub struct SchemaRecord {
-    pub rowid: u64,
+/// An entry in `sqlite_master`
+#[derive(Clone)]
+pub(crate) struct SchemaRecord {
+    pub rowid: i64,
+    pub object_type: &'static str,
+    pub name: String,
     pub table_name: String,
     pub root_page: u32,
-    pub sql: String,
+    pub sql: Option<String>,
 }
 
-impl Default for SchemaRecord {
-    fn default() -> Self {
+impl SchemaRecord {
+    /// rowid and root page are assigned when the database is written
+    pub fn new_table(table_name: &str, sql: &str) -> Self {
         Self {
             rowid: 0,
-            table_name: "".to_owned(),
-            root_page: 3,
-            sql: "".to_owned(),
+            object_type: "table",
+            name: table_name.to_owned(),
+            table_name: table_name.to_owned(),
+            root_page: 0,
+            sql: Some(sql.to_owned()),
         }
     }
-}
 
-impl SchemaRecord {
-    pub fn new(rowid: u64, table_name: &str, root_page: u32, sql: &str) -> Self {
+    /// Automatic indexes for PRIMARY KEY and UNIQUE constraints have no sql.
+    /// rowid and root page are assigned when the database is written
+    pub fn new_index(index_name: &str, table_name: &str, sql: Option<&str>) -> Self {
         Self {
-            rowid,
+            rowid: 0,
+            object_type: "index",
+            name: index_name.to_owned(),
             table_name: table_name.to_owned(),
-            root_page,
-            sql: sql.to_owned(),
+            root_page: 0,
+            sql: sql.map(str::to_owned),
         }
     }
 }
 
-const MAGIC_HEADER: [u8; 16] = [
+pub(crate) const MAGIC_HEADER: [u8; 16] = [
     0x53, 0x51, 0x4c, 0x69, 0x74, 0x65, 0x20, 0x66, 0x6f, 0x72, 0x6d, 0x61, 0x74, 0x20, 0x33, 0x00,
 ];
-pub const DEFAULT_PAGE_SIZE: u16 = 4096;
+pub const DEFAULT_PAGE_SIZE: usize = 4096;
+pub const MIN_PAGE_SIZE: usize = 512;
+pub const MAX_PAGE_SIZE: usize = 65536;
 const FILE_FORMAT_WRITE_VERSION: u8 = 1;
 const FILE_FORMAT_READ_VERSION: u8 = 1;
 const RESERVED_SIZE: u8 = 0;
@@ -214,7 +689,6 @@ const SCHEMA_COOKIE: u32 = 1;
 const SQLITE_SCHEMAVERSION: u32 = 4;
 const SUGGESTED_CACHESIZE: u32 = 0;
 const LARGEST_ROOT_BTREE_PAGE: u32 = 0;
-const ENCODING_UTF8: u32 = 1;
 const USER_VERSION: u32 = 0;
 const VACUUM_MODE_OFF: u32 = 0;
 const APP_ID: u32 = 0;
@@ -224,8 +698,7 @@ const FILLER: [u8; 20] = [
 ];
 const VERSION_VALID_FOR: [u8; 4] = [0, 0, 0x03, 250];
 const SQLITE_VERSION: [u8; 4] = [0x00, 0x2e, 0x5F, 0x1A];
-const NO_FREE_BLOCKS: u16 = 0;
 pub const TABLE_LEAF_PAGE: u8 = 0x0d;
 pub const TABLE_INTERIOR_PAGE: u8 = 0x05;
-const INDEX_LEAF_PAGE: u8 = 0x0a;
-const INDEX_INTERIOR_PAGE: u8 = 0x02;
+pub const INDEX_LEAF_PAGE: u8 = 0x0a;
+pub const INDEX_INTERIOR_PAGE: u8 = 0x02;
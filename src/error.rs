@@ -0,0 +1,106 @@
+use std::fmt;
+use std::io;
+
+/// Everything that can go wrong while building, writing or reading a database
+#[derive(Debug)]
+pub enum Error {
+    /// Writing to the sink failed
+    Io(io::Error),
+    /// The record is bigger than `SQLite` can read
+    RecordTooLarge { rowid: i64, size: usize },
+    /// Rowids have to be added in strictly increasing order, because the b-tree is built as records come in
+    NonMonotonicRowid {
+        table: String,
+        previous: i64,
+        rowid: i64,
+    },
+    /// A table or index definition that can't be used, or a builder method called at the wrong time
+    InvalidSchema(String),
+    /// The page size is not a power of two between 512 and 65536
+    InvalidPageSize(u32),
+    /// Two records have the same key in a unique index
+    DuplicateKey { index: String },
+    /// A row that can't be turned into a record, eg. a field with a nested struct
+    Serialize(String),
+    /// Input that can't be read, eg. malformed CSV
+    Input(String),
+    /// The database does not fit in the file format, eg. too many pages
+    Capacity(String),
+    /// A file that can't be read as a `SQLite` database, with the page where reading went wrong
+    Corrupt { page: u32, message: String },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(error) => write!(f, "i/o error: {error}"),
+            Error::RecordTooLarge { rowid, size } => {
+                write!(f, "record {rowid} is too large: {size} bytes")
+            }
+            Error::NonMonotonicRowid {
+                table,
+                previous,
+                rowid,
+            } => write!(
+                f,
+                "rowid {rowid} in table {table} is not greater than the previous rowid {previous}"
+            ),
+            Error::InvalidSchema(message) => write!(f, "invalid schema: {message}"),
+            Error::InvalidPageSize(page_size) => write!(
+                f,
+                "invalid page size {page_size}, it has to be a power of two between 512 and 65536"
+            ),
+            Error::DuplicateKey { index } => write!(f, "duplicate key in unique index {index}"),
+            Error::Serialize(message) => write!(f, "can't serialize row: {message}"),
+            Error::Input(message) => write!(f, "invalid input: {message}"),
+            Error::Capacity(message) => write!(f, "capacity exceeded: {message}"),
+            Error::Corrupt { page, message } => {
+                write!(f, "corrupt database, page {page}: {message}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::ser::Error for Error {
+    fn custom<T: fmt::Display>(message: T) -> Self {
+        Error::Serialize(message.to_string())
+    }
+}
+
+#[cfg(feature = "csv")]
+impl From<csv::Error> for Error {
+    fn from(error: csv::Error) -> Self {
+        let message = error.to_string();
+        match error.into_kind() {
+            csv::ErrorKind::Io(error) => Error::Io(error),
+            _ => Error::Input(message),
+        }
+    }
+}
+
+#[cfg(feature = "json")]
+impl From<serde_json::Error> for Error {
+    fn from(error: serde_json::Error) -> Self {
+        if error.is_io() {
+            Error::Io(error.into())
+        } else {
+            Error::Input(error.to_string())
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Error::Io(error)
+    }
+}
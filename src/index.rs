@@ -0,0 +1,266 @@
+use crate::database::SchemaRecord;
+use crate::error::Error;
+use crate::page::{self, Page};
+use crate::record::Record;
+use crate::values::{self, Collation, TextEncoding, Value};
+use crate::varint;
+use std::cmp::Ordering;
+
+/// Collects the keys of an index while records are added to its table.
+/// The b-tree is created once all keys are known, because they have to be sorted first.
+pub(crate) struct Index {
+    pub(crate) schema: SchemaRecord,
+    column_positions: Vec<usize>,
+    descending: Vec<bool>,
+    collations: Vec<Collation>,
+    unique: bool,
+    entries: Vec<(Vec<Value>, i64)>,
+}
+
+/// An index cell as it goes on a leaf page. On interior pages it is preceded by the left child pointer.
+struct Cell {
+    bytes: Vec<u8>,
+    overflow_pages: Vec<Page>,
+}
+
+impl Index {
+    pub(crate) fn new(
+        schema: SchemaRecord,
+        column_positions: Vec<usize>,
+        descending: Vec<bool>,
+        collations: Vec<Collation>,
+        unique: bool,
+    ) -> Self {
+        Self {
+            schema,
+            column_positions,
+            descending,
+            collations,
+            unique,
+            entries: Vec::new(),
+        }
+    }
+
+    /// Adds the indexed columns of a record. Missing columns are NULL.
+    pub(crate) fn add_entry(&mut self, values: &[Value], rowid: i64) {
+        let key = self
+            .column_positions
+            .iter()
+            .map(|position| values.get(*position).cloned().unwrap_or_else(values::null))
+            .collect();
+        self.entries.push((key, rowid));
+    }
+
+    fn compare_keys(&self, left: &[Value], right: &[Value], encoding: TextEncoding) -> Ordering {
+        left.iter()
+            .zip(right)
+            .zip(self.descending.iter().zip(&self.collations))
+            .map(|((l, r), (descending, collation))| {
+                let ordering = collation.compare(l, r, encoding);
+                if *descending {
+                    ordering.reverse()
+                } else {
+                    ordering
+                }
+            })
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or(Ordering::Equal)
+    }
+
+    /// Sorts the keys and creates the b-tree. The encoding is that of the text in the keys.
+    /// Returns the schema, the root page and the total number of pages in the tree.
+    pub(crate) fn into_btree(
+        mut self,
+        page_size: usize,
+        encoding: TextEncoding,
+    ) -> Result<(SchemaRecord, Page, usize), Error> {
+        let mut entries = std::mem::take(&mut self.entries);
+        entries.sort_by(|(left_key, left_rowid), (right_key, right_rowid)| {
+            self.compare_keys(left_key, right_key, encoding)
+                .then(left_rowid.cmp(right_rowid))
+        });
+
+        if self.unique {
+            for pair in entries.windows(2) {
+                let (left, right) = (&pair[0].0, &pair[1].0);
+                // NULLs are distinct from each other in a unique index
+                let has_null = left.iter().any(Value::is_null);
+                if !has_null && self.compare_keys(left, right, encoding).is_eq() {
+                    return Err(Error::DuplicateKey {
+                        index: self.schema.name,
+                    });
+                }
+            }
+        }
+
+        let cells = entries
+            .into_iter()
+            .map(|(key, rowid)| {
+                let mut record = Record::new(rowid);
+                for value in key {
+                    record.add_value(value);
+                }
+                record.add_value(values::integer(rowid));
+                let payload = record.into_payload();
+
+                let mut bytes = varint::write(payload.len() as u64);
+                let overflow_pages = page::append_payload(
+                    &mut bytes,
+                    &payload,
+                    page::max_local_index(page_size),
+                    page_size,
+                );
+                Cell {
+                    bytes,
+                    overflow_pages,
+                }
+            })
+            .collect();
+
+        let (mut child_pages, mut separators) = create_leaf_pages(cells, page_size);
+        let mut n_pages = child_pages.len();
+        while child_pages.len() > 1 {
+            (child_pages, separators) = create_interior_pages(child_pages, separators, page_size);
+            n_pages += child_pages.len();
+        }
+        Ok((self.schema, child_pages.remove(0), n_pages))
+    }
+}
+
+/// Unlike table b-trees, the keys on interior pages are not repeated on the leaf pages.
+/// Returns the leaf pages and the cells that separate them, to go on the interior pages.
+fn create_leaf_pages(cells: Vec<Cell>, page_size: usize) -> (Vec<Page>, Vec<Cell>) {
+    let cell_sizes: Vec<usize> = cells.iter().map(|cell| cell.bytes.len() + 2).collect();
+    let promoted = promoted_cells(
+        &cell_sizes,
+        page_size - page::POSITION_RIGHTMOST_POINTER_LEAFPAGES,
+    );
+
+    let mut leaf_pages = Vec::new();
+    let mut separators = Vec::new();
+    let mut leaf_page = new_leaf_page(page_size);
+    let mut n_cells = 0;
+    for (cell, promoted) in cells.into_iter().zip(promoted) {
+        if promoted {
+            finish_leaf_page(&mut leaf_page, n_cells);
+            leaf_pages.push(std::mem::replace(&mut leaf_page, new_leaf_page(page_size)));
+            n_cells = 0;
+            separators.push(cell);
+        } else {
+            leaf_page.put_cell(&cell.bytes, cell.overflow_pages);
+            n_cells += 1;
+        }
+    }
+    finish_leaf_page(&mut leaf_page, n_cells);
+    leaf_pages.push(leaf_page);
+    (leaf_pages, separators)
+}
+
+/// Every separator cell gets the child page on its left as left pointer,
+/// except for the ones that move up another level. Those children become the rightmost pointer.
+fn create_interior_pages(
+    child_pages: Vec<Page>,
+    separators: Vec<Cell>,
+    page_size: usize,
+) -> (Vec<Page>, Vec<Cell>) {
+    let cell_sizes: Vec<usize> = separators
+        .iter()
+        .map(|cell| 4 + cell.bytes.len() + 2)
+        .collect();
+    let promoted = promoted_cells(&cell_sizes, page_size - page::START_OF_INTERIOR_PAGE);
+
+    let mut interior_pages = Vec::new();
+    let mut new_separators = Vec::new();
+    let mut interior_page = new_interior_page(page_size);
+    let mut child_pages = child_pages.into_iter();
+    for (cell, promoted) in separators.into_iter().zip(promoted) {
+        interior_page.add_child(child_pages.next().unwrap()); // there is one more child than separators
+        if promoted {
+            finish_interior_page(&mut interior_page);
+            interior_pages.push(std::mem::replace(
+                &mut interior_page,
+                new_interior_page(page_size),
+            ));
+            new_separators.push(cell);
+        } else {
+            let mut bytes = vec![0; 4]; // left child pointer, set when the page is written
+            bytes.extend_from_slice(&cell.bytes);
+            interior_page.put_cell(&bytes, cell.overflow_pages);
+        }
+    }
+    interior_page.add_child(child_pages.next().unwrap());
+    finish_interior_page(&mut interior_page);
+    interior_pages.push(interior_page);
+    (interior_pages, new_separators)
+}
+
+/// Decides which cells move up to the parent level, so that the others fit on pages
+/// of the given capacity. Every page keeps at least one cell.
+fn promoted_cells(cell_sizes: &[usize], capacity: usize) -> Vec<bool> {
+    let mut promoted = vec![false; cell_sizes.len()];
+    let mut bytes_used = 0;
+    let mut n_cells = 0;
+    for (index, cell_size) in cell_sizes.iter().enumerate() {
+        if n_cells > 0 && bytes_used + cell_size > capacity {
+            promoted[index] = true;
+            bytes_used = 0;
+            n_cells = 0;
+        } else {
+            bytes_used += cell_size;
+            n_cells += 1;
+        }
+    }
+
+    // the last cell can't move up, because it would leave the last page empty
+    let n = promoted.len();
+    if n > 1 && promoted[n - 1] {
+        promoted[n - 1] = false;
+        promoted[n - 2] = true;
+    }
+    promoted
+}
+
+fn new_leaf_page(page_size: usize) -> Page {
+    let mut page = Page::new_index_leaf(page_size);
+    page.fw_position = page::POSITION_RIGHTMOST_POINTER_LEAFPAGES; // no rightmost pointer on a leaf
+    page
+}
+
+fn finish_leaf_page(page: &mut Page, n_cells: u16) {
+    page.fw_position = page::POSITION_CELL_COUNT;
+    page.put_u16(n_cells);
+    page.put_u16(page.content_start());
+}
+
+fn new_interior_page(page_size: usize) -> Page {
+    let mut page = Page::new_index_interior(page_size);
+    page.fw_position = page::START_OF_INTERIOR_PAGE;
+    page
+}
+
+/// the cell count and child pointers are written along with the page
+fn finish_interior_page(page: &mut Page) {
+    page.fw_position = page::START_OF_CONTENT_AREA;
+    page.put_u16(page.content_start());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_promoted_cells() {
+        assert_eq!(
+            promoted_cells(&[10, 10, 10, 10, 10], 20),
+            vec![false, false, true, false, false]
+        );
+    }
+
+    #[test]
+    fn test_last_cell_not_promoted() {
+        assert_eq!(
+            promoted_cells(&[10, 10, 10, 10, 10, 10], 20),
+            vec![false, false, true, false, true, false]
+        );
+    }
+}
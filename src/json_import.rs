@@ -0,0 +1,210 @@
+use crate::builder::no_rows;
+use crate::error::Error;
+use crate::record::Record;
+use crate::sql::quote_identifier;
+use crate::table_sink::{self, TableSink};
+use crate::values::{self, Value};
+use serde_json::Value as JsonValue;
+use std::io::{BufRead, BufReader, Read};
+
+/// The columns of a table that is filled with JSON objects: the union of their keys,
+/// in order of appearance. The first value that isn't NULL determines the type.
+struct Columns {
+    keys: Vec<String>,
+    names: Vec<String>,
+    types: Vec<Option<&'static str>>,
+}
+
+impl Columns {
+    fn new() -> Self {
+        Self {
+            keys: Vec::new(),
+            names: Vec::new(),
+            types: Vec::new(),
+        }
+    }
+
+    /// The values of the row, in column order. Keys that weren't seen before add a column,
+    /// keys that are missing are NULL.
+    fn values(&mut self, row: JsonValue) -> Result<Vec<Value>, Error> {
+        let JsonValue::Object(fields) = row else {
+            return Err(Error::Input(format!(
+                "rows have to be JSON objects, not {row}"
+            )));
+        };
+        let mut values = vec![values::null(); self.keys.len()];
+        for (key, value) in fields {
+            let position = match self.keys.iter().position(|column| *column == key) {
+                Some(position) => position,
+                None => {
+                    self.add_column(key);
+                    values.push(values::null());
+                    self.keys.len() - 1
+                }
+            };
+            let value = to_value(value);
+            if self.types[position].is_none() {
+                self.types[position] = value.sql_type();
+            }
+            values[position] = value;
+        }
+        Ok(values)
+    }
+
+    /// Keys that only differ in case get a suffix, because column names are case insensitive
+    fn add_column(&mut self, key: String) {
+        let is_taken = |name: &str| {
+            self.names
+                .iter()
+                .any(|taken| taken.eq_ignore_ascii_case(name))
+        };
+        let mut name = key.clone();
+        let mut suffix = 2;
+        while is_taken(&name) {
+            name = format!("{key}_{suffix}");
+            suffix += 1;
+        }
+        self.keys.push(key);
+        self.names.push(name);
+        self.types.push(None);
+    }
+
+    /// Columns without any values get no type
+    fn create_table(&self, table_name: &str) -> String {
+        let columns: Vec<String> = self
+            .names
+            .iter()
+            .zip(&self.types)
+            .map(|(name, sql_type)| match sql_type {
+                Some(sql_type) => format!("{} {sql_type}", quote_identifier(name)),
+                None => quote_identifier(name),
+            })
+            .collect();
+        format!(
+            "CREATE TABLE {}({})",
+            quote_identifier(table_name),
+            columns.join(", ")
+        )
+    }
+}
+
+/// Nested objects and arrays are stored as JSON text
+fn to_value(value: JsonValue) -> Value {
+    match value {
+        JsonValue::Null => values::null(),
+        JsonValue::Bool(boolean) => boolean.into(),
+        JsonValue::Number(number) => match number.as_i64() {
+            Some(integer) => values::integer(integer),
+            None => values::float(number.as_f64().unwrap_or(f64::NAN)),
+        },
+        JsonValue::String(string) => string.into(),
+        nested => nested.to_string().into(),
+    }
+}
+
+/// Declares the table and adds the rows, which are either the elements of a JSON array
+/// or a stream of objects such as NDJSON. An array is read as a whole, a stream row by row.
+pub(crate) fn add_json<R: Read>(
+    sink: &mut impl TableSink,
+    table_name: &str,
+    reader: R,
+) -> Result<(), Error> {
+    let mut reader = BufReader::new(reader);
+    if starts_with_array(&mut reader)? {
+        let rows: Vec<JsonValue> = serde_json::from_reader(reader)?;
+        add_rows(sink, table_name, rows.into_iter().map(Ok))
+    } else {
+        let rows = serde_json::Deserializer::from_reader(reader).into_iter::<JsonValue>();
+        add_rows(sink, table_name, rows.map(|row| row.map_err(Error::from)))
+    }
+}
+
+fn starts_with_array(reader: &mut impl BufRead) -> Result<bool, Error> {
+    loop {
+        let buffer = reader.fill_buf()?;
+        let Some(&first) = buffer.first() else {
+            return Ok(false);
+        };
+        let n_whitespace = buffer
+            .iter()
+            .take_while(|byte| byte.is_ascii_whitespace())
+            .count();
+        if n_whitespace == 0 {
+            return Ok(first == b'[');
+        }
+        reader.consume(n_whitespace);
+    }
+}
+
+/// Records have as many values as there were columns when they were added.
+/// SQLite reads the columns that were added later as NULL.
+fn add_rows(
+    sink: &mut impl TableSink,
+    table_name: &str,
+    rows: impl Iterator<Item = Result<JsonValue, Error>>,
+) -> Result<(), Error> {
+    let mut columns = Columns::new();
+    let mut is_declared = false;
+    for (rowid, row) in (1..).zip(rows) {
+        let values = columns.values(row?)?;
+        if !is_declared {
+            // the table needs at least one column
+            if values.is_empty() {
+                continue;
+            }
+            table_sink::schema(sink, table_name, &columns.create_table(table_name))?;
+            for empty_rowid in 1..rowid {
+                table_sink::add_record(sink, Record::new(empty_rowid))?;
+            }
+            is_declared = true;
+        }
+        table_sink::add_record(sink, Record::with_values(rowid, values))?;
+    }
+    if !is_declared {
+        return Err(no_rows(table_name));
+    }
+
+    // the columns are only known after all rows
+    if let Some(table) = sink.current_table() {
+        table.schema.sql = Some(columns.create_table(table_name));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_union_of_keys() -> Result<(), Error> {
+        let mut columns = Columns::new();
+        assert_eq!(columns.values(json!({"id": 1, "name": null}))?.len(), 2);
+        let values = columns.values(json!({"tags": ["a"], "ID": 2.5, "name": "foo"}))?;
+        assert!(values[0].is_null());
+        assert_eq!(values[1].sql_type(), Some("text"));
+        assert_eq!(values[2].sql_type(), Some("text"));
+        assert_eq!(values[3].sql_type(), Some("real"));
+        assert_eq!(
+            columns.create_table("foo"),
+            "CREATE TABLE \"foo\"(\"id\" integer, \"name\" text, \"tags\" text, \"ID_2\" real)"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_row_that_is_not_an_object() {
+        assert!(matches!(
+            Columns::new().values(json!([1, 2])),
+            Err(Error::Input(_))
+        ));
+    }
+
+    #[test]
+    fn test_starts_with_array() -> Result<(), Error> {
+        assert!(starts_with_array(&mut &b" \n [{}]"[..])?);
+        assert!(!starts_with_array(&mut &b"{}\n{}"[..])?);
+        assert!(!starts_with_array(&mut &b""[..])?);
+        Ok(())
+    }
+}
@@ -1,34 +1,67 @@
-#![allow(dead_code)]
+//! Creates `SQLite` database files from tabular data, without the need for `SQLite` itself,
+//! and reads them back.
+//!
+//! ```
+//! use sqlighters::{values, write_sqlite, DatabaseBuilder, Record};
+//!
+//! # fn main() -> Result<(), sqlighters::Error> {
+//! let mut builder = DatabaseBuilder::new();
+//! builder.schema("foo", "create table foo(bar varchar(10))")?;
+//! let mut record = Record::new(1);
+//! record.add_value(values::string("helloworld"));
+//! builder.add_record(record)?;
+//!
+//! let database = builder.build()?;
+//! write_sqlite(database, std::io::sink())?;
+//! # Ok(())
+//! # }
+//! ```
 
+mod append;
+#[cfg(feature = "arrow")]
+mod arrow_import;
 mod builder;
+mod changeset;
+#[cfg(feature = "csv")]
+mod csv_import;
 mod database;
+mod error;
+mod index;
+#[cfg(feature = "json")]
+mod json_import;
 mod page;
+mod patch;
+mod reader;
 mod record;
-mod values;
+#[cfg(feature = "axum")]
+mod response;
+mod row;
+#[cfg(feature = "serde")]
+mod serialize;
+mod sql;
+mod streaming;
+mod table_sink;
+mod validate;
+pub mod values;
 mod varint;
 
-#[cfg(test)]
-mod tests {
-    use crate::builder::DatabaseBuilder;
-    use crate::database::{write_sqlite, Database};
-    use crate::record::Record;
-    use crate::values;
-    use std::fs::File;
-    use std::io::{BufWriter, Error};
-
-    #[test]
-    fn test_build() -> Result<(), Error> {
-        let mut builder = DatabaseBuilder::new();
-        builder.schema("foo", "create table foo(bar varchar(10))");
-        for i in 0..10000 {
-            let mut record = Record::new(i);
-            record.add_value(values::string("helloworld"));
-            builder.add_record(record);
-        }
-        let database: Database = builder.into();
-        let file = File::create("foo.db")?;
-        let writer = BufWriter::new(file);
-        write_sqlite(database, writer)?;
-        Ok(())
-    }
-}
+pub use append::DatabaseAppender;
+pub use builder::DatabaseBuilder;
+pub use changeset::create_changeset;
+#[cfg(feature = "csv")]
+pub use csv_import::{ColumnType, CsvOptions, Inference};
+#[cfg(feature = "tokio")]
+pub use database::write_sqlite_async;
+pub use database::{write_sqlite, Database};
+pub use error::Error;
+pub use patch::{apply_patch, create_patch, Patch};
+pub use reader::{DatabaseReader, SchemaEntry};
+pub use record::Record;
+#[cfg(feature = "axum")]
+pub use response::Attachment;
+pub use row::SqliteRow;
+#[cfg(feature = "derive")]
+pub use sqlighters_derive::SqliteRow;
+pub use streaming::StreamingDatabaseBuilder;
+pub use validate::{validate, Diagnostic, DiagnosticKind};
+pub use values::{TextEncoding, Value};
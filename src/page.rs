@@ -1,118 +1,337 @@
-use crate::database;
+use crate::database::{
+    self, INDEX_INTERIOR_PAGE, INDEX_LEAF_PAGE, TABLE_INTERIOR_PAGE, TABLE_LEAF_PAGE,
+};
+use crate::varint;
 use byteorder::{BigEndian, ByteOrder};
 
-pub const POSITION_CELL_COUNT: u16 = 3;
-pub const START_OF_CONTENT_AREA: u16 = 5;
-pub const START_OF_INTERIOR_PAGE: u16 = 12;
-pub const POSITION_RIGHTMOST_POINTER_LEAFPAGES: u16 = 8;
+pub const POSITION_CELL_COUNT: usize = 3;
+pub const START_OF_CONTENT_AREA: usize = 5;
+pub const START_OF_INTERIOR_PAGE: usize = 12;
+pub const POSITION_RIGHTMOST_POINTER_LEAFPAGES: usize = 8;
+pub const START_OF_OVERFLOW_CONTENT: usize = 4;
 
-pub enum PageType {
+pub(crate) enum PageType {
     Leaf,
     Interior,
-    Root,
+    Overflow,
     Other,
 }
 
 /// Represents an `SQLite` page
-pub struct Page {
-    pub data: Vec<u8>,
-    pub fw_position: u16,
-    pub bw_position: u16,
-    pub key: u64,
-    pub children: Vec<Page>,
-    pub page_type: PageType,
+pub(crate) struct Page {
+    pub(crate) data: Vec<u8>,
+    pub(crate) fw_position: usize,
+    pub(crate) bw_position: usize,
+    pub(crate) key: i64,
+    pub(crate) children: Vec<Page>,
+    pub(crate) page_type: PageType,
+    pub(crate) overflow: Vec<OverflowChain>,
+}
+
+/// The overflow pages for a single cell that does not fit on its page
+pub(crate) struct OverflowChain {
+    /// position of the first overflow page number in the cell, written when page numbers are known
+    pub(crate) pointer_position: usize,
+    pub(crate) pages: Vec<Page>,
 }
 
 impl Page {
-    fn with_capacity(size: u16, page_type: PageType) -> Self {
+    fn with_capacity(size: usize, page_type: PageType) -> Self {
         Self {
-            data: vec![0; size as usize],
+            data: vec![0; size],
             fw_position: 0,
             bw_position: size,
             key: 0,
             children: Vec::new(),
             page_type,
+            overflow: Vec::new(),
         }
     }
 
-    fn default(size: usize) -> Self {
-        Self {
-            data: vec![0; size],
-            fw_position: 0,
-            bw_position: size as u16,
-            key: 0,
-            children: Vec::new(),
-            page_type: PageType::Other,
-        }
+    pub fn new_root(page_size: usize) -> Self {
+        Page::with_capacity(page_size, PageType::Other)
     }
 
-    pub fn new_root() -> Self {
-        Page::with_capacity(database::DEFAULT_PAGE_SIZE, PageType::Other)
-    }
-
-    pub fn new_leaf() -> Self {
-        let mut page = Page::with_capacity(database::DEFAULT_PAGE_SIZE, PageType::Leaf);
+    pub fn new_leaf(page_size: usize) -> Self {
+        let mut page = Page::with_capacity(page_size, PageType::Leaf);
         page.put_u8(database::TABLE_LEAF_PAGE);
         page
     }
 
-    pub fn new_interior() -> Self {
-        let mut page = Page::with_capacity(database::DEFAULT_PAGE_SIZE, PageType::Interior);
+    pub fn new_interior(page_size: usize) -> Self {
+        let mut page = Page::with_capacity(page_size, PageType::Interior);
         page.put_u8(database::TABLE_INTERIOR_PAGE);
         page
     }
 
+    pub fn new_index_leaf(page_size: usize) -> Self {
+        let mut page = Page::with_capacity(page_size, PageType::Leaf);
+        page.put_u8(database::INDEX_LEAF_PAGE);
+        page
+    }
+
+    pub fn new_index_interior(page_size: usize) -> Self {
+        let mut page = Page::with_capacity(page_size, PageType::Interior);
+        page.put_u8(database::INDEX_INTERIOR_PAGE);
+        page
+    }
+
+    /// the first 4 bytes are reserved for the next page in the chain
+    pub fn new_overflow(page_size: usize, content: &[u8]) -> Self {
+        let mut page = Page::with_capacity(page_size, PageType::Overflow);
+        page.fw_position = START_OF_OVERFLOW_CONTENT;
+        page.put_bytes(content);
+        page
+    }
+
+    /// Writes the cell from the end of the page and adds a pointer to it.
+    /// Overflow pages, if any, are linked to the last 4 bytes of the cell when the page is written.
+    pub fn put_cell(&mut self, cell: &[u8], overflow_pages: Vec<Page>) {
+        self.put_bytes_bw(cell);
+        if !overflow_pages.is_empty() {
+            self.overflow.push(OverflowChain {
+                pointer_position: self.bw_position + cell.len() - 4,
+                pages: overflow_pages,
+            });
+        }
+        self.put_u16(self.bw_position as u16);
+    }
+
+    /// The start of the cell content area, as it goes in the page header.
+    /// An empty 65536 byte page starts at 0.
+    pub fn content_start(&self) -> u16 {
+        self.bw_position as u16
+    }
+
     pub fn add_child(&mut self, child: Self) {
         self.children.push(child);
     }
 
     pub fn put_bytes(&mut self, bytes: &[u8]) {
         for v in bytes {
-            self.data[self.fw_position as usize] = *v;
+            self.data[self.fw_position] = *v;
             self.fw_position += 1;
         }
     }
 
     pub fn put_bytes_bw(&mut self, bytes: &[u8]) {
-        self.bw_position -= bytes.len() as u16;
+        self.bw_position -= bytes.len();
         for v in bytes {
-            self.data[self.bw_position as usize] = *v;
+            self.data[self.bw_position] = *v;
             self.bw_position += 1;
         }
-        self.bw_position -= bytes.len() as u16;
+        self.bw_position -= bytes.len();
     }
 
     pub fn put_u8(&mut self, value: u8) {
         self.put_bytes(&[value]);
     }
 
-    pub fn put_u8_bw(&mut self, value: u8) {
-        self.put_bytes_bw(&[value]);
-    }
-
     pub fn put_u16(&mut self, value: u16) {
         self.put_bytes(&u16_to_bytes(value));
     }
 
-    pub fn put_u16_bw(&mut self, value: u16) {
-        self.put_bytes_bw(&u16_to_bytes(value));
-    }
-
     pub fn put_u32(&mut self, value: u32) {
         self.put_bytes(&u32_to_bytes(value));
     }
+}
+
+/// The maximum amount of payload that is stored on a table leaf page itself
+pub fn max_local_table_leaf(usable_size: usize) -> usize {
+    usable_size - 35
+}
+
+/// The maximum amount of payload that is stored on an index page itself
+pub fn max_local_index(usable_size: usize) -> usize {
+    ((usable_size - 12) * 64 / 255) - 23
+}
 
-    pub fn put_u32_bw(&mut self, value: u32) {
-        self.put_bytes_bw(&u32_to_bytes(value));
+/// The minimum amount of payload that is stored on a page, once a payload overflows
+pub fn min_local(usable_size: usize) -> usize {
+    ((usable_size - 12) * 32 / 255) - 23
+}
+
+/// The number of payload bytes that stay on the b-tree page, as defined by the file format.
+/// The remainder goes to overflow pages, each holding `usable_size - 4` bytes.
+pub fn local_payload_len(payload_len: usize, max_local: usize, usable_size: usize) -> usize {
+    if payload_len <= max_local {
+        payload_len
+    } else {
+        let min_local = min_local(usable_size);
+        let local = min_local + ((payload_len - min_local) % (usable_size - 4));
+        if local <= max_local {
+            local
+        } else {
+            min_local
+        }
     }
+}
 
-    pub fn get_u16(&self) -> u16 {
-        let position = self.fw_position as usize;
-        (u16::from(self.data[position]) << 8) + u16::from(self.data[position + 1])
-        // does not increase the fw pointerr
+/// Appends the part of the payload that stays on the b-tree page to the cell.
+/// When the payload does not fit, 4 bytes are reserved for the first overflow page number
+/// and the overflow pages are returned.
+pub fn append_payload(
+    cell: &mut Vec<u8>,
+    payload: &[u8],
+    max_local: usize,
+    usable_size: usize,
+) -> Vec<Page> {
+    let local_len = local_payload_len(payload.len(), max_local, usable_size);
+    cell.extend_from_slice(&payload[..local_len]);
+    if local_len < payload.len() {
+        cell.extend_from_slice(&[0; 4]);
+        payload[local_len..]
+            .chunks(usable_size - 4)
+            .map(|content| Page::new_overflow(usable_size, content))
+            .collect()
+    } else {
+        Vec::new()
     }
 }
 
+/// The header of a b-tree page in an existing file
+pub(crate) struct PageHeader {
+    pub(crate) page_type: u8,
+    pub(crate) first_freeblock: u16,
+    pub(crate) cell_count: u16,
+    /// 65536 is stored as 0
+    pub(crate) content_start: usize,
+    pub(crate) fragmented_bytes: u8,
+    /// only interior pages have a rightmost child
+    pub(crate) rightmost_pointer: Option<u32>,
+}
+
+impl PageHeader {
+    /// Reads the header at the start of the bytes, which is 100 bytes into the first page.
+    /// Returns None for anything but a b-tree page.
+    pub(crate) fn read(bytes: &[u8]) -> Option<Self> {
+        let page_type = *bytes.first()?;
+        let is_interior = match page_type {
+            TABLE_INTERIOR_PAGE | INDEX_INTERIOR_PAGE => true,
+            TABLE_LEAF_PAGE | INDEX_LEAF_PAGE => false,
+            _ => return None,
+        };
+        let header = bytes.get(..if is_interior { 12 } else { 8 })?;
+        let content_start = BigEndian::read_u16(&header[5..]);
+        Some(Self {
+            page_type,
+            first_freeblock: BigEndian::read_u16(&header[1..]),
+            cell_count: BigEndian::read_u16(&header[3..]),
+            content_start: if content_start == 0 {
+                65536
+            } else {
+                usize::from(content_start)
+            },
+            fragmented_bytes: header[7],
+            rightmost_pointer: is_interior.then(|| BigEndian::read_u32(&header[8..])),
+        })
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        if self.rightmost_pointer.is_some() {
+            12
+        } else {
+            8
+        }
+    }
+
+    pub(crate) fn is_table(&self) -> bool {
+        matches!(self.page_type, TABLE_LEAF_PAGE | TABLE_INTERIOR_PAGE)
+    }
+
+    pub(crate) fn is_leaf(&self) -> bool {
+        self.rightmost_pointer.is_none()
+    }
+
+    /// The offsets of the cells in the page, in key order.
+    /// `header_offset` is 100 on the first page and 0 on the others.
+    pub(crate) fn cell_pointers(&self, page: &[u8], header_offset: usize) -> Option<Vec<usize>> {
+        let start = header_offset + self.len();
+        let pointers = page.get(start..start + 2 * usize::from(self.cell_count))?;
+        Some(
+            pointers
+                .chunks(2)
+                .map(|pointer| usize::from(BigEndian::read_u16(pointer)))
+                .collect(),
+        )
+    }
+}
+
+/// A cell in a b-tree page of an existing file
+pub(crate) struct CellContent<'a> {
+    /// the child page to the left of the key, on interior pages
+    pub(crate) left_child: Option<u32>,
+    /// the key of table b-tree cells
+    pub(crate) rowid: Option<i64>,
+    /// the size of the whole payload, including the part on overflow pages
+    pub(crate) payload_len: usize,
+    pub(crate) local_payload: &'a [u8],
+    pub(crate) first_overflow_page: Option<u32>,
+    /// the number of bytes the cell takes on the page
+    pub(crate) len: usize,
+}
+
+/// Reads the cell at the offset. Returns None when it doesn't fit in the page.
+pub(crate) fn read_cell<'a>(
+    page: &'a [u8],
+    offset: usize,
+    header: &PageHeader,
+    usable_size: usize,
+) -> Option<CellContent<'a>> {
+    let mut position = offset;
+    let read_varint = |position: &mut usize| {
+        let (value, len) = varint::read(page.get(*position..)?)?;
+        *position += len;
+        Some(value)
+    };
+
+    let left_child = match header.rightmost_pointer {
+        Some(_) => {
+            let child = BigEndian::read_u32(page.get(position..position + 4)?);
+            position += 4;
+            Some(child)
+        }
+        None => None,
+    };
+    // interior table cells only have a key
+    let has_payload = header.page_type != TABLE_INTERIOR_PAGE;
+    let payload_len = if has_payload {
+        usize::try_from(read_varint(&mut position)?).ok()?
+    } else {
+        0
+    };
+    let rowid = if header.is_table() {
+        Some(read_varint(&mut position)? as i64)
+    } else {
+        None
+    };
+
+    let max_local = if header.page_type == TABLE_LEAF_PAGE {
+        max_local_table_leaf(usable_size)
+    } else {
+        max_local_index(usable_size)
+    };
+    let local_len = local_payload_len(payload_len, max_local, usable_size);
+    let local_payload = page.get(position..position.checked_add(local_len)?)?;
+    position += local_len;
+    let first_overflow_page = if local_len < payload_len {
+        let overflow_page = BigEndian::read_u32(page.get(position..position + 4)?);
+        position += 4;
+        Some(overflow_page)
+    } else {
+        None
+    };
+
+    Some(CellContent {
+        left_child,
+        rowid,
+        payload_len,
+        local_payload,
+        first_overflow_page,
+        len: position - offset,
+    })
+}
+
 fn u16_to_bytes(value: u16) -> [u8; 2] {
     let mut buf = [0; 2];
     BigEndian::write_u16(&mut buf, value);
@@ -129,16 +348,37 @@ fn u32_to_bytes(value: u32) -> [u8; 4] {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_local_payload_fits() {
+        assert_eq!(
+            local_payload_len(4061, max_local_table_leaf(4096), 4096),
+            4061
+        );
+    }
+
+    #[test]
+    fn test_local_payload_overflows() {
+        assert_eq!(min_local(4096), 489);
+        assert_eq!(
+            local_payload_len(5000, max_local_table_leaf(4096), 4096),
+            908
+        );
+        assert_eq!(
+            local_payload_len(4062, max_local_table_leaf(4096), 4096),
+            489
+        );
+    }
+
     #[test]
     fn test_u8() {
-        let mut b = Page::default(1);
+        let mut b = Page::new_root(1);
         b.put_u8(64_u8);
         assert_eq!(b.data[0], 64);
     }
 
     #[test]
     fn test_u8a() {
-        let mut b = Page::default(2);
+        let mut b = Page::new_root(2);
         b.put_bytes(&[1, 2]);
         assert_eq!(b.data[0], 1);
         assert_eq!(b.data[1], 2);
@@ -146,7 +386,7 @@ mod tests {
 
     #[test]
     fn test_u16() {
-        let mut b = Page::default(2);
+        let mut b = Page::new_root(2);
         b.put_u16(4096);
         assert_eq!(b.data[0], 16);
         assert_eq!(b.data[1], 0);
@@ -154,7 +394,7 @@ mod tests {
 
     #[test]
     fn test_u32() {
-        let mut b = Page::default(4);
+        let mut b = Page::new_root(4);
         b.put_u32(0xFFFFFFFF);
         assert_eq!(b.data[0], 0xFF);
         assert_eq!(b.data[1], 0xFF);
@@ -164,7 +404,7 @@ mod tests {
 
     #[test]
     fn test_u16_position() {
-        let mut b = Page::default(4);
+        let mut b = Page::new_root(4);
         b.fw_position = 2;
         b.put_u16(4096);
         assert_eq!(b.data[0], 0);
@@ -175,8 +415,8 @@ mod tests {
 
     #[test]
     fn test_u16_backwards() {
-        let mut b = Page::default(4);
-        b.put_u16_bw(0x1000);
+        let mut b = Page::new_root(4);
+        b.put_bytes_bw(&0x1000_u16.to_be_bytes());
         assert_eq!(b.data[0], 0);
         assert_eq!(b.data[1], 0);
         assert_eq!(b.data[2], 0x10);
@@ -185,9 +425,9 @@ mod tests {
 
     #[test]
     fn test_u16_2_directions() {
-        let mut b = Page::default(5);
+        let mut b = Page::new_root(5);
         b.put_u16(0x1001);
-        b.put_u16_bw(0x1000);
+        b.put_bytes_bw(&0x1000_u16.to_be_bytes());
         assert_eq!(b.data[0], 0x10);
         assert_eq!(b.data[1], 0x01);
         assert_eq!(b.data[2], 0); // decimal suggests this value has not been written
@@ -197,9 +437,9 @@ mod tests {
 
     #[test]
     fn test_u32_2_directions() {
-        let mut b = Page::default(9);
+        let mut b = Page::new_root(9);
         b.put_u32(0x1001);
-        b.put_u32_bw(0x1002);
+        b.put_bytes_bw(&0x1002_u32.to_be_bytes());
         assert_eq!(b.data[0], 0x00);
         assert_eq!(b.data[1], 0x00);
         assert_eq!(b.data[2], 0x10);
@@ -0,0 +1,173 @@
+use crate::database::{check_page_count, check_page_size};
+use crate::error::Error;
+use crate::reader::{DatabaseReader, FILE_HEADER_SIZE};
+use byteorder::{BigEndian, ByteOrder};
+
+const MAGIC: &[u8; 16] = b"sqlighters patch";
+/// magic, the length and hash of the old file, page size, page count, file header and number of pages
+const PATCH_HEADER_SIZE: usize = 16 + 8 + 8 + 4 + 4 + FILE_HEADER_SIZE + 4;
+
+/// The pages that differ between two versions of a database file, made by [`create_patch`].
+/// It only applies to the old version that it was made for.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Patch {
+    base_len: u64,
+    base_hash: u64,
+    page_size: u32,
+    page_count: u32,
+    /// the file header of the new version
+    header: Vec<u8>,
+    /// the page numbers and contents of the pages that changed or are new, in ascending order
+    pages: Vec<(u32, Vec<u8>)>,
+}
+
+impl Patch {
+    /// The number of pages in the new version
+    pub fn page_count(&self) -> u32 {
+        self.page_count
+    }
+
+    /// The numbers of the pages that changed or are new
+    pub fn page_numbers(&self) -> Vec<u32> {
+        self.pages
+            .iter()
+            .map(|(page_number, _)| *page_number)
+            .collect()
+    }
+
+    /// The patch as bytes, to be sent to a client that has the old version
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(
+            PATCH_HEADER_SIZE + self.pages.len() * (4 + self.page_size as usize),
+        );
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&self.base_len.to_be_bytes());
+        bytes.extend_from_slice(&self.base_hash.to_be_bytes());
+        bytes.extend_from_slice(&self.page_size.to_be_bytes());
+        bytes.extend_from_slice(&self.page_count.to_be_bytes());
+        bytes.extend_from_slice(&self.header);
+        bytes.extend_from_slice(&(self.pages.len() as u32).to_be_bytes());
+        for (page_number, page) in &self.pages {
+            bytes.extend_from_slice(&page_number.to_be_bytes());
+            bytes.extend_from_slice(page);
+        }
+        bytes
+    }
+
+    /// Reads a patch that was written with [`Patch::to_bytes`]
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        if bytes.len() < PATCH_HEADER_SIZE || &bytes[..16] != MAGIC {
+            return Err(invalid_patch("it doesn't start with a patch header"));
+        }
+        let page_size = BigEndian::read_u32(&bytes[32..]);
+        let page_count = BigEndian::read_u32(&bytes[36..]);
+        let n_pages = BigEndian::read_u32(&bytes[140..]) as usize;
+        // like the page size in the header of a database file
+        let page_size_usize = check_page_size(page_size)
+            .map_err(|_| invalid_patch(&format!("invalid page size {page_size}")))?;
+        if page_count == 0 {
+            return Err(invalid_patch("the new file has no pages"));
+        }
+        check_page_count(u64::from(page_count))
+            .map_err(|_| invalid_patch(&format!("too many pages: {page_count}")))?;
+        if n_pages.checked_mul(4 + page_size_usize) != Some(bytes.len() - PATCH_HEADER_SIZE) {
+            return Err(invalid_patch("its size doesn't match the number of pages"));
+        }
+
+        let mut pages = Vec::with_capacity(n_pages);
+        for chunk in bytes[PATCH_HEADER_SIZE..].chunks(4 + page_size_usize) {
+            let page_number = BigEndian::read_u32(chunk);
+            let previous = pages.last().map_or(0, |(previous, _)| *previous);
+            if page_number <= previous || page_number > page_count {
+                return Err(invalid_patch(&format!(
+                    "page {page_number} is out of order or out of range"
+                )));
+            }
+            pages.push((page_number, chunk[4..].to_vec()));
+        }
+        Ok(Self {
+            base_len: BigEndian::read_u64(&bytes[16..]),
+            base_hash: BigEndian::read_u64(&bytes[24..]),
+            page_size,
+            page_count,
+            header: bytes[40..40 + FILE_HEADER_SIZE].to_vec(),
+            pages,
+        })
+    }
+}
+
+/// Compares the new version of a database file with the old one, page by page.
+/// The patch holds the pages of the new version that differ, and its file header.
+/// The old version can be any file, and when it is empty the patch holds every page.
+pub fn create_patch(old: &[u8], new: &[u8]) -> Result<Patch, Error> {
+    let reader = DatabaseReader::new(new)?;
+    let page_size = reader.page_size();
+    let page_count = reader.page_count();
+    let new = new
+        .get(..page_count as usize * page_size)
+        .ok_or_else(|| Error::Input("the new file ends before its last page".to_owned()))?;
+
+    let pages = new
+        .chunks(page_size)
+        .zip(1..)
+        .filter(|(page, page_number)| {
+            let start = (*page_number as usize - 1) * page_size;
+            // the file header goes in the patch anyway
+            let compared = if *page_number == 1 {
+                FILE_HEADER_SIZE
+            } else {
+                0
+            };
+            old.get(start + compared..start + page_size) != Some(&page[compared..])
+        })
+        .map(|(page, page_number)| (page_number, page.to_vec()))
+        .collect();
+    Ok(Patch {
+        base_len: old.len() as u64,
+        base_hash: hash(old),
+        page_size: page_size as u32,
+        page_count,
+        header: new[..FILE_HEADER_SIZE].to_vec(),
+        pages,
+    })
+}
+
+/// Makes the new version of the file from the old one and the patch
+pub fn apply_patch(old: &[u8], patch: &Patch) -> Result<Vec<u8>, Error> {
+    if old.len() as u64 != patch.base_len || hash(old) != patch.base_hash {
+        return Err(invalid_patch("it was made for another file"));
+    }
+    let page_size = patch.page_size as usize;
+    // the pages after the end of the old file are all in the patch, which limits the size of the new file
+    let old_page_count = old.len() / page_size;
+    let n_added_pages = patch
+        .pages
+        .iter()
+        .filter(|(page_number, _)| *page_number as usize > old_page_count)
+        .count();
+    if patch.page_count as usize > old_page_count + n_added_pages {
+        return Err(invalid_patch(
+            "it doesn't hold the pages after the end of the old file",
+        ));
+    }
+    let len = patch.page_count as usize * page_size;
+    let mut new = old[..len.min(old.len())].to_vec();
+    new.resize(len, 0);
+    for (page_number, page) in &patch.pages {
+        let start = (*page_number as usize - 1) * page_size;
+        new[start..start + page_size].copy_from_slice(page);
+    }
+    new[..FILE_HEADER_SIZE].copy_from_slice(&patch.header);
+    Ok(new)
+}
+
+/// FNV-1a, to tell whether the patch is applied to the file it was made for
+fn hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+fn invalid_patch(message: &str) -> Error {
+    Error::Input(format!("invalid patch: {message}"))
+}
@@ -0,0 +1,311 @@
+use crate::database::{MAGIC_HEADER, MAX_PAGE_SIZE, MIN_PAGE_SIZE};
+use crate::error::Error;
+use crate::page::{self, CellContent, PageHeader};
+use crate::record::{self, Record};
+use crate::values::{TextEncoding, Value};
+use byteorder::{BigEndian, ByteOrder};
+use std::borrow::Cow;
+use std::collections::HashSet;
+
+/// The file header at the start of the first page
+pub(crate) const FILE_HEADER_SIZE: usize = 100;
+
+/// A row of `sqlite_master`
+#[derive(Clone, Debug, PartialEq)]
+pub struct SchemaEntry {
+    /// `table` or `index`, or `view` or `trigger` in files that `SQLite` wrote
+    pub object_type: String,
+    pub name: String,
+    pub table_name: String,
+    /// 0 for views and triggers
+    pub root_page: u32,
+    /// None for automatic indexes
+    pub sql: Option<String>,
+}
+
+/// Reads a database file in memory, whether sqlighters or `SQLite` wrote it.
+/// Text is converted to UTF-8, so that values compare equal to the ones that were written.
+/// WITHOUT ROWID tables, freelists and WAL files are not read.
+pub struct DatabaseReader<'a> {
+    bytes: &'a [u8],
+    page_size: usize,
+    usable_size: usize,
+    page_count: u32,
+    encoding: TextEncoding,
+}
+
+impl<'a> DatabaseReader<'a> {
+    /// Reads the file header
+    pub fn new(bytes: &'a [u8]) -> Result<Self, Error> {
+        if bytes.len() < FILE_HEADER_SIZE || bytes[..16] != MAGIC_HEADER {
+            return Err(corrupt(1, "not a SQLite database"));
+        }
+        // 65536 doesn't fit in 2 bytes and is written as 1
+        let page_size = match BigEndian::read_u16(&bytes[16..]) {
+            1 => MAX_PAGE_SIZE,
+            page_size => usize::from(page_size),
+        };
+        if !page_size.is_power_of_two() || page_size < MIN_PAGE_SIZE {
+            return Err(corrupt(1, &format!("invalid page size {page_size}")));
+        }
+        let usable_size = page_size - usize::from(bytes[20]);
+        if usable_size < 480 {
+            return Err(corrupt(1, "too many reserved bytes per page"));
+        }
+        let encoding = match BigEndian::read_u32(&bytes[56..]) {
+            0 | 1 => TextEncoding::Utf8,
+            2 => TextEncoding::Utf16le,
+            3 => TextEncoding::Utf16be,
+            encoding => return Err(corrupt(1, &format!("unknown text encoding {encoding}"))),
+        };
+        // like SQLite, the page count in the header only counts when the version-valid-for number
+        // matches the change counter, and otherwise the size of the file does
+        let header_page_count = BigEndian::read_u32(&bytes[28..]);
+        let page_count = if header_page_count != 0 && bytes[24..28] == bytes[92..96] {
+            header_page_count
+        } else {
+            u32::try_from(bytes.len() / page_size).unwrap_or(u32::MAX)
+        };
+        if page_count == 0 {
+            return Err(corrupt(1, "the file is shorter than a page"));
+        }
+        Ok(Self {
+            bytes,
+            page_size,
+            usable_size,
+            page_count,
+            encoding,
+        })
+    }
+
+    pub fn page_size(&self) -> usize {
+        self.page_size
+    }
+
+    pub fn page_count(&self) -> u32 {
+        self.page_count
+    }
+
+    pub fn encoding(&self) -> TextEncoding {
+        self.encoding
+    }
+
+    /// the page size without the bytes that are reserved at the end of every page
+    pub(crate) fn usable_size(&self) -> usize {
+        self.usable_size
+    }
+
+    /// The tables and indexes in `sqlite_master`, in the order they are stored
+    pub fn schema(&self) -> Result<Vec<SchemaEntry>, Error> {
+        self.payload_cells(1, true)?
+            .into_iter()
+            .map(|(page_number, cell)| {
+                let values = self.values(page_number, &cell)?;
+                // like SQLite, invalid UTF-8 doesn't make the schema unreadable
+                let text = |position: usize| {
+                    values
+                        .get(position)
+                        .filter(|value| value.is_text())
+                        .map(|value| String::from_utf8_lossy(&value.data).into_owned())
+                };
+                let invalid = || corrupt(page_number, "invalid sqlite_master record");
+                Ok(SchemaEntry {
+                    object_type: text(0).ok_or_else(invalid)?,
+                    name: text(1).ok_or_else(invalid)?,
+                    table_name: text(2).ok_or_else(invalid)?,
+                    root_page: values
+                        .get(3)
+                        .and_then(Value::as_integer)
+                        .and_then(|root_page| u32::try_from(root_page).ok())
+                        .ok_or_else(invalid)?,
+                    sql: text(4),
+                })
+            })
+            .collect()
+    }
+
+    /// The records of the table, in rowid order
+    pub fn records(&self, table_name: &str) -> Result<Vec<Record>, Error> {
+        let root_page = self.root_page("table", table_name)?;
+        self.payload_cells(root_page, true)?
+            .into_iter()
+            .map(|(page_number, cell)| {
+                let rowid = cell.rowid.unwrap_or_default(); // table cells have one
+                Ok(Record::with_values(rowid, self.values(page_number, &cell)?))
+            })
+            .collect()
+    }
+
+    /// The entries of the index, in index order. The last value of an entry is the rowid.
+    pub fn index_entries(&self, index_name: &str) -> Result<Vec<Vec<Value>>, Error> {
+        let root_page = self.root_page("index", index_name)?;
+        self.payload_cells(root_page, false)?
+            .into_iter()
+            .map(|(page_number, cell)| self.values(page_number, &cell))
+            .collect()
+    }
+
+    fn root_page(&self, object_type: &str, name: &str) -> Result<u32, Error> {
+        self.schema()?
+            .into_iter()
+            .find(|entry| entry.object_type == object_type && entry.name.eq_ignore_ascii_case(name))
+            .map(|entry| entry.root_page)
+            .ok_or_else(|| Error::InvalidSchema(format!("no {object_type} {name}")))
+    }
+
+    /// The bytes of the page, numbered from 1
+    pub(crate) fn page(&self, page_number: u32) -> Result<&'a [u8], Error> {
+        if page_number == 0 || page_number > self.page_count {
+            return Err(corrupt(
+                page_number,
+                &format!("page number out of range 1..={}", self.page_count),
+            ));
+        }
+        let start = (page_number as usize - 1) * self.page_size;
+        self.bytes
+            .get(start..start + self.page_size)
+            .ok_or_else(|| corrupt(page_number, "the file ends before the page"))
+    }
+
+    /// The page and its b-tree header, which comes after the file header on the first page
+    pub(crate) fn btree_page(
+        &self,
+        page_number: u32,
+    ) -> Result<(&'a [u8], usize, PageHeader), Error> {
+        let page = self.page(page_number)?;
+        let header_offset = if page_number == 1 {
+            FILE_HEADER_SIZE
+        } else {
+            0
+        };
+        let header = PageHeader::read(&page[header_offset..])
+            .ok_or_else(|| corrupt(page_number, "not a b-tree page"))?;
+        Ok((page, header_offset, header))
+    }
+
+    /// The cells of a b-tree page, in key order
+    pub(crate) fn cells(
+        &self,
+        page_number: u32,
+        page: &'a [u8],
+        header_offset: usize,
+        header: &PageHeader,
+    ) -> Result<Vec<CellContent<'a>>, Error> {
+        let pointers = header
+            .cell_pointers(page, header_offset)
+            .ok_or_else(|| corrupt(page_number, "too many cells for the page"))?;
+        pointers
+            .into_iter()
+            .enumerate()
+            .map(|(index, offset)| {
+                page::read_cell(&page[..self.usable_size], offset, header, self.usable_size)
+                    .ok_or_else(|| corrupt(page_number, &format!("cell {index} is out of bounds")))
+            })
+            .collect()
+    }
+
+    /// The cells that have a payload, in key order, with the number of their page:
+    /// the leaf cells of a table b-tree, or all cells of an index b-tree.
+    /// The tree is walked without recursion, and a page that is reached twice is an error.
+    fn payload_cells(
+        &self,
+        root_page: u32,
+        is_table: bool,
+    ) -> Result<Vec<(u32, CellContent<'a>)>, Error> {
+        enum Step<'a> {
+            Page(u32),
+            Cell(u32, CellContent<'a>),
+        }
+
+        let mut visited = HashSet::new();
+        let mut steps = vec![Step::Page(root_page)];
+        let mut payload_cells = Vec::new();
+        while let Some(step) = steps.pop() {
+            let page_number = match step {
+                Step::Page(page_number) => page_number,
+                Step::Cell(page_number, cell) => {
+                    payload_cells.push((page_number, cell));
+                    continue;
+                }
+            };
+            if !visited.insert(page_number) {
+                return Err(corrupt(
+                    page_number,
+                    "the page is in a b-tree more than once",
+                ));
+            }
+            let (page, header_offset, header) = self.btree_page(page_number)?;
+            if header.is_table() != is_table {
+                let expected = if is_table { "table" } else { "index" };
+                return Err(corrupt(
+                    page_number,
+                    &format!("expected a {expected} b-tree page"),
+                ));
+            }
+
+            // the steps are taken in reverse order
+            if let Some(rightmost_pointer) = header.rightmost_pointer {
+                steps.push(Step::Page(rightmost_pointer));
+            }
+            for cell in self
+                .cells(page_number, page, header_offset, &header)?
+                .into_iter()
+                .rev()
+            {
+                let left_child = cell.left_child;
+                // interior cells of an index come after their left child
+                if header.is_leaf() || !is_table {
+                    steps.push(Step::Cell(page_number, cell));
+                }
+                if let Some(left_child) = left_child {
+                    steps.push(Step::Page(left_child));
+                }
+            }
+        }
+        Ok(payload_cells)
+    }
+
+    /// The whole payload of the cell, including the part on overflow pages
+    pub(crate) fn payload(
+        &self,
+        page_number: u32,
+        cell: &CellContent<'a>,
+    ) -> Result<Cow<'a, [u8]>, Error> {
+        let Some(mut overflow_page) = cell.first_overflow_page else {
+            return Ok(Cow::Borrowed(cell.local_payload));
+        };
+        if cell.payload_len / (self.usable_size - 4) > self.page_count as usize {
+            return Err(corrupt(page_number, "the payload is larger than the file"));
+        }
+        let mut payload = Vec::with_capacity(cell.payload_len);
+        payload.extend_from_slice(cell.local_payload);
+        while payload.len() < cell.payload_len {
+            if overflow_page == 0 {
+                return Err(corrupt(page_number, "the overflow chain ends too soon"));
+            }
+            let page = self.page(overflow_page)?;
+            let len = (cell.payload_len - payload.len()).min(self.usable_size - 4);
+            payload.extend_from_slice(&page[4..4 + len]);
+            overflow_page = BigEndian::read_u32(page);
+        }
+        Ok(Cow::Owned(payload))
+    }
+
+    /// The values of the record in the cell, with text in UTF-8
+    fn values(&self, page_number: u32, cell: &CellContent<'a>) -> Result<Vec<Value>, Error> {
+        let payload = self.payload(page_number, cell)?;
+        let values =
+            record::read_payload(&payload).ok_or_else(|| corrupt(page_number, "invalid record"))?;
+        Ok(values
+            .into_iter()
+            .map(|value| value.decode_text(self.encoding))
+            .collect())
+    }
+}
+
+pub(crate) fn corrupt(page: u32, message: &str) -> Error {
+    Error::Corrupt {
+        page,
+        message: message.to_owned(),
+    }
+}
@@ -1,72 +1,132 @@
 use crate::database::SchemaRecord;
-use crate::values::{integer, string, Value};
+use crate::values::{self, integer, string, TextEncoding, Value};
 use crate::varint;
+use std::mem;
 
+/// `SQLite` won't read records bigger than this
+pub(crate) const MAX_PAYLOAD_SIZE: usize = 1_000_000_000;
+
+/// A table row, identified by its rowid
+#[derive(Debug, PartialEq)]
 pub struct Record {
-    pub rowid: u64,
-    //or should it be i64??
+    pub rowid: i64,
     values: Vec<Value>,
 }
 
 impl Record {
-    /// Users have to supply the rowid. In the java version this can be done automatically
-    pub fn new(rowid: u64) -> Self {
+    /// The rowid is replaced when the builder assigns rowids automatically,
+    /// see [`DatabaseBuilder::auto_rowid`](crate::DatabaseBuilder::auto_rowid)
+    pub fn new(rowid: i64) -> Self {
         Self {
             rowid,
             values: vec![],
         }
     }
 
+    pub(crate) fn with_values(rowid: i64, values: Vec<Value>) -> Self {
+        Self { rowid, values }
+    }
+
     pub fn add_value(&mut self, value: Value) {
         self.values.push(value);
     }
 
-    /// length of the byte representation
-    pub fn bytes_len(&self) -> u16 {
-        let record_length: u16 = self.values.iter().map(Value::len).sum();
-        record_length + 1
+    pub fn values(&self) -> &[Value] {
+        &self.values
     }
-}
 
-impl From<Record> for Vec<u8> {
-    fn from(mut record: Record) -> Vec<u8> {
-        let record_length = record.bytes_len();
-        let mut length_bytes = varint::write(u64::from(record_length));
-        let mut rowid_bytes = varint::write(record.rowid);
+    pub(crate) fn value_mut(&mut self, position: usize) -> Option<&mut Value> {
+        self.values.get_mut(position)
+    }
 
-        let mut buffer =
-            Vec::with_capacity(length_bytes.len() + rowid_bytes.len() + record_length as usize);
-        buffer.append(&mut length_bytes);
-        buffer.append(&mut rowid_bytes);
+    pub(crate) fn encode_text(&mut self, encoding: TextEncoding) {
+        if encoding != TextEncoding::Utf8 {
+            self.values = mem::take(&mut self.values)
+                .into_iter()
+                .map(|value| value.encode_text(encoding))
+                .collect();
+        }
+    }
 
-        // 'The initial portion of the payload that does not spill to overflow pages.'
+    /// length of the byte representation of the payload
+    pub(crate) fn bytes_len(&self) -> usize {
+        let header_length = self.header_len();
+        let values_length: usize = self.values.iter().map(|v| v.data.len()).sum();
+        header_length + values_length
+    }
+
+    /// the record header consists of its own length, followed by all column types
+    fn header_len(&self) -> usize {
         let length_of_encoded_column_types: usize =
-            record.values.iter().map(|v| v.datatype.len()).sum();
-        buffer.append(&mut varint::write(
-            (length_of_encoded_column_types + 1) as u64,
-        ));
+            self.values.iter().map(|v| v.datatype.len()).sum();
+        let mut header_length = length_of_encoded_column_types + 1;
+        while varint::write(header_length as u64).len() + length_of_encoded_column_types
+            > header_length
+        {
+            header_length += 1;
+        }
+        header_length
+    }
+
+    /// The payload, ie. the record header followed by the values,
+    /// without the length and the rowid that precede it in a cell
+    pub(crate) fn into_payload(mut self) -> Vec<u8> {
+        let mut buffer = Vec::with_capacity(self.bytes_len());
+        buffer.append(&mut varint::write(self.header_len() as u64));
 
         //write all types
-        for v in &mut record.values {
+        for v in &mut self.values {
             buffer.append(&mut v.datatype);
         }
 
         //  write all values
-        for v in &mut record.values {
+        for v in &mut self.values {
             buffer.append(&mut v.data);
         }
         buffer
     }
 }
 
+/// The values in a payload, in the encoding of the database.
+/// Returns None when the payload is not a valid record.
+pub(crate) fn read_payload(payload: &[u8]) -> Option<Vec<Value>> {
+    let (header_len, mut position) = varint::read(payload)?;
+    let header_len = usize::try_from(header_len).ok()?;
+    let mut data = payload.get(header_len..)?;
+    let mut values = Vec::new();
+    while position < header_len {
+        let (serial_type, len) = varint::read(payload.get(position..header_len)?)?;
+        position += len;
+        let value_len = values::serial_type_len(serial_type)?;
+        let value = data.get(..value_len)?;
+        values.push(values::from_serial_type(serial_type, value));
+        data = &data[value_len..];
+    }
+    Some(values)
+}
+
+impl From<Record> for Vec<u8> {
+    fn from(record: Record) -> Vec<u8> {
+        let mut length_bytes = varint::write(record.bytes_len() as u64);
+        let mut rowid_bytes = varint::write_signed(record.rowid);
+        let mut payload = record.into_payload();
+
+        let mut buffer = Vec::with_capacity(length_bytes.len() + rowid_bytes.len() + payload.len());
+        buffer.append(&mut length_bytes);
+        buffer.append(&mut rowid_bytes);
+        buffer.append(&mut payload);
+        buffer
+    }
+}
+
 impl From<SchemaRecord> for Record {
     fn from(s: SchemaRecord) -> Self {
         let mut record = Record::new(s.rowid);
-        record.add_value(string("table"));
-        record.add_value(string(&s.table_name.to_ascii_lowercase()));
+        record.add_value(string(s.object_type));
+        record.add_value(string(&s.name.to_ascii_lowercase()));
         record.add_value(string(&s.table_name.to_ascii_lowercase()));
         record.add_value(integer(i64::from(s.root_page)));
-        record.add_value(string(&s.sql));
+        record.add_value(s.sql.as_deref().into()); // NULL for automatic indexes
         record
     }
 }
@@ -82,4 +142,30 @@ mod tests {
         let bytes: Vec<u8> = record.into();
         assert_eq!(bytes, vec![7, 1, 2, 23, 104, 101, 108, 108, 111]);
     }
+
+    #[test]
+    fn test_read_payload() {
+        let mut record = Record::new(1);
+        record.add_value(string("hello"));
+        record.add_value(integer(-300));
+        record.add_value(values::float(0.5));
+        record.add_value(values::null());
+        record.add_value(values::blob(vec![1; 100]));
+        let values = record.values().to_vec();
+        assert_eq!(read_payload(&record.into_payload()), Some(values));
+        assert_eq!(read_payload(&[2, 23, 104]), None);
+    }
+
+    #[test]
+    fn test_long_header() {
+        let mut record = Record::new(1);
+        for _ in 0..127 {
+            record.add_value(integer(0));
+        }
+        // 127 column types and a header length that takes 2 bytes
+        assert_eq!(record.bytes_len(), 129);
+        let payload = record.into_payload();
+        assert_eq!(payload[0..2], [0x81, 0x01]);
+        assert_eq!(payload.len(), 129);
+    }
 }
@@ -0,0 +1,129 @@
+use crate::database::{Database, FilePages};
+use axum_core::body::Body;
+use axum_core::response::{IntoResponse, Response};
+use bytes::Bytes;
+use http::header::{CONTENT_DISPOSITION, CONTENT_LENGTH, CONTENT_TYPE};
+use http::HeaderValue;
+use http_body::{Frame, SizeHint};
+use std::convert::Infallible;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+const CONTENT_TYPE_SQLITE: &str = "application/vnd.sqlite3";
+
+/// A database that is downloaded as a file with the given name
+pub struct Attachment {
+    database: Database,
+    filename: String,
+}
+
+impl Database {
+    /// Responds with a `Content-Disposition` header, so that the database is saved under the file name
+    pub fn attachment(self, filename: &str) -> Attachment {
+        Attachment {
+            database: self,
+            filename: filename.to_owned(),
+        }
+    }
+}
+
+impl IntoResponse for Database {
+    fn into_response(self) -> Response {
+        response(self, None)
+    }
+}
+
+impl IntoResponse for Attachment {
+    fn into_response(self) -> Response {
+        response(self.database, Some(&self.filename))
+    }
+}
+
+/// The body is sent one page at a time, and each page is produced when it is sent.
+/// A [`Database`] can always be written, because [`DatabaseBuilder::build`](crate::DatabaseBuilder::build)
+/// reports what would keep it from being written.
+fn response(database: Database, filename: Option<&str>) -> Response {
+    let size = database.size();
+    let pages = database.pages();
+
+    let mut response = Response::new(Body::new(PagesBody {
+        database,
+        pages,
+        remaining: size,
+    }));
+    let headers = response.headers_mut();
+    headers.insert(CONTENT_TYPE, HeaderValue::from_static(CONTENT_TYPE_SQLITE));
+    headers.insert(CONTENT_LENGTH, HeaderValue::from(size));
+    if let Some(filename) = filename {
+        if let Ok(value) = HeaderValue::try_from(content_disposition(filename)) {
+            headers.insert(CONTENT_DISPOSITION, value);
+        }
+    }
+    response
+}
+
+/// Characters that can't go in a quoted string are replaced by an underscore
+fn content_disposition(filename: &str) -> String {
+    let filename: String = filename
+        .chars()
+        .map(|c| {
+            if c.is_ascii_graphic() && c != '"' && c != '\\' || c == ' ' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("attachment; filename=\"{filename}\"")
+}
+
+struct PagesBody {
+    database: Database,
+    pages: FilePages,
+    remaining: u64,
+}
+
+impl http_body::Body for PagesBody {
+    type Data = Bytes;
+    type Error = Infallible;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, Infallible>>> {
+        let PagesBody {
+            database,
+            pages,
+            remaining,
+        } = &mut *self;
+        Poll::Ready(pages.next(database).map(|page| {
+            *remaining -= page.len() as u64;
+            Ok(Frame::data(Bytes::from(page.into_owned())))
+        }))
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.remaining == 0
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        SizeHint::with_exact(self.remaining)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_content_disposition() {
+        assert_eq!(
+            content_disposition("foo bar.db"),
+            "attachment; filename=\"foo bar.db\""
+        );
+        assert_eq!(
+            content_disposition("\"Łódź\".db"),
+            "attachment; filename=\"___d__.db\""
+        );
+    }
+}
@@ -0,0 +1,33 @@
+use crate::error::Error;
+use crate::record::Record;
+
+/// A struct that is stored as a row of its own table.
+/// With the `derive` feature, it is implemented by `#[derive(SqliteRow)]`:
+///
+/// ```ignore
+/// #[derive(SqliteRow)]
+/// #[sqlite(table = "people")]
+/// struct Person {
+///     #[sqlite(primary_key)]
+///     id: i64,
+///     #[sqlite(rename = "full_name")]
+///     name: String,
+///     #[sqlite(type = "VARCHAR(2)")]
+///     country: Option<String>,
+/// }
+/// ```
+/// Fields become columns in the order of declaration. Fields that are not an `Option` are `NOT NULL`.
+/// The table is named after the struct in snake case, unless it is renamed.
+pub trait SqliteRow {
+    /// The name of the table
+    fn table_name() -> &'static str;
+
+    /// The `CREATE TABLE` statement
+    fn create_table() -> String;
+
+    /// The record with the given rowid.
+    /// When the primary key is a single INTEGER column, it is an alias for the rowid:
+    /// its value is the rowid instead, and the column itself is stored as NULL.
+    /// It is an error when that value doesn't fit in a rowid.
+    fn into_record(self, rowid: i64) -> Result<Record, Error>;
+}
@@ -0,0 +1,512 @@
+use crate::error::Error;
+use crate::sql::quote_identifier;
+use crate::values::{self, Value};
+use serde::ser::{self, Impossible, Serialize};
+
+/// The columns of a table that is filled with `Serialize` rows.
+/// The first row determines the names, the first value that isn't NULL determines the type.
+pub(crate) struct Columns {
+    names: Vec<String>,
+    types: Vec<Option<&'static str>>,
+}
+
+impl Columns {
+    pub(crate) fn new() -> Self {
+        Self {
+            names: Vec::new(),
+            types: Vec::new(),
+        }
+    }
+
+    /// The values of the row, in column order. Fields that are missing are NULL.
+    pub(crate) fn values<T: Serialize>(&mut self, row: &T) -> Result<Vec<Value>, Error> {
+        let fields = row.serialize(RowSerializer)?;
+        if self.names.is_empty() {
+            self.names = fields.iter().map(|(name, _)| name.clone()).collect();
+            self.types = vec![None; fields.len()];
+        }
+
+        let mut values = vec![values::null(); self.names.len()];
+        for (name, value) in fields {
+            let position = self
+                .names
+                .iter()
+                .position(|column| *column == name)
+                .ok_or_else(|| Error::Serialize(format!("field {name} is not in the first row")))?;
+            if self.types[position].is_none() {
+                self.types[position] = value.sql_type();
+            }
+            values[position] = value;
+        }
+        Ok(values)
+    }
+
+    /// Columns without any values get no type
+    pub(crate) fn create_table(&self, table_name: &str) -> String {
+        let columns: Vec<String> = self
+            .names
+            .iter()
+            .zip(&self.types)
+            .map(|(name, sql_type)| match sql_type {
+                Some(sql_type) => format!("{} {sql_type}", quote_identifier(name)),
+                None => quote_identifier(name),
+            })
+            .collect();
+        format!(
+            "CREATE TABLE {}({})",
+            quote_identifier(table_name),
+            columns.join(", ")
+        )
+    }
+}
+
+fn unsupported<T>(what: &str) -> Result<T, Error> {
+    Err(Error::Serialize(format!("{what} are not supported")))
+}
+
+/// A row is a struct or a map, with a column for every field
+struct RowSerializer;
+
+/// Collects the fields of a row
+struct FieldsSerializer {
+    fields: Vec<(String, Value)>,
+    key: Option<String>,
+}
+
+/// Turns a single field into a [`Value`]
+struct ValueSerializer;
+
+impl FieldsSerializer {
+    fn new() -> Self {
+        Self {
+            fields: Vec::new(),
+            key: None,
+        }
+    }
+
+    fn add<T: ?Sized + Serialize>(&mut self, name: String, value: &T) -> Result<(), Error> {
+        let value = value
+            .serialize(ValueSerializer)
+            .map_err(|error| Error::Serialize(format!("field {name}: {error}")))?;
+        self.fields.push((name, value));
+        Ok(())
+    }
+}
+
+impl ser::Serializer for RowSerializer {
+    type Ok = Vec<(String, Value)>;
+    type Error = Error;
+    type SerializeSeq = Impossible<Self::Ok, Error>;
+    type SerializeTuple = Impossible<Self::Ok, Error>;
+    type SerializeTupleStruct = Impossible<Self::Ok, Error>;
+    type SerializeTupleVariant = Impossible<Self::Ok, Error>;
+    type SerializeMap = FieldsSerializer;
+    type SerializeStruct = FieldsSerializer;
+    type SerializeStructVariant = Impossible<Self::Ok, Error>;
+
+    fn serialize_bool(self, _: bool) -> Result<Self::Ok, Error> {
+        unsupported("rows other than structs or maps")
+    }
+
+    fn serialize_i8(self, _: i8) -> Result<Self::Ok, Error> {
+        unsupported("rows other than structs or maps")
+    }
+
+    fn serialize_i16(self, _: i16) -> Result<Self::Ok, Error> {
+        unsupported("rows other than structs or maps")
+    }
+
+    fn serialize_i32(self, _: i32) -> Result<Self::Ok, Error> {
+        unsupported("rows other than structs or maps")
+    }
+
+    fn serialize_i64(self, _: i64) -> Result<Self::Ok, Error> {
+        unsupported("rows other than structs or maps")
+    }
+
+    fn serialize_u8(self, _: u8) -> Result<Self::Ok, Error> {
+        unsupported("rows other than structs or maps")
+    }
+
+    fn serialize_u16(self, _: u16) -> Result<Self::Ok, Error> {
+        unsupported("rows other than structs or maps")
+    }
+
+    fn serialize_u32(self, _: u32) -> Result<Self::Ok, Error> {
+        unsupported("rows other than structs or maps")
+    }
+
+    fn serialize_u64(self, _: u64) -> Result<Self::Ok, Error> {
+        unsupported("rows other than structs or maps")
+    }
+
+    fn serialize_f32(self, _: f32) -> Result<Self::Ok, Error> {
+        unsupported("rows other than structs or maps")
+    }
+
+    fn serialize_f64(self, _: f64) -> Result<Self::Ok, Error> {
+        unsupported("rows other than structs or maps")
+    }
+
+    fn serialize_char(self, _: char) -> Result<Self::Ok, Error> {
+        unsupported("rows other than structs or maps")
+    }
+
+    fn serialize_str(self, _: &str) -> Result<Self::Ok, Error> {
+        unsupported("rows other than structs or maps")
+    }
+
+    fn serialize_bytes(self, _: &[u8]) -> Result<Self::Ok, Error> {
+        unsupported("rows other than structs or maps")
+    }
+
+    fn serialize_none(self) -> Result<Self::Ok, Error> {
+        unsupported("rows other than structs or maps")
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<Self::Ok, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Self::Ok, Error> {
+        unsupported("rows other than structs or maps")
+    }
+
+    fn serialize_unit_struct(self, _: &'static str) -> Result<Self::Ok, Error> {
+        unsupported("rows other than structs or maps")
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+    ) -> Result<Self::Ok, Error> {
+        unsupported("rows other than structs or maps")
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: &T,
+    ) -> Result<Self::Ok, Error> {
+        unsupported("rows other than structs or maps")
+    }
+
+    fn serialize_seq(self, _: Option<usize>) -> Result<Self::SerializeSeq, Error> {
+        unsupported("rows other than structs or maps")
+    }
+
+    fn serialize_tuple(self, _: usize) -> Result<Self::SerializeTuple, Error> {
+        unsupported("rows other than structs or maps")
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeTupleStruct, Error> {
+        unsupported("rows other than structs or maps")
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeTupleVariant, Error> {
+        unsupported("rows other than structs or maps")
+    }
+
+    fn serialize_map(self, _: Option<usize>) -> Result<Self::SerializeMap, Error> {
+        Ok(FieldsSerializer::new())
+    }
+
+    fn serialize_struct(self, _: &'static str, _: usize) -> Result<Self::SerializeStruct, Error> {
+        Ok(FieldsSerializer::new())
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeStructVariant, Error> {
+        unsupported("rows other than structs or maps")
+    }
+}
+
+impl ser::SerializeStruct for FieldsSerializer {
+    type Ok = Vec<(String, Value)>;
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.add(key.to_owned(), value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Error> {
+        Ok(self.fields)
+    }
+}
+
+/// map keys have to be text
+impl ser::SerializeMap for FieldsSerializer {
+    type Ok = Vec<(String, Value)>;
+    type Error = Error;
+
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<(), Error> {
+        let key = key.serialize(ValueSerializer)?;
+        if !key.is_text() {
+            return unsupported("map keys other than text");
+        }
+        self.key = Some(String::from_utf8_lossy(&key.data).into_owned());
+        Ok(())
+    }
+
+    fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        let key = self.key.take().unwrap_or_default(); // serde calls serialize_key first
+        self.add(key, value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Error> {
+        Ok(self.fields)
+    }
+}
+
+impl ser::Serializer for ValueSerializer {
+    type Ok = Value;
+    type Error = Error;
+    type SerializeSeq = Impossible<Value, Error>;
+    type SerializeTuple = Impossible<Value, Error>;
+    type SerializeTupleStruct = Impossible<Value, Error>;
+    type SerializeTupleVariant = Impossible<Value, Error>;
+    type SerializeMap = Impossible<Value, Error>;
+    type SerializeStruct = Impossible<Value, Error>;
+    type SerializeStructVariant = Impossible<Value, Error>;
+
+    fn serialize_bool(self, value: bool) -> Result<Value, Error> {
+        Ok(value.into())
+    }
+
+    fn serialize_i8(self, value: i8) -> Result<Value, Error> {
+        Ok(value.into())
+    }
+
+    fn serialize_i16(self, value: i16) -> Result<Value, Error> {
+        Ok(value.into())
+    }
+
+    fn serialize_i32(self, value: i32) -> Result<Value, Error> {
+        Ok(value.into())
+    }
+
+    fn serialize_i64(self, value: i64) -> Result<Value, Error> {
+        Ok(value.into())
+    }
+
+    fn serialize_u8(self, value: u8) -> Result<Value, Error> {
+        Ok(value.into())
+    }
+
+    fn serialize_u16(self, value: u16) -> Result<Value, Error> {
+        Ok(value.into())
+    }
+
+    fn serialize_u32(self, value: u32) -> Result<Value, Error> {
+        Ok(value.into())
+    }
+
+    /// `SQLite` integers are signed
+    fn serialize_u64(self, value: u64) -> Result<Value, Error> {
+        i64::try_from(value)
+            .map(values::integer)
+            .map_err(|_| Error::Serialize(format!("{value} does not fit in an integer")))
+    }
+
+    fn serialize_f32(self, value: f32) -> Result<Value, Error> {
+        Ok(value.into())
+    }
+
+    fn serialize_f64(self, value: f64) -> Result<Value, Error> {
+        Ok(value.into())
+    }
+
+    fn serialize_char(self, value: char) -> Result<Value, Error> {
+        Ok(values::string(value.encode_utf8(&mut [0; 4])))
+    }
+
+    fn serialize_str(self, value: &str) -> Result<Value, Error> {
+        Ok(value.into())
+    }
+
+    fn serialize_bytes(self, value: &[u8]) -> Result<Value, Error> {
+        Ok(value.into())
+    }
+
+    fn serialize_none(self) -> Result<Value, Error> {
+        Ok(values::null())
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<Value, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Value, Error> {
+        Ok(values::null())
+    }
+
+    fn serialize_unit_struct(self, _: &'static str) -> Result<Value, Error> {
+        Ok(values::null())
+    }
+
+    /// enums without data are stored as the name of the variant
+    fn serialize_unit_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+    ) -> Result<Value, Error> {
+        Ok(values::string(variant))
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _: &'static str,
+        value: &T,
+    ) -> Result<Value, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: &T,
+    ) -> Result<Value, Error> {
+        unsupported("enums with data")
+    }
+
+    fn serialize_seq(self, _: Option<usize>) -> Result<Self::SerializeSeq, Error> {
+        unsupported("nested values")
+    }
+
+    fn serialize_tuple(self, _: usize) -> Result<Self::SerializeTuple, Error> {
+        unsupported("nested values")
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeTupleStruct, Error> {
+        unsupported("nested values")
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeTupleVariant, Error> {
+        unsupported("enums with data")
+    }
+
+    fn serialize_map(self, _: Option<usize>) -> Result<Self::SerializeMap, Error> {
+        unsupported("nested values")
+    }
+
+    fn serialize_struct(self, _: &'static str, _: usize) -> Result<Self::SerializeStruct, Error> {
+        unsupported("nested values")
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeStructVariant, Error> {
+        unsupported("enums with data")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Serialize;
+    use std::collections::BTreeMap;
+
+    #[derive(Serialize)]
+    struct Row {
+        id: u32,
+        name: Option<String>,
+        score: f64,
+    }
+
+    #[test]
+    fn test_create_table() -> Result<(), Error> {
+        let mut columns = Columns::new();
+        columns.values(&Row {
+            id: 1,
+            name: None,
+            score: 1.5,
+        })?;
+        assert_eq!(
+            columns.create_table("rows"),
+            "CREATE TABLE \"rows\"(\"id\" integer, \"name\", \"score\" real)"
+        );
+
+        columns.values(&Row {
+            id: 2,
+            name: Some("foo".to_owned()),
+            score: 2.5,
+        })?;
+        assert_eq!(
+            columns.create_table("rows"),
+            "CREATE TABLE \"rows\"(\"id\" integer, \"name\" text, \"score\" real)"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_map_fields_by_name() -> Result<(), Error> {
+        let mut columns = Columns::new();
+        columns.values(&BTreeMap::from([("a", 1), ("b", 2)]))?;
+        let values = columns.values(&BTreeMap::from([("b", 3)]))?;
+        assert!(values[0].is_null());
+        assert_eq!(values[1].data, vec![3]);
+        assert!(columns.values(&BTreeMap::from([("c", 3)])).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_nested_values() {
+        #[derive(Serialize)]
+        struct Nested {
+            tags: Vec<String>,
+        }
+        let mut columns = Columns::new();
+        assert!(matches!(
+            columns.values(&Nested { tags: vec![] }),
+            Err(Error::Serialize(_))
+        ));
+    }
+}
@@ -0,0 +1,722 @@
+use crate::values::Collation;
+
+/// Just enough of a SQL parser to get column names out of `CREATE TABLE`
+/// and the indexed columns out of `CREATE INDEX` statements
+#[derive(Debug, PartialEq)]
+enum Token {
+    Word(String),
+    Quoted(String),
+    Literal(String),
+    Symbol(char),
+}
+
+impl Token {
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self, Token::Word(word) if word.eq_ignore_ascii_case(keyword))
+    }
+
+    fn identifier(&self) -> Option<&str> {
+        match self {
+            Token::Word(name) | Token::Quoted(name) => Some(name),
+            _ => None,
+        }
+    }
+}
+
+fn tokenize(sql: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut chars = sql.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {}
+            '"' | '`' | '[' | '\'' => {
+                let closing = if c == '[' { ']' } else { c };
+                let mut quoted = String::new();
+                while let Some(q) = chars.next() {
+                    if q == closing {
+                        // a doubled quote is an escaped quote
+                        if closing != ']' && chars.peek() == Some(&closing) {
+                            chars.next();
+                        } else {
+                            break;
+                        }
+                    }
+                    quoted.push(q);
+                }
+                if c == '\'' {
+                    tokens.push(Token::Literal(quoted));
+                } else {
+                    tokens.push(Token::Quoted(quoted));
+                }
+            }
+            c if c.is_alphanumeric() || c == '_' => {
+                let mut word = String::from(c);
+                while let Some(&w) = chars.peek() {
+                    if w.is_alphanumeric() || w == '_' || w == '$' {
+                        word.push(w);
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                if c.is_ascii_digit() {
+                    tokens.push(Token::Literal(word));
+                } else {
+                    tokens.push(Token::Word(word));
+                }
+            }
+            c => tokens.push(Token::Symbol(c)),
+        }
+    }
+    tokens
+}
+
+/// Splits the tokens between the first opening parenthesis and its closing counterpart on commas.
+/// Returns the parts and the tokens after the closing parenthesis.
+fn split_parenthesized(tokens: &[Token]) -> Option<(Vec<&[Token]>, &[Token])> {
+    let start = tokens.iter().position(|t| *t == Token::Symbol('('))? + 1;
+    let mut parts = Vec::new();
+    let mut depth = 0;
+    let mut part_start = start;
+    for (index, token) in tokens.iter().enumerate().skip(start) {
+        match token {
+            Token::Symbol('(') => depth += 1,
+            Token::Symbol(')') if depth == 0 => {
+                parts.push(&tokens[part_start..index]);
+                return Some((parts, &tokens[index + 1..]));
+            }
+            Token::Symbol(')') => depth -= 1,
+            Token::Symbol(',') if depth == 0 => {
+                parts.push(&tokens[part_start..index]);
+                part_start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Puts double quotes around a name, so that it can be anything
+#[cfg(any(
+    feature = "serde",
+    feature = "csv",
+    feature = "json",
+    feature = "arrow"
+))]
+pub(crate) fn quote_identifier(identifier: &str) -> String {
+    format!("\"{}\"", identifier.replace('"', "\"\""))
+}
+
+const TABLE_CONSTRAINTS: [&str; 5] = ["CONSTRAINT", "PRIMARY", "UNIQUE", "CHECK", "FOREIGN"];
+
+/// The names of the columns in a `CREATE TABLE` statement, in order
+pub(crate) fn column_names(create_table_sql: &str) -> Vec<String> {
+    let tokens = tokenize(create_table_sql);
+    let Some((parts, _)) = split_parenthesized(&tokens) else {
+        return Vec::new();
+    };
+    parts
+        .into_iter()
+        .filter_map(|part| part.first())
+        .filter(|first| !TABLE_CONSTRAINTS.iter().any(|c| first.is_keyword(c)))
+        .filter_map(|first| first.identifier().map(str::to_owned))
+        .collect()
+}
+
+/// Whether the `CREATE TABLE` statement ends with the WITHOUT ROWID option.
+/// Such a table is stored as an index b-tree on its PRIMARY KEY.
+pub(crate) fn is_without_rowid(create_table_sql: &str) -> bool {
+    let tokens = tokenize(create_table_sql);
+    split_parenthesized(&tokens).is_some_and(|(_, options)| has_without_rowid(options))
+}
+
+/// the table options after the column list
+fn has_without_rowid(options: &[Token]) -> bool {
+    options
+        .windows(2)
+        .any(|pair| pair[0].is_keyword("WITHOUT") && pair[1].is_keyword("ROWID"))
+}
+
+/// A PRIMARY KEY or UNIQUE constraint in a `CREATE TABLE` statement
+struct Constraint {
+    primary_key: bool,
+    columns: Vec<IndexedColumn>,
+    /// a PRIMARY KEY DESC on a column is not a rowid alias, one in a table constraint is
+    on_column: bool,
+}
+
+/// A column in a `CREATE TABLE` statement
+struct ColumnDefinition<'a> {
+    name: &'a str,
+    declared_type: Vec<&'a Token>,
+    /// the name after COLLATE, if it is declared
+    collation: Option<&'a str>,
+}
+
+/// The columns and the PRIMARY KEY and UNIQUE constraints of a `CREATE TABLE` statement
+struct TableConstraints<'a> {
+    columns: Vec<ColumnDefinition<'a>>,
+    constraints: Vec<Constraint>,
+    without_rowid: bool,
+}
+
+impl TableConstraints<'_> {
+    /// An INTEGER PRIMARY KEY on a single column, except a PRIMARY KEY DESC on the column itself,
+    /// in a table that has a rowid
+    fn is_rowid_alias(&self, constraint: &Constraint) -> bool {
+        if self.without_rowid {
+            return false;
+        }
+        match &constraint.columns[..] {
+            [column] => {
+                constraint.primary_key
+                    && !(constraint.on_column && column.descending)
+                    && self.columns.iter().any(|definition| {
+                        definition.name.eq_ignore_ascii_case(&column.name)
+                            && matches!(definition.declared_type[..], [token] if token.is_keyword("INTEGER"))
+                    })
+            }
+            _ => false,
+        }
+    }
+
+    fn column(&self, name: &str) -> Option<&ColumnDefinition<'_>> {
+        self.columns
+            .iter()
+            .find(|column| column.name.eq_ignore_ascii_case(name))
+    }
+
+    /// The columns of the constraint. A column constraint has the collation of its column.
+    fn indexed_columns(&self, constraint: &Constraint) -> Vec<IndexedColumn> {
+        constraint
+            .columns
+            .iter()
+            .map(|column| IndexedColumn {
+                collation: column.collation.clone().or_else(|| {
+                    let collation = self.column(&column.name)?.collation?;
+                    Some(collation.to_owned())
+                }),
+                ..column.clone()
+            })
+            .collect()
+    }
+}
+
+fn parse_constraints<'a>(
+    tokens: &'a [Token],
+    create_table_sql: &str,
+) -> Result<TableConstraints<'a>, String> {
+    let mut columns = Vec::new();
+    let mut constraints = Vec::new();
+    let Some((parts, options)) = split_parenthesized(tokens) else {
+        return Ok(TableConstraints {
+            columns,
+            constraints,
+            without_rowid: false,
+        });
+    };
+
+    for part in parts {
+        let Some(first) = part.first() else {
+            continue;
+        };
+        if TABLE_CONSTRAINTS.iter().any(|c| first.is_keyword(c)) {
+            let constraint = if first.is_keyword("CONSTRAINT") {
+                part.get(2..).unwrap_or_default() // skip the name
+            } else {
+                part
+            };
+            let primary_key = constraint.first().is_some_and(|t| t.is_keyword("PRIMARY"));
+            if primary_key || constraint.first().is_some_and(|t| t.is_keyword("UNIQUE")) {
+                let (columns, _) = split_parenthesized(constraint)
+                    .ok_or_else(|| format!("expected a column list in {create_table_sql}"))?;
+                constraints.push(Constraint {
+                    primary_key,
+                    columns: columns
+                        .into_iter()
+                        .map(|column| parse_indexed_column(column, create_table_sql))
+                        .collect::<Result<_, _>>()?,
+                    on_column: false,
+                });
+            }
+        } else if let Some(name) = first.identifier() {
+            columns.push(ColumnDefinition {
+                name,
+                declared_type: declared_type(&part[1..]),
+                collation: declared_collation(&part[1..]),
+            });
+            constraints.extend(column_constraints(name, &part[1..]));
+        }
+    }
+    Ok(TableConstraints {
+        columns,
+        constraints,
+        without_rowid: has_without_rowid(options),
+    })
+}
+
+/// The position of the column that is an alias for the rowid, if the table has one.
+/// `SQLite` stores its value as the rowid and the column itself as NULL.
+pub(crate) fn rowid_alias(create_table_sql: &str) -> Result<Option<usize>, String> {
+    let tokens = tokenize(create_table_sql);
+    let table = parse_constraints(&tokens, create_table_sql)?;
+    let Some(primary_key) = table.constraints.iter().find(|c| c.primary_key) else {
+        return Ok(None);
+    };
+    if !table.is_rowid_alias(primary_key) {
+        return Ok(None);
+    }
+    Ok(table.columns.iter().position(|column| {
+        column
+            .name
+            .eq_ignore_ascii_case(&primary_key.columns[0].name)
+    }))
+}
+
+/// The positions of the PRIMARY KEY columns, in the order of the key. Empty when there is none.
+pub(crate) fn primary_key(create_table_sql: &str) -> Result<Vec<usize>, String> {
+    let tokens = tokenize(create_table_sql);
+    let table = parse_constraints(&tokens, create_table_sql)?;
+    let Some(primary_key) = table.constraints.iter().find(|c| c.primary_key) else {
+        return Ok(Vec::new());
+    };
+    primary_key
+        .columns
+        .iter()
+        .map(|column| {
+            table
+                .columns
+                .iter()
+                .position(|definition| definition.name.eq_ignore_ascii_case(&column.name))
+                .ok_or_else(|| format!("no column {} in {create_table_sql}", column.name))
+        })
+        .collect()
+}
+
+/// The indexes that `SQLite` creates for the PRIMARY KEY and UNIQUE constraints of a table,
+/// named `sqlite_autoindex_<table>_<n>`. An INTEGER PRIMARY KEY is the rowid and doesn't need one.
+/// Constraints on the same columns with the same collations share an index.
+pub(crate) fn autoindexes(
+    table_name: &str,
+    create_table_sql: &str,
+) -> Result<Vec<IndexDefinition>, String> {
+    let tokens = tokenize(create_table_sql);
+    let table = parse_constraints(&tokens, create_table_sql)?;
+
+    let mut indexes: Vec<IndexDefinition> = Vec::new();
+    for constraint in &table.constraints {
+        if table.is_rowid_alias(constraint) {
+            continue;
+        }
+        let columns = table.indexed_columns(constraint);
+        let same_columns = |index: &IndexDefinition| {
+            index.columns.len() == columns.len()
+                && index.columns.iter().zip(&columns).all(|(left, right)| {
+                    left.name.eq_ignore_ascii_case(&right.name)
+                        && collation_name(left).eq_ignore_ascii_case(collation_name(right))
+                })
+        };
+        if indexes.iter().any(same_columns) {
+            continue;
+        }
+        indexes.push(IndexDefinition {
+            name: format!("sqlite_autoindex_{}_{}", table_name, indexes.len() + 1),
+            table_name: table_name.to_owned(),
+            unique: true,
+            columns,
+        });
+    }
+    Ok(indexes)
+}
+
+/// The PRIMARY KEY as an index on the table, named after the table, if the table has one.
+/// A WITHOUT ROWID table is stored as this index, with the other columns after the key.
+pub(crate) fn primary_key_index(
+    table_name: &str,
+    create_table_sql: &str,
+) -> Result<Option<IndexDefinition>, String> {
+    let tokens = tokenize(create_table_sql);
+    let table = parse_constraints(&tokens, create_table_sql)?;
+    Ok(table
+        .constraints
+        .iter()
+        .find(|constraint| constraint.primary_key)
+        .map(|primary_key| IndexDefinition {
+            name: table_name.to_owned(),
+            table_name: table_name.to_owned(),
+            unique: true,
+            columns: table.indexed_columns(primary_key),
+        }))
+}
+
+fn collation_name(column: &IndexedColumn) -> &str {
+    column.collation.as_deref().unwrap_or("BINARY")
+}
+
+/// The collation of each indexed column: the one in the index definition,
+/// or else the one of the column in the table, or else BINARY
+pub(crate) fn index_collations(
+    definition: &IndexDefinition,
+    create_table_sql: &str,
+) -> Result<Vec<Collation>, String> {
+    let tokens = tokenize(create_table_sql);
+    let table = parse_constraints(&tokens, create_table_sql)?;
+    definition
+        .columns
+        .iter()
+        .map(|column| {
+            let name = column.collation.as_deref().or_else(|| {
+                table
+                    .column(&column.name)
+                    .and_then(|definition| definition.collation)
+            });
+            match name {
+                None => Ok(Collation::Binary),
+                Some(name) => Collation::from_name(name).ok_or_else(|| {
+                    format!(
+                        "collation {name} of column {} in index {} is not supported",
+                        column.name, definition.name
+                    )
+                }),
+            }
+        })
+        .collect()
+}
+
+const COLUMN_CONSTRAINTS: [&str; 11] = [
+    "CONSTRAINT",
+    "PRIMARY",
+    "NOT",
+    "NULL",
+    "UNIQUE",
+    "CHECK",
+    "DEFAULT",
+    "COLLATE",
+    "REFERENCES",
+    "GENERATED",
+    "AS",
+];
+
+/// the tokens of the type name that follows the column name
+fn declared_type(column_definition: &[Token]) -> Vec<&Token> {
+    column_definition
+        .iter()
+        .take_while(|t| !COLUMN_CONSTRAINTS.iter().any(|c| t.is_keyword(c)))
+        .filter(|t| matches!(t, Token::Word(_)))
+        .collect()
+}
+
+/// the name after COLLATE, outside of parentheses
+fn declared_collation(column_definition: &[Token]) -> Option<&str> {
+    let mut depth = 0;
+    for (index, token) in column_definition.iter().enumerate() {
+        match token {
+            Token::Symbol('(') => depth += 1,
+            Token::Symbol(')') => depth -= 1,
+            _ if depth == 0 && token.is_keyword("COLLATE") => {
+                return column_definition.get(index + 1)?.identifier();
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// PRIMARY KEY and UNIQUE outside of parentheses, eg. in a CHECK or a DEFAULT
+fn column_constraints(name: &str, column_definition: &[Token]) -> Vec<Constraint> {
+    let mut constraints = Vec::new();
+    let mut depth = 0;
+    for (index, token) in column_definition.iter().enumerate() {
+        match token {
+            Token::Symbol('(') => depth += 1,
+            Token::Symbol(')') => depth -= 1,
+            _ if depth > 0 => {}
+            _ if token.is_keyword("PRIMARY") || token.is_keyword("UNIQUE") => {
+                let primary_key = token.is_keyword("PRIMARY");
+                let order = column_definition.get(index + 2);
+                constraints.push(Constraint {
+                    primary_key,
+                    columns: vec![IndexedColumn {
+                        name: name.to_owned(),
+                        descending: primary_key && order.is_some_and(|t| t.is_keyword("DESC")),
+                        collation: None,
+                    }],
+                    on_column: true,
+                });
+            }
+            _ => {}
+        }
+    }
+    constraints
+}
+
+/// The parts of a `CREATE INDEX` statement that determine the contents of the index
+#[derive(Debug, PartialEq)]
+pub(crate) struct IndexDefinition {
+    pub(crate) name: String,
+    pub(crate) table_name: String,
+    pub(crate) unique: bool,
+    pub(crate) columns: Vec<IndexedColumn>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct IndexedColumn {
+    pub(crate) name: String,
+    pub(crate) descending: bool,
+    /// the name after COLLATE, if it is declared
+    pub(crate) collation: Option<String>,
+}
+
+/// Parses `CREATE [UNIQUE] INDEX [IF NOT EXISTS] name ON table(column [COLLATE name] [ASC|DESC], ...)`.
+/// Indexes on expressions and partial indexes are not supported.
+pub(crate) fn parse_create_index(sql: &str) -> Result<IndexDefinition, String> {
+    let tokens = tokenize(sql);
+    let keyword_at = |position: usize, keyword: &str| {
+        tokens.get(position).is_some_and(|t| t.is_keyword(keyword))
+    };
+    if !keyword_at(0, "CREATE") {
+        return Err(format!("expected CREATE in {sql}"));
+    }
+
+    let unique = keyword_at(1, "UNIQUE");
+    let mut position = if unique { 2 } else { 1 };
+    if !keyword_at(position, "INDEX") {
+        return Err(format!("expected INDEX in {sql}"));
+    }
+    position += 1;
+    if keyword_at(position, "IF") {
+        position += 3; // IF NOT EXISTS
+    }
+
+    let mut name = identifier_at(&tokens, position, sql)?;
+    position += 1;
+    if tokens.get(position) == Some(&Token::Symbol('.')) {
+        // schema name
+        name = identifier_at(&tokens, position + 1, sql)?;
+        position += 2;
+    }
+    if !keyword_at(position, "ON") {
+        return Err(format!("expected ON in {sql}"));
+    }
+    let table_name = identifier_at(&tokens, position + 1, sql)?;
+
+    let (parts, rest) = split_parenthesized(&tokens[position + 2..])
+        .ok_or_else(|| format!("expected a column list in {sql}"))?;
+    if !rest.is_empty() && *rest != [Token::Symbol(';')] {
+        return Err(format!("partial indexes are not supported: {sql}"));
+    }
+
+    let columns = parts
+        .into_iter()
+        .map(|part| parse_indexed_column(part, sql))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(IndexDefinition {
+        name,
+        table_name,
+        unique,
+        columns,
+    })
+}
+
+fn identifier_at(tokens: &[Token], position: usize, sql: &str) -> Result<String, String> {
+    tokens
+        .get(position)
+        .and_then(Token::identifier)
+        .map(str::to_owned)
+        .ok_or_else(|| format!("expected a name in {sql}"))
+}
+
+fn parse_indexed_column(part: &[Token], sql: &str) -> Result<IndexedColumn, String> {
+    let name = part
+        .first()
+        .and_then(Token::identifier)
+        .ok_or_else(|| format!("indexes on expressions are not supported: {sql}"))?
+        .to_owned();
+    let mut rest = &part[1..];
+    let mut collation = None;
+    if rest.first().is_some_and(|t| t.is_keyword("COLLATE")) {
+        let name = rest
+            .get(1)
+            .and_then(Token::identifier)
+            .ok_or_else(|| format!("expected a collation name in {sql}"))?;
+        collation = Some(name.to_owned());
+        rest = &rest[2..];
+    }
+    let descending = match rest {
+        [] => false,
+        [order] if order.is_keyword("ASC") => false,
+        [order] if order.is_keyword("DESC") => true,
+        _ => return Err(format!("indexes on expressions are not supported: {sql}")),
+    };
+    Ok(IndexedColumn {
+        name,
+        descending,
+        collation,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_column_names() {
+        assert_eq!(
+            column_names(
+                "create table foo(id integer primary key, \"bar baz\" varchar(10) not null, [qux] decimal(10, 2), primary key(id))"
+            ),
+            vec!["id", "bar baz", "qux"]
+        );
+    }
+
+    #[test]
+    fn test_is_without_rowid() {
+        assert!(is_without_rowid(
+            "create table foo(id text primary key) strict, without rowid"
+        ));
+        assert!(!is_without_rowid(
+            "create table foo(\"without rowid\" text, check (1 not in (select 1 without rowid)))"
+        ));
+    }
+
+    #[test]
+    fn test_autoindexes() {
+        let names = |sql: &str| -> Vec<Vec<String>> {
+            autoindexes("foo", sql)
+                .unwrap()
+                .into_iter()
+                .map(|index| index.columns.into_iter().map(|c| c.name).collect())
+                .collect()
+        };
+        assert!(names("create table foo(id integer primary key, bar text)").is_empty());
+        assert!(names("create table foo(id INTEGER, bar text, primary key(id desc))").is_empty());
+        assert_eq!(
+            names("create table foo(id integer primary key, bar text unique) without rowid"),
+            vec![vec!["id"], vec!["bar"]]
+        );
+        assert_eq!(
+            names("create table foo(id int primary key, bar text unique, check (bar <> 'unique'))"),
+            vec![vec!["id"], vec!["bar"]]
+        );
+        assert_eq!(
+            names("create table foo(id integer primary key desc, bar text, constraint u unique (bar, id), unique(bar, id))"),
+            vec![vec!["id"], vec!["bar", "id"]]
+        );
+        assert_eq!(
+            autoindexes("foo", "create table foo(a text, b text, primary key(a, b))").unwrap()[0]
+                .name,
+            "sqlite_autoindex_foo_1"
+        );
+        // the collation of the column, unless the constraint has one
+        let collations: Vec<Option<String>> = autoindexes(
+            "foo",
+            "create table foo(a text collate nocase unique, unique(a collate binary), unique(a))",
+        )
+        .unwrap()
+        .into_iter()
+        .map(|index| index.columns[0].collation.clone())
+        .collect();
+        assert_eq!(
+            collations,
+            [Some("nocase".to_owned()), Some("binary".to_owned())]
+        );
+    }
+
+    #[test]
+    fn test_rowid_alias() {
+        let alias = |sql: &str| rowid_alias(sql).unwrap();
+        assert_eq!(
+            alias("create table foo(bar text, id integer primary key)"),
+            Some(1)
+        );
+        assert_eq!(
+            alias("create table foo(id INTEGER, bar text, primary key(id desc))"),
+            Some(0)
+        );
+        assert_eq!(
+            alias("create table foo(id int primary key, bar text)"),
+            None
+        );
+        assert_eq!(alias("create table foo(id integer primary key desc)"), None);
+        assert_eq!(
+            alias("create table foo(a integer, b integer, primary key(a, b))"),
+            None
+        );
+        assert_eq!(alias("create table foo(id integer unique)"), None);
+        assert_eq!(
+            alias("create table foo(id integer primary key) without rowid"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_primary_key_index() {
+        let index = primary_key_index(
+            "foo",
+            "create table foo(a text collate nocase, b integer, primary key(b desc, a)) without rowid",
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(
+            index.columns,
+            [
+                IndexedColumn {
+                    name: "b".to_owned(),
+                    descending: true,
+                    collation: None,
+                },
+                IndexedColumn {
+                    name: "a".to_owned(),
+                    descending: false,
+                    collation: Some("nocase".to_owned()),
+                },
+            ]
+        );
+        assert_eq!(
+            primary_key_index("foo", "create table foo(a unique)"),
+            Ok(None)
+        );
+    }
+
+    #[test]
+    fn test_primary_key() {
+        let key = |sql: &str| primary_key(sql).unwrap();
+        assert_eq!(
+            key("create table foo(bar text, id integer primary key)"),
+            [1]
+        );
+        assert_eq!(key("create table foo(a, b, c, primary key(c, a))"), [2, 0]);
+        assert!(key("create table foo(a unique, b)").is_empty());
+        assert!(primary_key("create table foo(a, primary key(b))").is_err());
+    }
+
+    #[test]
+    fn test_parse_create_index() {
+        assert_eq!(
+            parse_create_index("CREATE UNIQUE INDEX IF NOT EXISTS main.foo_bar ON foo (bar DESC, baz COLLATE binary)"),
+            Ok(IndexDefinition {
+                name: "foo_bar".to_owned(),
+                table_name: "foo".to_owned(),
+                unique: true,
+                columns: vec![
+                    IndexedColumn {
+                        name: "bar".to_owned(),
+                        descending: true,
+                        collation: None
+                    },
+                    IndexedColumn {
+                        name: "baz".to_owned(),
+                        descending: false,
+                        collation: Some("binary".to_owned())
+                    }
+                ],
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_unsupported_index() {
+        assert!(parse_create_index("create index foo_bar on foo(lower(bar))").is_err());
+        assert!(parse_create_index("create index foo_bar on foo(bar) where bar > 1").is_err());
+    }
+}
@@ -0,0 +1,302 @@
+use crate::builder::{already_declared, TableBuilder};
+#[cfg(feature = "csv")]
+use crate::csv_import::{self, CsvOptions};
+use crate::database::{
+    check_page_count, create_schema_pages, set_childrefs_write, set_overflow_refs,
+    write_interior_pages, write_overflow_pages, SchemaRecord,
+};
+use crate::error::Error;
+#[cfg(feature = "json")]
+use crate::json_import;
+use crate::page::Page;
+use crate::record::Record;
+use crate::row::SqliteRow;
+use crate::sql;
+use crate::table_sink::{self, Settings, TableSink};
+use crate::values::TextEncoding;
+use std::borrow::Cow;
+use std::io::{Seek, SeekFrom, Write};
+use std::mem;
+
+/// Writes leaf pages to the sink as soon as they are full, instead of keeping them in memory.
+/// Only the key and page number of every leaf page are kept, to create the interior pages
+/// when a table is complete. The schema follows the last table, and the header page is written last.
+///
+/// The keys of indexes are kept in memory until their table is complete, because they need sorting.
+///
+/// The first page is reserved when the first table is declared, so the encoding and the page size
+/// can be set until then.
+///
+/// The page layout differs from [`write_sqlite`](crate::write_sqlite):
+/// interior pages follow the leaf pages and overflow pages follow the page that refers to them.
+pub struct StreamingDatabaseBuilder<W: Write + Seek> {
+    writer: W,
+    settings: Settings,
+    /// the number of the next page to write
+    page_counter: u32,
+    /// where the reserved header page starts in the sink, which doesn't have to be at its start
+    header_position: u64,
+    /// the tables and indexes that were written
+    schema: Vec<SchemaRecord>,
+    current_table: Option<TableBuilder>,
+    leaf_refs: Vec<(i64, u32)>,
+}
+
+impl<W: Write + Seek> StreamingDatabaseBuilder<W> {
+    /// The database is written from the current position of the writer
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            settings: Settings::default(),
+            page_counter: 2,
+            header_position: 0,
+            schema: Vec::new(),
+            current_table: None,
+            leaf_refs: Vec::new(),
+        }
+    }
+
+    /// Sets the encoding for all text in the database. The default is UTF-8.
+    /// It has to be set before tables are declared.
+    pub fn encoding(&mut self, encoding: TextEncoding) -> Result<(), Error> {
+        table_sink::set_encoding(self, encoding)
+    }
+
+    /// Sets the page size, a power of two between 512 and 65536. The default is 4096.
+    /// It has to be set before tables are declared.
+    pub fn page_size(&mut self, page_size: u32) -> Result<(), Error> {
+        table_sink::set_page_size(self, page_size)
+    }
+
+    /// Completes the current table and declares a new one.
+    /// All records added after this call end up in this table.
+    pub fn schema(&mut self, table_name: &str, sql: &str) -> Result<(), Error> {
+        table_sink::schema(self, table_name, sql)
+    }
+
+    /// Completes the current table, declares a new one and fills it with the rows,
+    /// which have to serialize as structs or maps. The columns are named after the fields
+    /// of the first row, and get the type of the first value that isn't NULL. Rowids start at 1.
+    #[cfg(feature = "serde")]
+    pub fn add_rows<T: serde::Serialize>(
+        &mut self,
+        table_name: &str,
+        rows: impl IntoIterator<Item = T>,
+    ) -> Result<(), Error> {
+        table_sink::add_rows(self, table_name, rows)
+    }
+
+    /// Completes the current table, declares a table for the CSV and adds its rows.
+    /// The column types are inferred from the cells: INTEGER, REAL or TEXT, and empty cells are NULL.
+    #[cfg(feature = "csv")]
+    pub fn add_csv(
+        &mut self,
+        table_name: &str,
+        reader: impl std::io::Read,
+        options: &CsvOptions,
+    ) -> Result<(), Error> {
+        csv_import::add_csv(self, table_name, reader, options)
+    }
+
+    /// Completes the current table, declares a table for the JSON and adds its rows.
+    /// The JSON is an array of objects, or a stream of objects such as NDJSON. Every key becomes a column,
+    /// and keys that are missing from an object are NULL. Nested objects and arrays are stored as JSON text.
+    #[cfg(feature = "json")]
+    pub fn add_json(&mut self, table_name: &str, reader: impl std::io::Read) -> Result<(), Error> {
+        json_import::add_json(self, table_name, reader)
+    }
+
+    /// Completes the current table and declares the table for the Arrow schema.
+    /// Integers and booleans are INTEGER, floats and decimals REAL, strings, dates and timestamps
+    /// TEXT and binaries BLOB. Fields that aren't nullable are `NOT NULL`.
+    #[cfg(feature = "arrow")]
+    pub fn schema_from_arrow(
+        &mut self,
+        table_name: &str,
+        schema: &arrow_schema::Schema,
+    ) -> Result<(), Error> {
+        table_sink::schema_from_arrow(self, table_name, schema)
+    }
+
+    /// Adds the rows of the batch to the table that was declared last, with rowids after the previous one.
+    /// Nulls are stored as NULL.
+    #[cfg(feature = "arrow")]
+    pub fn add_record_batch(&mut self, batch: &arrow_array::RecordBatch) -> Result<(), Error> {
+        table_sink::add_record_batch(self, batch)
+    }
+
+    /// Completes the current table and declares the table for rows of type `T`
+    pub fn schema_for<T: SqliteRow>(&mut self) -> Result<(), Error> {
+        self.schema(T::table_name(), &T::create_table())
+    }
+
+    /// Adds the row to the table that was declared last, with the rowid after the previous one
+    pub fn add_row<T: SqliteRow>(&mut self, row: T) -> Result<(), Error> {
+        table_sink::add_row(self, row)
+    }
+
+    /// Declares an index as a `CREATE INDEX` statement, on the table that was declared last.
+    /// It has to be declared before any records are added to the table.
+    pub fn index(&mut self, sql: &str) -> Result<(), Error> {
+        let definition = sql::parse_create_index(sql).map_err(Error::InvalidSchema)?;
+        if self.declares(&definition.name) {
+            return Err(already_declared(&definition.name));
+        }
+        let table = self
+            .current_table
+            .as_mut()
+            .filter(|table| table.is_named(&definition.table_name))
+            .ok_or_else(|| {
+                Error::InvalidSchema(format!(
+                    "index {} has to be declared right after table {}",
+                    definition.name, definition.table_name
+                ))
+            })?;
+        table.add_index(definition, Some(sql))
+    }
+
+    /// Numbers the records of every table 1, 2, 3 and so on, in the order they are added,
+    /// instead of using the rowids of the records. It can be switched on and off at any time.
+    pub fn auto_rowid(&mut self, auto_rowid: bool) {
+        self.settings.auto_rowid = auto_rowid;
+    }
+
+    /// Adds the record to the table that was declared last.
+    /// Rowids have to be strictly increasing within a table, unless they are assigned automatically.
+    pub fn add_record(&mut self, record: Record) -> Result<(), Error> {
+        table_sink::add_record(self, record)
+    }
+
+    /// Completes the last table and writes the schema and the header page. Returns the sink.
+    pub fn finish(mut self) -> Result<W, Error> {
+        if !self.has_tables() {
+            self.reserve_header_page()?;
+        }
+        self.finish_table()?;
+
+        // the rest of the schema goes last
+        let (first_page, schema_pages) = create_schema_pages(
+            mem::take(&mut self.schema),
+            self.page_counter,
+            self.settings.encoding,
+            self.settings.page_size,
+        )?;
+        for page in schema_pages {
+            self.writer.write_all(&page)?;
+        }
+        let end = self.writer.stream_position()?;
+        self.writer.seek(SeekFrom::Start(self.header_position))?;
+        self.writer.write_all(&first_page)?;
+        self.writer.seek(SeekFrom::Start(end))?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    /// The header page is written last, where the sink was when the first table was declared
+    fn reserve_header_page(&mut self) -> Result<(), Error> {
+        self.header_position = self.writer.stream_position()?;
+        self.writer.write_all(&vec![0; self.settings.page_size])?;
+        Ok(())
+    }
+
+    /// overflow pages directly follow their leaf page
+    fn write_leaf_page(&mut self, page: Page) -> Result<(), Error> {
+        let n_overflow_pages: usize = page.overflow.iter().map(|chain| chain.pages.len()).sum();
+        check_page_count(self.page_counter as u64 + n_overflow_pages as u64)?;
+        let page_number = self.page_counter;
+        self.page_counter += 1;
+        let mut data = Cow::Borrowed(&page.data[..]);
+        let mut overflow_pages = Vec::new();
+        set_overflow_refs(
+            &page,
+            &mut data,
+            &mut self.page_counter,
+            &mut overflow_pages,
+        );
+
+        self.writer.write_all(&data)?;
+        write_overflow_pages(&overflow_pages, &mut self.writer)?;
+        self.leaf_refs.push((page.key, page_number));
+        Ok(())
+    }
+
+    /// Writes the last leaf page, the interior pages and the indexes of the current table
+    fn finish_table(&mut self) -> Result<(), Error> {
+        let Some(table) = self.current_table.take() else {
+            return Ok(());
+        };
+        let (mut schema, leaf_pages, indexes) = table.finish();
+        for leaf_page in leaf_pages {
+            self.write_leaf_page(leaf_page)?;
+        }
+
+        let mut child_refs = mem::take(&mut self.leaf_refs);
+        while child_refs.len() > 1 {
+            child_refs = write_interior_pages(
+                &child_refs,
+                &mut self.writer,
+                &mut self.page_counter,
+                self.settings.page_size,
+            )?;
+        }
+        schema.root_page = child_refs[0].1;
+        self.schema.push(schema);
+
+        for index in indexes {
+            let (mut schema, root_page, n_btree_pages) =
+                index.into_btree(self.settings.page_size, self.settings.encoding)?;
+            check_page_count(self.page_counter as u64 + n_btree_pages as u64)?;
+            schema.root_page = self.page_counter;
+            self.page_counter += n_btree_pages as u32;
+
+            // the overflow pages of an index follow its b-tree
+            set_childrefs_write(
+                &root_page,
+                &mut self.writer,
+                schema.root_page,
+                &mut self.page_counter,
+            )?;
+            self.schema.push(schema);
+        }
+        Ok(())
+    }
+}
+
+/// Full leaf pages are written right away. A new table completes the current one.
+impl<W: Write + Seek> TableSink for StreamingDatabaseBuilder<W> {
+    fn settings(&mut self) -> &mut Settings {
+        &mut self.settings
+    }
+
+    fn has_tables(&self) -> bool {
+        !self.schema.is_empty() || self.current_table.is_some()
+    }
+
+    fn declares(&self, name: &str) -> bool {
+        self.schema
+            .iter()
+            .any(|schema_record| schema_record.name.eq_ignore_ascii_case(name))
+            || self
+                .current_table
+                .as_ref()
+                .is_some_and(|table| table.declares(name))
+    }
+
+    fn push_table(&mut self, table: TableBuilder) -> Result<(), Error> {
+        if self.has_tables() {
+            self.finish_table()?;
+        } else {
+            self.reserve_header_page()?;
+        }
+        self.current_table = Some(table);
+        Ok(())
+    }
+
+    fn current_table(&mut self) -> Option<&mut TableBuilder> {
+        self.current_table.as_mut()
+    }
+
+    fn full_page(&mut self, page: Page) -> Result<(), Error> {
+        self.write_leaf_page(page)
+    }
+}
@@ -0,0 +1,152 @@
+#[cfg(feature = "arrow")]
+use crate::arrow_import;
+#[cfg(feature = "serde")]
+use crate::builder::no_rows;
+use crate::builder::{already_declared, no_table_declared, TableBuilder};
+use crate::database::{check_page_size, DEFAULT_PAGE_SIZE};
+use crate::error::Error;
+use crate::page::Page;
+use crate::record::Record;
+use crate::row::SqliteRow;
+#[cfg(feature = "serde")]
+use crate::serialize::Columns;
+use crate::values::TextEncoding;
+
+/// What [`DatabaseBuilder`](crate::DatabaseBuilder) and
+/// [`StreamingDatabaseBuilder`](crate::StreamingDatabaseBuilder) have in common,
+/// so that every way of filling a table is written once. They differ in what happens to full leaf pages.
+pub(crate) trait TableSink {
+    fn settings(&mut self) -> &mut Settings;
+
+    fn has_tables(&self) -> bool;
+
+    /// the names of tables and indexes are shared
+    fn declares(&self, name: &str) -> bool;
+
+    /// Records are added to this table from now on
+    fn push_table(&mut self, table: TableBuilder) -> Result<(), Error>;
+
+    /// The table that was declared last
+    fn current_table(&mut self) -> Option<&mut TableBuilder>;
+
+    /// Takes a leaf page of the current table when it is full
+    fn full_page(&mut self, page: Page) -> Result<(), Error>;
+}
+
+/// The settings of a builder, which apply to all tables
+pub(crate) struct Settings {
+    pub(crate) encoding: TextEncoding,
+    pub(crate) page_size: usize,
+    pub(crate) auto_rowid: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            encoding: TextEncoding::Utf8,
+            page_size: DEFAULT_PAGE_SIZE,
+            auto_rowid: false,
+        }
+    }
+}
+
+pub(crate) fn set_encoding(sink: &mut impl TableSink, encoding: TextEncoding) -> Result<(), Error> {
+    if sink.has_tables() {
+        return Err(Error::InvalidSchema(
+            "the encoding has to be set before tables are declared".to_owned(),
+        ));
+    }
+    sink.settings().encoding = encoding;
+    Ok(())
+}
+
+pub(crate) fn set_page_size(sink: &mut impl TableSink, page_size: u32) -> Result<(), Error> {
+    if sink.has_tables() {
+        return Err(Error::InvalidSchema(
+            "the page size has to be set before tables are declared".to_owned(),
+        ));
+    }
+    sink.settings().page_size = check_page_size(page_size)?;
+    Ok(())
+}
+
+pub(crate) fn schema(sink: &mut impl TableSink, table_name: &str, sql: &str) -> Result<(), Error> {
+    if sink.declares(table_name) {
+        return Err(already_declared(table_name));
+    }
+    let table = TableBuilder::new(table_name, sql, sink.settings().page_size)?;
+    sink.push_table(table)
+}
+
+/// The columns are named after the fields of the first row, and get the type of the first value
+/// that isn't NULL. Rowids start at 1.
+#[cfg(feature = "serde")]
+pub(crate) fn add_rows<T: serde::Serialize>(
+    sink: &mut impl TableSink,
+    table_name: &str,
+    rows: impl IntoIterator<Item = T>,
+) -> Result<(), Error> {
+    let mut columns = Columns::new();
+    let mut rows = rows.into_iter();
+    let first_row = rows.next().ok_or_else(|| no_rows(table_name))?;
+    let values = columns.values(&first_row)?;
+    schema(sink, table_name, &columns.create_table(table_name))?;
+    add_record(sink, Record::with_values(1, values))?;
+    for (rowid, row) in (2..).zip(rows) {
+        add_record(sink, Record::with_values(rowid, columns.values(&row)?))?;
+    }
+
+    // the types are only known after all rows
+    if let Some(table) = sink.current_table() {
+        table.schema.sql = Some(columns.create_table(table_name));
+    }
+    Ok(())
+}
+
+#[cfg(feature = "arrow")]
+pub(crate) fn schema_from_arrow(
+    sink: &mut impl TableSink,
+    table_name: &str,
+    schema: &arrow_schema::Schema,
+) -> Result<(), Error> {
+    self::schema(
+        sink,
+        table_name,
+        &arrow_import::create_table(table_name, schema)?,
+    )
+}
+
+#[cfg(feature = "arrow")]
+pub(crate) fn add_record_batch(
+    sink: &mut impl TableSink,
+    batch: &arrow_array::RecordBatch,
+) -> Result<(), Error> {
+    let table = sink.current_table().ok_or_else(no_table_declared)?;
+    for record in arrow_import::records(batch, table.next_rowid())? {
+        add_record(sink, record)?;
+    }
+    Ok(())
+}
+
+pub(crate) fn add_row<T: SqliteRow>(sink: &mut impl TableSink, row: T) -> Result<(), Error> {
+    let table = sink.current_table().ok_or_else(no_table_declared)?;
+    let record = row.into_record(table.next_rowid())?;
+    add_record(sink, record)
+}
+
+pub(crate) fn add_record(sink: &mut impl TableSink, mut record: Record) -> Result<(), Error> {
+    let Settings {
+        encoding,
+        auto_rowid,
+        ..
+    } = *sink.settings();
+    record.encode_text(encoding);
+    let table = sink.current_table().ok_or_else(no_table_declared)?;
+    if auto_rowid {
+        record.rowid = table.next_rowid();
+    }
+    if let Some(full_page) = table.add_record(record)? {
+        sink.full_page(full_page)?;
+    }
+    Ok(())
+}
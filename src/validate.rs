@@ -0,0 +1,1020 @@
+use crate::page::{CellContent, PageHeader};
+use crate::reader::{DatabaseReader, SchemaEntry};
+use crate::record;
+use crate::sql::{self, IndexDefinition};
+use crate::values::{self, Collation, TextEncoding, Value};
+use byteorder::{BigEndian, ByteOrder};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+
+/// `SQLite` doesn't go deeper than this either
+const MAX_DEPTH: usize = 20;
+/// like the default of `PRAGMA integrity_check`
+const MAX_DIAGNOSTICS: usize = 100;
+
+/// A problem in a database file, found by [`validate`]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    pub kind: DiagnosticKind,
+    /// the page where the problem is, 1 for the file header
+    pub page: u32,
+    pub message: String,
+}
+
+/// What part of the file a [`Diagnostic`] is about
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DiagnosticKind {
+    /// The file header, eg. a page count that doesn't match the size of the file
+    Header,
+    /// The header of a b-tree page, eg. an unknown page type
+    PageHeader,
+    /// Cell pointers outside of the cell content area, or cells that overlap
+    CellPointers,
+    /// Free blocks that are out of order, and free space that doesn't add up
+    Freeblocks,
+    /// Keys out of order, within a page or compared to the keys in the parent pages
+    KeyOrder,
+    /// Child pages that are out of range or used twice, and leaves at different depths
+    ChildPages,
+    /// Overflow chains that are too short or too long
+    Overflow,
+    /// The list of free pages
+    Freelist,
+    /// Pages that are not used by any b-tree, overflow chain or the freelist
+    UnusedPage,
+    /// Records that can't be decoded
+    Record,
+    /// Entries in `sqlite_master` that don't match the rest of the file
+    Schema,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "page {}: {}", self.page, self.message)
+    }
+}
+
+/// Checks the structure of a database file, the way `PRAGMA integrity_check` does:
+/// the headers, the cells and free blocks of every page, the order of keys within and across pages,
+/// child page numbers, overflow chains, the page count, `sqlite_master`, and whether every row
+/// is in the indexes of its table, for which the rows of indexed tables are held in memory.
+/// Returns nothing for a valid file, and at most 100 diagnostics.
+pub fn validate(bytes: &[u8]) -> Vec<Diagnostic> {
+    let reader = match DatabaseReader::new(bytes) {
+        Ok(reader) => reader,
+        Err(error) => {
+            return vec![diagnostic(DiagnosticKind::Header, 1, error.to_string())];
+        }
+    };
+    let mut validator = Validator::new(reader, bytes);
+    validator.check_header(bytes);
+    validator.check_schema();
+    validator.check_freelist(bytes);
+    validator.check_unused_pages(bytes);
+    validator.diagnostics
+}
+
+fn diagnostic(kind: DiagnosticKind, page: u32, message: String) -> Diagnostic {
+    Diagnostic {
+        kind,
+        page,
+        message,
+    }
+}
+
+/// The key of a cell: the rowid in a table b-tree, or all values in an index b-tree
+enum Key {
+    Rowid(i64),
+    Entry(Vec<Value>),
+}
+
+impl fmt::Debug for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Key::Rowid(rowid) => write!(f, "{rowid}"),
+            Key::Entry(values) => write!(f, "{values:?}"),
+        }
+    }
+}
+
+/// What is known about the b-tree that is being checked
+struct Tree {
+    is_table: bool,
+    /// the order of an index b-tree, if the definition could be parsed and its collations are supported
+    order: Option<KeyOrder>,
+    leaf_depth: Option<usize>,
+    n_entries: usize,
+    /// the rows of a table, or the entries of an index, when they are compared
+    entries: Option<Vec<(i64, Vec<Value>)>>,
+}
+
+impl Tree {
+    fn new(is_table: bool, order: Option<KeyOrder>) -> Self {
+        Self {
+            is_table,
+            order,
+            leaf_depth: None,
+            n_entries: 0,
+            entries: None,
+        }
+    }
+
+    /// None when the order of the index is unknown
+    fn compare(&self, left: &Key, right: &Key) -> Option<Ordering> {
+        match (left, right) {
+            (Key::Rowid(left), Key::Rowid(right)) => Some(left.cmp(right)),
+            (Key::Entry(left), Key::Entry(right)) => {
+                Some(self.order.as_ref()?.compare(left, right))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// How the entries of an index b-tree are ordered: by their first values,
+/// each with a descending flag and a collation
+struct KeyOrder {
+    columns: Vec<(bool, Collation)>,
+    encoding: TextEncoding,
+}
+
+impl KeyOrder {
+    /// The entries of an index on a table with a rowid end with the rowid, which is ascending.
+    /// Those of an index on a WITHOUT ROWID table end with the PRIMARY KEY columns that aren't indexed.
+    /// A WITHOUT ROWID table itself is ordered by its PRIMARY KEY. None when a collation isn't supported.
+    fn new(definition: &IndexDefinition, table_sql: &str, encoding: TextEncoding) -> Option<Self> {
+        let collations = sql::index_collations(definition, table_sql).ok()?;
+        let mut columns: Vec<(bool, Collation)> = definition
+            .columns
+            .iter()
+            .map(|column| column.descending)
+            .zip(collations)
+            .collect();
+        if sql::is_without_rowid(table_sql) {
+            let primary_key = sql::primary_key_index(&definition.table_name, table_sql).ok()??;
+            let primary_key_collations = sql::index_collations(&primary_key, table_sql).ok()?;
+            for (column, collation) in primary_key.columns.iter().zip(primary_key_collations) {
+                let is_indexed = definition.columns.iter().zip(&columns).any(
+                    |(indexed_column, (_, indexed_collation))| {
+                        indexed_column.name.eq_ignore_ascii_case(&column.name)
+                            && *indexed_collation == collation
+                    },
+                );
+                if !is_indexed {
+                    columns.push((column.descending, collation));
+                }
+            }
+        } else {
+            columns.push((false, Collation::Binary));
+        }
+        Some(Self { columns, encoding })
+    }
+
+    /// Compares the values that make up the key. The other values of a WITHOUT ROWID table don't count.
+    fn compare(&self, left: &[Value], right: &[Value]) -> Ordering {
+        let n_columns = self.columns.len();
+        left.iter()
+            .zip(right)
+            .zip(&self.columns)
+            .map(|((l, r), (descending, collation))| {
+                let ordering = collation.compare(l, r, self.encoding);
+                if *descending {
+                    ordering.reverse()
+                } else {
+                    ordering
+                }
+            })
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or_else(|| left.len().min(n_columns).cmp(&right.len().min(n_columns)))
+    }
+}
+
+/// Compares index entries value by value, as stored, to find the ones that are the same
+fn compare_entries(left: &[Value], right: &[Value]) -> Ordering {
+    left.iter()
+        .zip(right)
+        .map(|(l, r)| values::compare(l, r))
+        .find(|ordering| ordering.is_ne())
+        .unwrap_or_else(|| left.len().cmp(&right.len()))
+}
+
+struct Validator<'a> {
+    reader: DatabaseReader<'a>,
+    usable_size: usize,
+    /// whether each page is in use, indexed by page number
+    used: Vec<bool>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'a> Validator<'a> {
+    fn new(reader: DatabaseReader<'a>, bytes: &[u8]) -> Self {
+        // never more pages than the file holds, whatever the header says
+        let n_pages = (reader.page_count() as usize).min(bytes.len() / reader.page_size());
+        let usable_size = reader.usable_size();
+        let mut used = vec![false; n_pages + 1];
+        used[0] = true;
+        Self {
+            reader,
+            usable_size,
+            used,
+            diagnostics: Vec::new(),
+        }
+    }
+
+    fn report(&mut self, kind: DiagnosticKind, page: u32, message: String) {
+        if self.diagnostics.len() < MAX_DIAGNOSTICS {
+            self.diagnostics.push(diagnostic(kind, page, message));
+        }
+    }
+
+    /// Marks a page as used. Reports pages that are out of range or used already.
+    fn mark_used(&mut self, kind: DiagnosticKind, page_number: u32, referrer: u32) -> bool {
+        match self.used.get_mut(page_number as usize) {
+            Some(used) if page_number > 0 && !*used => {
+                *used = true;
+                true
+            }
+            Some(_) if page_number > 0 => {
+                self.report(
+                    kind,
+                    referrer,
+                    format!("page {page_number} is used more than once"),
+                );
+                false
+            }
+            _ => {
+                self.report(
+                    kind,
+                    referrer,
+                    format!("page number {page_number} is out of range"),
+                );
+                false
+            }
+        }
+    }
+
+    fn check_header(&mut self, bytes: &[u8]) {
+        let page_size = self.reader.page_size();
+        if !bytes.len().is_multiple_of(page_size) {
+            self.report(
+                DiagnosticKind::Header,
+                1,
+                format!(
+                    "the file size {} is not a multiple of the page size {page_size}",
+                    bytes.len()
+                ),
+            );
+        }
+        let header_page_count = BigEndian::read_u32(&bytes[28..]) as usize;
+        let file_page_count = bytes.len() / page_size;
+        if header_page_count != file_page_count {
+            self.report(
+                DiagnosticKind::Header,
+                1,
+                format!("the header says there are {header_page_count} pages, the file has {file_page_count}"),
+            );
+        }
+        if bytes[21..24] != [64, 32, 32] {
+            self.report(
+                DiagnosticKind::Header,
+                1,
+                format!(
+                    "the payload fractions are {:?} instead of [64, 32, 32]",
+                    &bytes[21..24]
+                ),
+            );
+        }
+        let is_auto_vacuum = BigEndian::read_u32(&bytes[52..]) != 0;
+        if !is_auto_vacuum && BigEndian::read_u32(&bytes[64..]) != 0 {
+            self.report(
+                DiagnosticKind::Header,
+                1,
+                "incremental vacuum is set without auto-vacuum".to_owned(),
+            );
+        }
+    }
+
+    /// Checks `sqlite_master`, then the b-trees of all tables and then those of the indexes,
+    /// which have to contain the columns of every row of their table
+    fn check_schema(&mut self) {
+        self.mark_used(DiagnosticKind::Schema, 1, 1);
+        let mut schema_tree = Tree::new(true, None);
+        schema_tree.entries = Some(Vec::new());
+        self.check_page(&mut schema_tree, 1, 0, None, None);
+        // SQLite would fail to parse the statement, or not find the object by its name
+        for (rowid, values) in schema_tree.entries.unwrap_or_default() {
+            if values
+                .iter()
+                .any(|value| value.is_text() && value.as_text().is_none())
+            {
+                self.report(
+                    DiagnosticKind::Schema,
+                    1,
+                    format!("sqlite_master row {rowid} has text that is not valid UTF-8"),
+                );
+            }
+        }
+        let schema = match self.reader.schema() {
+            Ok(schema) => schema,
+            Err(error) => {
+                self.report(DiagnosticKind::Schema, 1, error.to_string());
+                return;
+            }
+        };
+
+        for (position, entry) in schema.iter().enumerate() {
+            let is_duplicate = schema[..position]
+                .iter()
+                .any(|other| other.name.eq_ignore_ascii_case(&entry.name));
+            if is_duplicate {
+                self.report(
+                    DiagnosticKind::Schema,
+                    1,
+                    format!("{} is declared twice", entry.name),
+                );
+            }
+        }
+
+        let objects: Vec<(&SchemaEntry, bool)> = schema
+            .iter()
+            .filter_map(|entry| Some((entry, self.is_table(entry)?)))
+            .collect();
+        let indexes: Vec<(&SchemaEntry, Option<IndexDefinition>)> = objects
+            .iter()
+            .filter(|(_, is_table)| !is_table)
+            .map(|(entry, _)| (*entry, index_definition(entry, &schema)))
+            .collect();
+
+        let encoding = self.reader.encoding();
+        let mut tables = HashMap::new();
+        for (entry, _) in objects.iter().filter(|(_, is_table)| *is_table) {
+            let sql = entry.sql.as_deref().unwrap_or_default();
+            // a WITHOUT ROWID table is an index b-tree on its PRIMARY KEY
+            let mut tree = if sql::is_without_rowid(sql) {
+                let order = sql::primary_key_index(&entry.name, sql)
+                    .ok()
+                    .flatten()
+                    .and_then(|primary_key| KeyOrder::new(&primary_key, sql, encoding));
+                Tree::new(false, order)
+            } else {
+                Tree::new(true, None)
+            };
+            let is_indexed = indexes.iter().any(|(index, definition)| {
+                definition.is_some() && index.table_name.eq_ignore_ascii_case(&entry.name)
+            });
+            if is_indexed && tree.is_table {
+                tree.entries = Some(Vec::new());
+            }
+            if self.check_tree(entry, &mut tree) {
+                tables.insert(entry.name.to_ascii_lowercase(), (*entry, tree));
+            }
+        }
+
+        for (entry, definition) in indexes {
+            if let Some(definition) = definition.as_ref().filter(|_| entry.sql.is_some()) {
+                let is_named = definition.name.eq_ignore_ascii_case(&entry.name)
+                    && definition
+                        .table_name
+                        .eq_ignore_ascii_case(&entry.table_name);
+                if !is_named {
+                    self.report(
+                        DiagnosticKind::Schema,
+                        1,
+                        format!(
+                            "index {} on {} is created as {} on {}",
+                            entry.name, entry.table_name, definition.name, definition.table_name
+                        ),
+                    );
+                }
+            }
+            let table_sql = schema
+                .iter()
+                .find(|table| {
+                    table.object_type == "table"
+                        && table.name.eq_ignore_ascii_case(&entry.table_name)
+                })
+                .and_then(|table| table.sql.as_deref());
+            let order = definition
+                .as_ref()
+                .zip(table_sql)
+                .and_then(|(definition, table_sql)| KeyOrder::new(definition, table_sql, encoding));
+            let mut tree = Tree::new(false, order);
+            tree.entries = definition.as_ref().map(|_| Vec::new());
+            if !self.check_tree(entry, &mut tree) {
+                continue;
+            }
+            let Some((table, table_tree)) = tables.get(&entry.table_name.to_ascii_lowercase())
+            else {
+                self.report(
+                    DiagnosticKind::Schema,
+                    1,
+                    format!(
+                        "index {} is on unknown table {}",
+                        entry.name, entry.table_name
+                    ),
+                );
+                continue;
+            };
+            let expected = definition.and_then(|definition| {
+                expected_entries(
+                    &definition,
+                    table.sql.as_deref()?,
+                    table_tree.entries.as_ref()?,
+                )
+            });
+            match (expected, tree.entries.take()) {
+                (Some(expected), Some(entries)) => {
+                    self.check_index_entries(entry, expected, entries);
+                }
+                _ if tree.n_entries != table_tree.n_entries => self.report(
+                    DiagnosticKind::Schema,
+                    entry.root_page,
+                    format!(
+                        "index {} has {} entries, but table {} has {} rows",
+                        entry.name, tree.n_entries, entry.table_name, table_tree.n_entries
+                    ),
+                ),
+                _ => {}
+            }
+        }
+    }
+
+    /// Whether the entry is a table or an index, or None for views and triggers.
+    /// Like `SQLite`, the statement decides when the type is unknown.
+    fn is_table(&mut self, entry: &SchemaEntry) -> Option<bool> {
+        match entry.object_type.as_str() {
+            "table" => Some(true),
+            "index" => Some(false),
+            "view" | "trigger" if entry.root_page == 0 => None,
+            object_type => {
+                self.report(
+                    DiagnosticKind::Schema,
+                    1,
+                    format!("{} has an unknown type {object_type}", entry.name),
+                );
+                let sql = entry.sql.as_deref().unwrap_or_default();
+                let is_table = !sql::column_names(sql).is_empty()
+                    && sql
+                        .trim_start()
+                        .get(..12)
+                        .is_some_and(|start| start.eq_ignore_ascii_case("create table"));
+                (entry.root_page != 0).then_some(is_table)
+            }
+        }
+    }
+
+    /// Checks the b-tree of a table or an index. Returns false when it can't be checked.
+    fn check_tree(&mut self, entry: &SchemaEntry, tree: &mut Tree) -> bool {
+        if tree.is_table
+            && sql::column_names(entry.sql.as_deref().unwrap_or_default()).is_empty()
+            && !entry.name.starts_with("sqlite_")
+        {
+            self.report(
+                DiagnosticKind::Schema,
+                1,
+                format!("table {} has no columns", entry.name),
+            );
+        }
+        if !self.mark_used(DiagnosticKind::Schema, entry.root_page, 1) {
+            return false;
+        }
+        self.check_page(tree, entry.root_page, 0, None, None);
+        true
+    }
+
+    /// Reports the rows that are missing from the index and the entries that are not in the table
+    fn check_index_entries(
+        &mut self,
+        entry: &SchemaEntry,
+        mut expected: Vec<(i64, Vec<Value>)>,
+        mut entries: Vec<(i64, Vec<Value>)>,
+    ) {
+        let compare = |left: &(i64, Vec<Value>), right: &(i64, Vec<Value>)| {
+            compare_entries(&left.1, &right.1)
+        };
+        expected.sort_by(compare);
+        entries.sort_by(compare);
+
+        let (mut expected, mut entries) = (expected.iter().peekable(), entries.iter().peekable());
+        loop {
+            let ordering = match (expected.peek(), entries.peek()) {
+                (Some(left), Some(right)) => compare(left, right),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => break,
+            };
+            match ordering {
+                Ordering::Equal => {
+                    expected.next();
+                    entries.next();
+                }
+                Ordering::Less => {
+                    let (rowid, _) = expected.next().unwrap(); // there is one to compare
+                    self.report(
+                        DiagnosticKind::Schema,
+                        entry.root_page,
+                        format!("row {rowid} is missing from index {}", entry.name),
+                    );
+                }
+                Ordering::Greater => {
+                    let (rowid, values) = entries.next().unwrap(); // there is one to compare
+                    self.report(
+                        DiagnosticKind::Schema,
+                        entry.root_page,
+                        format!(
+                            "index {} has an entry {values:?} for row {rowid}, which is not in table {}",
+                            entry.name, entry.table_name
+                        ),
+                    );
+                }
+            }
+        }
+    }
+
+    /// Checks a page and its children. The keys have to be greater than `lower`,
+    /// and not greater than `upper`, or less than `upper` in an index.
+    fn check_page(
+        &mut self,
+        tree: &mut Tree,
+        page_number: u32,
+        depth: usize,
+        lower: Option<&Key>,
+        upper: Option<&Key>,
+    ) {
+        if depth > MAX_DEPTH {
+            self.report(
+                DiagnosticKind::ChildPages,
+                page_number,
+                format!("the b-tree is more than {MAX_DEPTH} levels deep"),
+            );
+            return;
+        }
+        let (page, header_offset, header) = match self.reader.btree_page(page_number) {
+            Ok(page) => page,
+            Err(error) => {
+                self.report(DiagnosticKind::PageHeader, page_number, error.to_string());
+                return;
+            }
+        };
+        if header.is_table() != tree.is_table {
+            let expected = if tree.is_table { "table" } else { "index" };
+            self.report(
+                DiagnosticKind::PageHeader,
+                page_number,
+                format!("expected a {expected} b-tree page"),
+            );
+            return;
+        }
+        if header.is_leaf() {
+            match tree.leaf_depth {
+                Some(leaf_depth) if leaf_depth != depth => self.report(
+                    DiagnosticKind::ChildPages,
+                    page_number,
+                    format!("the leaf is at depth {depth}, other leaves are at depth {leaf_depth}"),
+                ),
+                Some(_) => {}
+                None => tree.leaf_depth = Some(depth),
+            }
+        }
+
+        let cells = self.check_layout(page_number, page, header_offset, &header);
+        let mut previous: Option<Key> = None;
+        for (index, cell) in cells {
+            let has_entry = !tree.is_table || header.is_leaf();
+            let is_complete = self.check_overflow(page_number, index, &cell);
+            let key = match cell.rowid {
+                Some(rowid) => Key::Rowid(rowid),
+                None if is_complete => match self.values(page_number, index, &cell) {
+                    Some(values) => Key::Entry(values),
+                    None => continue,
+                },
+                None => continue,
+            };
+            if has_entry {
+                tree.n_entries += 1;
+                let values = match &key {
+                    Key::Rowid(_) if is_complete => self.values(page_number, index, &cell),
+                    Key::Entry(values) if tree.entries.is_some() => Some(values.clone()),
+                    _ => None,
+                };
+                if let (Some(entries), Some(values)) = (&mut tree.entries, values) {
+                    let rowid = match &key {
+                        Key::Rowid(rowid) => *rowid,
+                        Key::Entry(_) => values
+                            .last()
+                            .and_then(Value::as_integer)
+                            .unwrap_or_default(),
+                    };
+                    entries.push((rowid, values));
+                }
+            }
+
+            if let Some(lower) = previous.as_ref().or(lower) {
+                if tree.compare(&key, lower).is_some_and(Ordering::is_le) {
+                    self.report(
+                        DiagnosticKind::KeyOrder,
+                        page_number,
+                        format!("the key {key:?} of cell {index} is not greater than {lower:?}"),
+                    );
+                }
+            }
+            if let Some(upper) = upper {
+                let ordering = tree.compare(&key, upper);
+                let too_large = if tree.is_table {
+                    ordering == Some(Ordering::Greater)
+                } else {
+                    ordering.is_some_and(Ordering::is_ge)
+                };
+                if too_large {
+                    self.report(
+                        DiagnosticKind::KeyOrder,
+                        page_number,
+                        format!("the key {key:?} of cell {index} is not less than {upper:?} in the parent page"),
+                    );
+                }
+            }
+
+            if let Some(left_child) = cell.left_child {
+                if self.mark_used(DiagnosticKind::ChildPages, left_child, page_number) {
+                    let lower = previous.as_ref().or(lower);
+                    self.check_page(tree, left_child, depth + 1, lower, Some(&key));
+                }
+            }
+            previous = Some(key);
+        }
+        if let Some(rightmost_pointer) = header.rightmost_pointer {
+            if self.mark_used(DiagnosticKind::ChildPages, rightmost_pointer, page_number) {
+                let lower = previous.as_ref().or(lower);
+                self.check_page(tree, rightmost_pointer, depth + 1, lower, upper);
+            }
+        }
+    }
+
+    /// Checks that the cells and free blocks are within the cell content area, don't overlap
+    /// and account for all of its bytes. Returns the cells that can be read, with their index.
+    fn check_layout(
+        &mut self,
+        page_number: u32,
+        page: &'a [u8],
+        header_offset: usize,
+        header: &PageHeader,
+    ) -> Vec<(usize, CellContent<'a>)> {
+        let usable_size = self.usable_size;
+        if header.content_start > usable_size {
+            self.report(
+                DiagnosticKind::PageHeader,
+                page_number,
+                format!(
+                    "the cell content area starts at {}, after the end of the page",
+                    header.content_start
+                ),
+            );
+            return Vec::new();
+        }
+        let pointers_end = header_offset + header.len() + 2 * usize::from(header.cell_count);
+        if pointers_end > header.content_start {
+            self.report(
+                DiagnosticKind::CellPointers,
+                page_number,
+                format!(
+                    "the {} cell pointers overlap the cell content area at {}",
+                    header.cell_count, header.content_start
+                ),
+            );
+            return Vec::new();
+        }
+
+        // the start and end of the cells and free blocks
+        let mut areas = Vec::new();
+        let mut cells = Vec::new();
+        let pointers = header
+            .cell_pointers(page, header_offset)
+            .unwrap_or_default();
+        for (index, offset) in pointers.into_iter().enumerate() {
+            let cell = if offset < header.content_start {
+                None
+            } else {
+                crate::page::read_cell(&page[..usable_size], offset, header, usable_size)
+            };
+            let Some(cell) = cell else {
+                self.report(
+                    DiagnosticKind::CellPointers,
+                    page_number,
+                    format!("cell {index} at {offset} is outside of the cell content area"),
+                );
+                continue;
+            };
+            // SQLite never uses less than 4 bytes for a cell
+            let len = cell.len.max(4);
+            if offset + len > usable_size {
+                self.report(
+                    DiagnosticKind::CellPointers,
+                    page_number,
+                    format!("cell {index} at {offset} extends beyond the end of the page"),
+                );
+                continue;
+            }
+            areas.push((offset, offset + len, "cell"));
+            cells.push((index, cell));
+        }
+        self.check_freeblocks(page_number, page, header, &mut areas);
+
+        areas.sort_unstable();
+        for pair in areas.windows(2) {
+            let ((_, end, left), (start, _, right)) = (pair[0], pair[1]);
+            if start < end {
+                let kind = if left == "cell" && right == "cell" {
+                    DiagnosticKind::CellPointers
+                } else {
+                    DiagnosticKind::Freeblocks
+                };
+                self.report(
+                    kind,
+                    page_number,
+                    format!("a {left} and a {right} overlap at {start}"),
+                );
+            }
+        }
+
+        if header.fragmented_bytes > 60 {
+            self.report(
+                DiagnosticKind::Freeblocks,
+                page_number,
+                format!(
+                    "{} fragmented bytes, more than the maximum of 60",
+                    header.fragmented_bytes
+                ),
+            );
+        }
+        let accounted: usize = areas
+            .iter()
+            .map(|(start, end, _)| end - start)
+            .sum::<usize>()
+            + usize::from(header.fragmented_bytes);
+        let content_area = usable_size - header.content_start;
+        if accounted != content_area {
+            self.report(
+                DiagnosticKind::Freeblocks,
+                page_number,
+                format!("cells, free blocks and fragments take {accounted} bytes of the {content_area} byte cell content area"),
+            );
+        }
+        cells
+    }
+
+    /// Free blocks are linked in ascending order, each starting with the offset of the next
+    /// and its own size
+    fn check_freeblocks(
+        &mut self,
+        page_number: u32,
+        page: &[u8],
+        header: &PageHeader,
+        areas: &mut Vec<(usize, usize, &'static str)>,
+    ) {
+        let mut offset = usize::from(header.first_freeblock);
+        while offset != 0 {
+            if offset < header.content_start || offset + 4 > self.usable_size {
+                self.report(
+                    DiagnosticKind::Freeblocks,
+                    page_number,
+                    format!("free block at {offset} is outside of the cell content area"),
+                );
+                return;
+            }
+            let next = usize::from(BigEndian::read_u16(&page[offset..]));
+            let size = usize::from(BigEndian::read_u16(&page[offset + 2..]));
+            if size < 4 || offset + size > self.usable_size {
+                self.report(
+                    DiagnosticKind::Freeblocks,
+                    page_number,
+                    format!("free block at {offset} has an invalid size {size}"),
+                );
+                return;
+            }
+            areas.push((offset, offset + size, "free block"));
+            if next != 0 && next <= offset + size {
+                self.report(
+                    DiagnosticKind::Freeblocks,
+                    page_number,
+                    format!("the free block after {offset} is at {next}, which is not in ascending order"),
+                );
+                return;
+            }
+            offset = next;
+        }
+    }
+
+    /// Checks that the overflow chain of the cell is as long as its payload.
+    /// Returns whether the whole payload can be read.
+    fn check_overflow(&mut self, page_number: u32, index: usize, cell: &CellContent) -> bool {
+        let Some(mut overflow_page) = cell.first_overflow_page else {
+            return true;
+        };
+        let mut remaining = cell.payload_len - cell.local_payload.len();
+        let mut referrer = page_number;
+        while remaining > 0 {
+            if overflow_page == 0 {
+                self.report(
+                    DiagnosticKind::Overflow,
+                    page_number,
+                    format!("the overflow chain of cell {index} ends {remaining} bytes too soon"),
+                );
+                return false;
+            }
+            if !self.mark_used(DiagnosticKind::Overflow, overflow_page, referrer) {
+                return false;
+            }
+            // in range, because it could be marked
+            let Ok(page) = self.reader.page(overflow_page) else {
+                return false;
+            };
+            remaining -= remaining.min(self.usable_size - 4);
+            referrer = overflow_page;
+            overflow_page = BigEndian::read_u32(page);
+        }
+        if overflow_page != 0 {
+            self.report(
+                DiagnosticKind::Overflow,
+                referrer,
+                format!("the overflow chain of cell {index} on page {page_number} continues after the end of the payload"),
+            );
+        }
+        true
+    }
+
+    /// The values of the record in the cell, as they are stored
+    fn values(&mut self, page_number: u32, index: usize, cell: &CellContent) -> Option<Vec<Value>> {
+        let values = self
+            .reader
+            .payload(page_number, cell)
+            .ok()
+            .and_then(|payload| record::read_payload(&payload));
+        if values.is_none() {
+            self.report(
+                DiagnosticKind::Record,
+                page_number,
+                format!("the record in cell {index} can't be decoded"),
+            );
+        }
+        values
+    }
+
+    /// The pages on the freelist: trunk pages that list leaf pages
+    fn check_freelist(&mut self, bytes: &[u8]) {
+        let mut trunk_page = BigEndian::read_u32(&bytes[32..]);
+        let expected = BigEndian::read_u32(&bytes[36..]) as usize;
+        let mut n_pages = 0;
+        let mut referrer = 1;
+        while trunk_page != 0 {
+            if !self.mark_used(DiagnosticKind::Freelist, trunk_page, referrer) {
+                return;
+            }
+            let Ok(page) = self.reader.page(trunk_page) else {
+                return;
+            };
+            n_pages += 1;
+            let n_leaves = BigEndian::read_u32(&page[4..]) as usize;
+            if n_leaves > (self.usable_size - 8) / 4 {
+                self.report(
+                    DiagnosticKind::Freelist,
+                    trunk_page,
+                    format!("{n_leaves} leaves don't fit in a freelist trunk page"),
+                );
+                return;
+            }
+            for leaf in page[8..8 + 4 * n_leaves].chunks(4) {
+                if self.mark_used(
+                    DiagnosticKind::Freelist,
+                    BigEndian::read_u32(leaf),
+                    trunk_page,
+                ) {
+                    n_pages += 1;
+                }
+            }
+            referrer = trunk_page;
+            trunk_page = BigEndian::read_u32(page);
+        }
+        if n_pages != expected {
+            self.report(
+                DiagnosticKind::Freelist,
+                1,
+                format!("the freelist has {n_pages} pages, the header says {expected}"),
+            );
+        }
+    }
+
+    /// Pointer map pages are not checked, so unused pages are only reported without auto-vacuum
+    fn check_unused_pages(&mut self, bytes: &[u8]) {
+        if BigEndian::read_u32(&bytes[52..]) != 0 {
+            return;
+        }
+        let unused: Vec<u32> = (1..self.used.len() as u32)
+            .filter(|page_number| !self.used[*page_number as usize])
+            .collect();
+        for page_number in unused {
+            self.report(
+                DiagnosticKind::UnusedPage,
+                page_number,
+                "the page is never used".to_owned(),
+            );
+        }
+    }
+}
+
+/// The definition of an index, or of an automatic index from the constraints of its table.
+/// None for indexes on expressions and other definitions that can't be parsed.
+fn index_definition(entry: &SchemaEntry, schema: &[SchemaEntry]) -> Option<IndexDefinition> {
+    match &entry.sql {
+        Some(sql) => sql::parse_create_index(sql).ok(),
+        None => {
+            let table_sql = schema
+                .iter()
+                .find(|table| {
+                    table.object_type == "table"
+                        && table.name.eq_ignore_ascii_case(&entry.table_name)
+                })?
+                .sql
+                .as_deref()?;
+            sql::autoindexes(&entry.table_name, table_sql)
+                .ok()?
+                .into_iter()
+                .find(|index| index.name.eq_ignore_ascii_case(&entry.name))
+        }
+    }
+}
+
+/// The index entries for the rows of the table: the indexed columns followed by the rowid.
+/// Columns that are missing from a row are NULL, and an INTEGER PRIMARY KEY is the rowid.
+fn expected_entries(
+    definition: &IndexDefinition,
+    table_sql: &str,
+    rows: &[(i64, Vec<Value>)],
+) -> Option<Vec<(i64, Vec<Value>)>> {
+    let column_names = sql::column_names(table_sql);
+    let rowid_alias = sql::rowid_alias(table_sql).ok()?;
+    let positions = definition
+        .columns
+        .iter()
+        .map(|column| {
+            column_names
+                .iter()
+                .position(|name| name.eq_ignore_ascii_case(&column.name))
+        })
+        .collect::<Option<Vec<_>>>()?;
+    Some(
+        rows.iter()
+            .map(|(rowid, values)| {
+                let entry = positions
+                    .iter()
+                    .map(|position| {
+                        if Some(*position) == rowid_alias {
+                            values::integer(*rowid)
+                        } else {
+                            values.get(*position).cloned().unwrap_or_else(values::null)
+                        }
+                    })
+                    .chain([values::integer(*rowid)])
+                    .collect();
+                (*rowid, entry)
+            })
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compare_index_keys() {
+        let order = |columns| {
+            Some(KeyOrder {
+                columns,
+                encoding: TextEncoding::Utf8,
+            })
+        };
+        let tree = Tree::new(
+            false,
+            order(vec![(true, Collation::Binary), (false, Collation::Binary)]),
+        );
+        let key = |value: i64, rowid: i64| {
+            Key::Entry(vec![values::integer(value), values::integer(rowid)])
+        };
+        assert_eq!(tree.compare(&key(2, 1), &key(1, 2)), Some(Ordering::Less));
+        assert_eq!(tree.compare(&key(1, 1), &key(1, 2)), Some(Ordering::Less));
+        assert_eq!(Tree::new(false, None).compare(&key(1, 1), &key(1, 2)), None);
+
+        // only the primary key of a WITHOUT ROWID table counts
+        let tree = Tree::new(false, order(vec![(false, Collation::NoCase)]));
+        let row = |a: &str, b: i64| Key::Entry(vec![values::string(a), values::integer(b)]);
+        assert_eq!(
+            tree.compare(&row("a", 2), &row("B", 1)),
+            Some(Ordering::Less)
+        );
+        assert_eq!(
+            tree.compare(&row("A", 2), &row("a", 1)),
+            Some(Ordering::Equal)
+        );
+    }
+}
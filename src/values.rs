@@ -1,25 +1,248 @@
 use crate::varint;
 use byteorder::{BigEndian, ByteOrder};
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::fmt;
 
+/// A single column value, encoded in the `SQLite` record format.
+/// Values are equal when they are encoded the same.
+#[derive(Clone, PartialEq)]
 pub struct Value {
-    pub datatype: Vec<u8>,
-    pub data: Vec<u8>,
+    pub(crate) datatype: Vec<u8>,
+    pub(crate) data: Vec<u8>,
 }
 
 impl Value {
-    pub fn len(&self) -> u16 {
-        (self.datatype.len() + self.data.len()) as u16
+    pub fn is_null(&self) -> bool {
+        self.datatype == [0]
+    }
+
+    /// text has odd serial types from 13 up
+    pub(crate) fn is_text(&self) -> bool {
+        (self.datatype.len() > 1 || self.datatype[0] >= 13)
+            && self.datatype[self.datatype.len() - 1] & 1 == 1
+    }
+
+    /// The value of an INTEGER
+    pub fn as_integer(&self) -> Option<i64> {
+        match sort_key(self) {
+            SortKey::Integer(integer) => Some(integer),
+            _ => None,
+        }
+    }
+
+    /// The value of a REAL
+    pub fn as_float(&self) -> Option<f64> {
+        match sort_key(self) {
+            SortKey::Real(real) => Some(real),
+            _ => None,
+        }
+    }
+
+    /// The value of TEXT, if it is UTF-8
+    pub fn as_text(&self) -> Option<&str> {
+        match sort_key(self) {
+            SortKey::Text(text) => std::str::from_utf8(text).ok(),
+            _ => None,
+        }
+    }
+
+    /// The value of a BLOB
+    pub fn as_blob(&self) -> Option<&[u8]> {
+        match sort_key(self) {
+            SortKey::Blob(blob) => Some(blob),
+            _ => None,
+        }
+    }
+
+    /// The column type that matches the value, or None for NULL
+    #[cfg(any(feature = "serde", feature = "json"))]
+    pub(crate) fn sql_type(&self) -> Option<&'static str> {
+        match self.datatype[..] {
+            [0] => None,
+            [7] => Some("real"),
+            [serial_type] if serial_type < 12 => Some("integer"),
+            _ if self.is_text() => Some("text"),
+            _ => Some("blob"),
+        }
+    }
+
+    /// Values are created as UTF-8. Text is converted when the database uses another encoding.
+    pub(crate) fn encode_text(self, encoding: TextEncoding) -> Value {
+        if !self.is_text() || encoding == TextEncoding::Utf8 {
+            return self;
+        }
+        let value = String::from_utf8_lossy(&self.data);
+        let utf16 = value.encode_utf16();
+        if encoding == TextEncoding::Utf16le {
+            text(utf16.flat_map(u16::to_le_bytes).collect())
+        } else {
+            text(utf16.flat_map(u16::to_be_bytes).collect())
+        }
+    }
+
+    /// Text that is read from a database with another encoding is converted back to UTF-8
+    pub(crate) fn decode_text(self, encoding: TextEncoding) -> Value {
+        if !self.is_text() || encoding == TextEncoding::Utf8 {
+            return self;
+        }
+        text(utf8(&self.data, encoding).into_owned())
     }
 }
 
+/// Text in UTF-8, whatever the encoding of the database
+fn utf8(text: &[u8], encoding: TextEncoding) -> Cow<'_, [u8]> {
+    if encoding == TextEncoding::Utf8 {
+        return Cow::Borrowed(text);
+    }
+    let utf16: Vec<u16> = text
+        .chunks_exact(2)
+        .map(|bytes| {
+            if encoding == TextEncoding::Utf16le {
+                u16::from_le_bytes([bytes[0], bytes[1]])
+            } else {
+                u16::from_be_bytes([bytes[0], bytes[1]])
+            }
+        })
+        .collect();
+    Cow::Owned(String::from_utf16_lossy(&utf16).into_bytes())
+}
+
+/// Values are shown like SQL literals
+impl fmt::Debug for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match sort_key(self) {
+            SortKey::Null => f.write_str("NULL"),
+            SortKey::Integer(integer) => write!(f, "{integer}"),
+            SortKey::Real(real) => write!(f, "{real:?}"),
+            SortKey::Text(text) => {
+                write!(f, "'{}'", String::from_utf8_lossy(text).replace('\'', "''"))
+            }
+            SortKey::Blob(blob) => {
+                f.write_str("x'")?;
+                for byte in blob {
+                    write!(f, "{byte:02x}")?;
+                }
+                f.write_str("'")
+            }
+        }
+    }
+}
+
+/// The encoding for all text in a database
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum TextEncoding {
+    #[default]
+    Utf8,
+    Utf16le,
+    Utf16be,
+}
+
+impl TextEncoding {
+    /// the value in the database header
+    pub(crate) fn header_value(self) -> u32 {
+        match self {
+            TextEncoding::Utf8 => 1,
+            TextEncoding::Utf16le => 2,
+            TextEncoding::Utf16be => 3,
+        }
+    }
+}
+
+/// How an index compares text, as declared with COLLATE on the indexed column or on the table column
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) enum Collation {
+    #[default]
+    Binary,
+    /// ASCII letters are equal to their lower case
+    NoCase,
+    /// trailing spaces are ignored
+    Rtrim,
+}
+
+impl Collation {
+    /// The collations that are built into `SQLite`
+    pub(crate) fn from_name(name: &str) -> Option<Self> {
+        [
+            ("BINARY", Collation::Binary),
+            ("NOCASE", Collation::NoCase),
+            ("RTRIM", Collation::Rtrim),
+        ]
+        .into_iter()
+        .find(|(collation_name, _)| collation_name.eq_ignore_ascii_case(name))
+        .map(|(_, collation)| collation)
+    }
+
+    /// Like [`compare`], except for text. `SQLite` compares text with NOCASE and RTRIM as UTF-8,
+    /// and with BINARY in the encoding of the database.
+    pub(crate) fn compare(self, left: &Value, right: &Value, encoding: TextEncoding) -> Ordering {
+        match (self, sort_key(left), sort_key(right)) {
+            (Collation::NoCase, SortKey::Text(l), SortKey::Text(r)) => {
+                let (l, r) = (utf8(l, encoding), utf8(r, encoding));
+                l.iter()
+                    .map(u8::to_ascii_lowercase)
+                    .cmp(r.iter().map(u8::to_ascii_lowercase))
+            }
+            (Collation::Rtrim, SortKey::Text(l), SortKey::Text(r)) => {
+                let (l, r) = (utf8(l, encoding), utf8(r, encoding));
+                trim_end_spaces(&l).cmp(trim_end_spaces(&r))
+            }
+            _ => compare(left, right),
+        }
+    }
+}
+
+fn trim_end_spaces(text: &[u8]) -> &[u8] {
+    let len = text
+        .iter()
+        .rposition(|byte| *byte != b' ')
+        .map_or(0, |i| i + 1);
+    &text[..len]
+}
+
+/// SQL NULL
+pub fn null() -> Value {
+    Value {
+        datatype: vec![0],
+        data: vec![],
+    }
+}
+
+/// A TEXT value
 pub fn string(value: &str) -> Value {
-    let bytes = value.chars().map(|c| c as u8).collect::<Vec<_>>();
+    text(value.as_bytes().to_vec())
+}
+
+/// The number of bytes of a value with the serial type, or None for the reserved types 10 and 11
+pub(crate) fn serial_type_len(serial_type: u64) -> Option<usize> {
+    let len = match serial_type {
+        0 | 8 | 9 => 0,
+        1..=4 => serial_type,
+        5 => 6,
+        6 | 7 => 8,
+        10 | 11 => return None,
+        _ => (serial_type - 12) / 2,
+    };
+    usize::try_from(len).ok()
+}
+
+/// A value as it is stored in a record
+pub(crate) fn from_serial_type(serial_type: u64, data: &[u8]) -> Value {
+    Value {
+        datatype: varint::write(serial_type),
+        data: data.to_vec(),
+    }
+}
+
+/// text in any encoding; the serial type depends on the number of bytes
+fn text(bytes: Vec<u8>) -> Value {
     Value {
         datatype: varint::write((bytes.len() * 2 + 13) as u64),
         data: bytes,
     }
 }
 
+/// A BLOB value
 pub fn blob(value: Vec<u8>) -> Value {
     Value {
         datatype: varint::write((value.len() * 2 + 12) as u64),
@@ -27,6 +250,7 @@ pub fn blob(value: Vec<u8>) -> Value {
     }
 }
 
+/// An INTEGER value, stored in the smallest encoding that fits
 pub fn integer(value: i64) -> Value {
     Value {
         datatype: get_int_type(value),
@@ -34,6 +258,7 @@ pub fn integer(value: i64) -> Value {
     }
 }
 
+/// A REAL value
 pub fn float(value: f64) -> Value {
     let mut buffer = [0_u8; 8];
     BigEndian::write_f64(&mut buffer, value);
@@ -43,8 +268,59 @@ pub fn float(value: f64) -> Value {
     }
 }
 
-pub fn len(value: &Value) -> usize {
-    value.datatype.len() + value.data.len()
+macro_rules! from_integer {
+    ($($t:ty),*) => {
+        $(impl From<$t> for Value {
+            fn from(value: $t) -> Self {
+                integer(i64::from(value))
+            }
+        })*
+    };
+}
+
+from_integer!(i8, i16, i32, i64, u8, u16, u32, bool);
+
+impl From<f32> for Value {
+    fn from(value: f32) -> Self {
+        float(f64::from(value))
+    }
+}
+
+impl From<f64> for Value {
+    fn from(value: f64) -> Self {
+        float(value)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        string(value)
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        string(&value)
+    }
+}
+
+impl From<&[u8]> for Value {
+    fn from(value: &[u8]) -> Self {
+        blob(value.to_vec())
+    }
+}
+
+impl From<Vec<u8>> for Value {
+    fn from(value: Vec<u8>) -> Self {
+        blob(value)
+    }
+}
+
+/// `None` becomes NULL
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(value: Option<T>) -> Self {
+        value.map_or_else(null, Into::into)
+    }
 }
 
 /// sqlite specific way to encode integers
@@ -83,6 +359,84 @@ fn get_int_type(value: i64) -> Vec<u8> {
     }
 }
 
+/// A decoded value, as far as needed for sorting
+enum SortKey<'a> {
+    Null,
+    Integer(i64),
+    Real(f64),
+    Text(&'a [u8]),
+    Blob(&'a [u8]),
+}
+
+fn sort_key(value: &Value) -> SortKey<'_> {
+    match value.datatype[..] {
+        [0] => SortKey::Null,
+        [7] => SortKey::Real(BigEndian::read_f64(&value.data)),
+        [8] => SortKey::Integer(0),
+        [9] => SortKey::Integer(1),
+        [serial_type] if serial_type < 7 => {
+            SortKey::Integer(BigEndian::read_int(&value.data, value.data.len()))
+        }
+        _ if value.is_text() => SortKey::Text(&value.data),
+        _ => SortKey::Blob(&value.data),
+    }
+}
+
+/// Appends the value the way a session changeset stores it: a type byte, followed by 8 big-endian bytes
+/// for numbers, or the length and bytes of text and blobs. Text has to be UTF-8.
+pub(crate) fn write_changeset_value(value: &Value, bytes: &mut Vec<u8>) {
+    match sort_key(value) {
+        SortKey::Null => bytes.push(5),
+        SortKey::Integer(integer) => {
+            bytes.push(1);
+            bytes.extend_from_slice(&integer.to_be_bytes());
+        }
+        SortKey::Real(real) => {
+            bytes.push(2);
+            bytes.extend_from_slice(&real.to_be_bytes());
+        }
+        SortKey::Text(data) | SortKey::Blob(data) => {
+            bytes.push(if value.is_text() { 3 } else { 4 });
+            bytes.extend_from_slice(&varint::write(data.len() as u64));
+            bytes.extend_from_slice(data);
+        }
+    }
+}
+
+/// Compares values the way `SQLite` does with the BINARY collation:
+/// NULL comes first, then numbers, then text and finally blobs
+pub(crate) fn compare(left: &Value, right: &Value) -> Ordering {
+    use SortKey::*;
+    match (sort_key(left), sort_key(right)) {
+        (Null, Null) => Ordering::Equal,
+        (Null, _) => Ordering::Less,
+        (_, Null) => Ordering::Greater,
+        (Integer(l), Integer(r)) => l.cmp(&r),
+        (Real(l), Real(r)) => l.total_cmp(&r),
+        (Integer(l), Real(r)) => compare_integer_real(l, r),
+        (Real(l), Integer(r)) => compare_integer_real(r, l).reverse(),
+        (Integer(_) | Real(_), _) => Ordering::Less,
+        (_, Integer(_) | Real(_)) => Ordering::Greater,
+        (Text(l), Text(r)) | (Blob(l), Blob(r)) => l.cmp(r),
+        (Text(_), Blob(_)) => Ordering::Less,
+        (Blob(_), Text(_)) => Ordering::Greater,
+    }
+}
+
+/// exact comparison, also for integers that do not fit in a f64
+fn compare_integer_real(integer: i64, real: f64) -> Ordering {
+    if real < -9_223_372_036_854_775_808.0 {
+        Ordering::Greater
+    } else if real >= 9_223_372_036_854_775_808.0 {
+        Ordering::Less
+    } else {
+        match integer.cmp(&(real as i64)) {
+            Ordering::Equal => (integer as f64).total_cmp(&real),
+            ordering => ordering,
+        }
+    }
+}
+
 fn get_length_of_byte_encoding(value: i64) -> u8 {
     let u = if value < 0 { !value } else { value };
     if u <= 127 {
@@ -111,6 +465,32 @@ mod tests {
         assert_eq!(v.data, vec![0x68, 0x65, 0x6C, 0x6C, 0x6F]);
     }
 
+    #[test]
+    fn test_string_utf8() {
+        let v = string("zażółć");
+        assert_eq!(v.datatype, vec![33]);
+        assert_eq!(v.data, "zażółć".as_bytes());
+    }
+
+    #[test]
+    fn test_string_utf16() {
+        let v = string("zażółć").encode_text(TextEncoding::Utf16le);
+        assert_eq!(v.datatype, vec![37]);
+        assert_eq!(v.data[0..4], [0x7a, 0x00, 0x61, 0x00]);
+        let v = string("日本").encode_text(TextEncoding::Utf16be);
+        assert_eq!(v.datatype, vec![21]);
+        assert_eq!(v.data, vec![0x65, 0xe5, 0x67, 0x2c]);
+    }
+
+    #[test]
+    fn test_encode_text_keeps_other_values() {
+        let v = integer(3).encode_text(TextEncoding::Utf16le);
+        assert_eq!(v.datatype, vec![1]);
+        assert_eq!(v.data, vec![3]);
+        let v = blob(vec![1, 2]).encode_text(TextEncoding::Utf16le);
+        assert_eq!(v.data, vec![1, 2]);
+    }
+
     #[test]
     fn test_blob() {
         let v = blob(vec![1, 2, 3, 4, 5]);
@@ -129,14 +509,14 @@ mod tests {
     fn test_integer0() {
         let v = integer(0);
         assert_eq!(v.datatype, vec![8]);
-        assert_eq!(v.data, vec![]);
+        assert_eq!(v.data, Vec::<u8>::new());
     }
 
     #[test]
     fn test_integer1() {
         let v = integer(1);
         assert_eq!(v.datatype, vec![9]);
-        assert_eq!(v.data, vec![]);
+        assert_eq!(v.data, Vec::<u8>::new());
     }
 
     #[test]
@@ -146,6 +526,86 @@ mod tests {
         assert_eq!(v.data, vec![2]);
     }
 
+    #[test]
+    fn test_null() {
+        let v = null();
+        assert_eq!(v.datatype, vec![0]);
+        assert_eq!(v.data, Vec::<u8>::new());
+    }
+
+    #[test]
+    fn test_from_option() {
+        let v = Value::from(None::<i64>);
+        assert_eq!(v.datatype, vec![0]);
+        let v = Value::from(Some("hello"));
+        assert_eq!(v.datatype, vec![23]);
+        assert_eq!(v.data, vec![0x68, 0x65, 0x6C, 0x6C, 0x6F]);
+        let v = Value::from(Some(128_u8));
+        assert_eq!(v.datatype, vec![2]);
+        assert_eq!(v.data, vec![0, 128]);
+    }
+
+    #[test]
+    fn test_compare() {
+        let ordered = vec![
+            null(),
+            integer(-300),
+            float(-1.5),
+            integer(0),
+            integer(1),
+            float(1.5),
+            integer(128),
+            string("a"),
+            string("ab"),
+            string("b"),
+            blob(vec![0]),
+        ];
+        for (index, value) in ordered.iter().enumerate() {
+            assert_eq!(compare(value, value), Ordering::Equal);
+            for other in &ordered[index + 1..] {
+                assert_eq!(compare(value, other), Ordering::Less);
+                assert_eq!(compare(other, value), Ordering::Greater);
+            }
+        }
+    }
+
+    #[test]
+    fn test_compare_collated() {
+        let utf16 = |value: &str| string(value).encode_text(TextEncoding::Utf16be);
+        let nocase = |left: &Value, right: &Value, encoding| {
+            Collation::NoCase.compare(left, right, encoding)
+        };
+        assert_eq!(
+            nocase(&string("Abc"), &string("aBC"), TextEncoding::Utf8),
+            Ordering::Equal
+        );
+        assert_eq!(
+            nocase(&string("B"), &string("a"), TextEncoding::Utf8),
+            Ordering::Greater
+        );
+        assert_eq!(
+            nocase(&utf16("_"), &utf16("a"), TextEncoding::Utf16be),
+            Ordering::Less
+        );
+        // only ASCII letters
+        assert_eq!(
+            nocase(&string("Ä"), &string("ä"), TextEncoding::Utf8),
+            Ordering::Less
+        );
+        assert_eq!(
+            nocase(&integer(1), &string("a"), TextEncoding::Utf8),
+            Ordering::Less
+        );
+
+        let rtrim = |left: &str, right: &str| {
+            Collation::Rtrim.compare(&string(left), &string(right), TextEncoding::Utf8)
+        };
+        assert_eq!(rtrim("a  ", "a"), Ordering::Equal);
+        assert_eq!(rtrim(" a", "a"), Ordering::Less);
+        assert_eq!(Collation::from_name("nocase"), Some(Collation::NoCase));
+        assert_eq!(Collation::from_name("unicode"), None);
+    }
+
     #[test]
     fn test_integer128() {
         let v = integer(128);
@@ -26,6 +26,28 @@ pub fn write(value: u64) -> Vec<u8> {
     }
 }
 
+/// Signed integers, like rowids, are written in two's complement. Negative ones take 9 bytes.
+pub fn write_signed(value: i64) -> Vec<u8> {
+    write(value as u64)
+}
+
+/// Reads a varint from the start of the bytes. Returns the value and the number of bytes it takes,
+/// or None when the bytes end before the varint does.
+pub fn read(bytes: &[u8]) -> Option<(u64, usize)> {
+    let mut value = 0_u64;
+    for (index, byte) in bytes.iter().enumerate() {
+        if index == 8 {
+            // the ninth byte contributes all of its 8 bits
+            return Some(((value << 8) | u64::from(*byte), 9));
+        }
+        value = (value << 7) | u64::from(byte & 0x7f);
+        if byte & 0x80 == 0 {
+            return Some((value, index + 1));
+        }
+    }
+    None
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -43,6 +65,37 @@ mod tests {
         assert_eq!(vec![1], write(0x01));
     }
 
+    #[test]
+    fn test_write_signed() {
+        assert_eq!(vec![0xFF; 9], write_signed(-1));
+        assert_eq!(
+            vec![0xC0, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x00],
+            write_signed(i64::MIN)
+        );
+        assert_eq!(write(0x7f), write_signed(0x7f));
+    }
+
+    #[test]
+    fn test_read() {
+        for value in [
+            0,
+            1,
+            0x7f,
+            0x80,
+            0x3fff,
+            0x4000,
+            u64::MAX >> 8,
+            u64::MAX - 1,
+            u64::MAX,
+        ] {
+            let bytes = write(value);
+            assert_eq!(read(&bytes), Some((value, bytes.len())));
+        }
+        assert_eq!(read(&write_signed(i64::MIN)), Some((i64::MIN as u64, 9)));
+        assert_eq!(read(&[0x81, 0x01, 0xff]), Some((0x81, 2)));
+        assert_eq!(read(&[0x81]), None);
+    }
+
     #[test]
     fn test_write0() {
         assert_eq!(vec![0], write(0));
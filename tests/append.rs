@@ -0,0 +1,187 @@
+use sqlighters::{
+    validate, values, DatabaseAppender, DatabaseBuilder, DatabaseReader, Error, Record,
+    TextEncoding,
+};
+use std::io::Cursor;
+
+const SQLITE_FILE: &[u8] = include_bytes!("data/sqlite.db");
+const PAGE_SIZE: usize = 512;
+
+fn record(rowid: i64) -> Record {
+    let mut record = Record::new(rowid);
+    record.add_value(values::string(&format!("foo {rowid}")));
+    // every 100th record overflows
+    let len = if rowid % 100 == 0 { 2000 } else { 10 };
+    record.add_value(values::blob(vec![rowid as u8; len]));
+    record
+}
+
+fn database(rowids: impl Iterator<Item = i64>) -> Result<Vec<u8>, Error> {
+    let mut builder = DatabaseBuilder::new();
+    builder.page_size(PAGE_SIZE as u32)?;
+    builder.encoding(TextEncoding::Utf16le)?;
+    builder.schema("foo", "create table foo(bar text, baz blob)")?;
+    for rowid in rowids {
+        builder.add_record(record(rowid))?;
+    }
+    builder.schema("other", "create table other(bar)")?;
+    let mut record = Record::new(1);
+    record.add_value(values::integer(1));
+    builder.add_record(record)?;
+    let database = builder.build()?;
+    database.to_bytes()
+}
+
+fn changed_pages(before: &[u8], after: &[u8]) -> Vec<usize> {
+    before
+        .chunks(PAGE_SIZE)
+        .zip(after.chunks(PAGE_SIZE))
+        .enumerate()
+        .filter(|(_, (before, after))| before != after)
+        .map(|(index, _)| index + 1)
+        .collect()
+}
+
+#[test]
+fn test_append() -> Result<(), Error> {
+    // from a single root leaf page to a b-tree with interior pages on several levels
+    for (n_records, n_appended) in [(0, 0), (0, 3), (3, 5000), (1000, 1), (1000, 20_000)] {
+        let before = database(1..=n_records)?;
+        let mut appender = DatabaseAppender::new(&before)?;
+        appender.table("foo")?;
+        for rowid in n_records + 1..=n_records + n_appended {
+            appender.add_record(record(rowid))?;
+        }
+        let after = appender.into_bytes()?;
+
+        assert_eq!(validate(&after), [], "{n_records} + {n_appended}");
+        let reader = DatabaseReader::new(&after)?;
+        let records = reader.records("foo")?;
+        assert_eq!(records.len() as i64, n_records + n_appended);
+        for (record, rowid) in records.iter().zip(1..) {
+            assert_eq!(record, &self::record(rowid));
+        }
+        assert_eq!(reader.records("other")?.len(), 1);
+        assert_eq!(
+            DatabaseReader::new(&before)?.schema()?,
+            reader.schema()?,
+            "the root pages stay the same"
+        );
+    }
+    Ok(())
+}
+
+#[test]
+fn test_unchanged_pages() -> Result<(), Error> {
+    let before = database(1..=1000)?;
+    let mut appender = DatabaseAppender::new(&before)?;
+    appender.table("foo")?;
+    appender.add_record(record(1001))?;
+    let after = appender.into_bytes()?;
+    assert_eq!(after.len(), before.len());
+    // the header and the last leaf page
+    assert_eq!(changed_pages(&before, &after).len(), 2);
+
+    let mut appender = DatabaseAppender::new(&before)?;
+    appender.table("foo")?;
+    for rowid in 1001..=1100 {
+        appender.add_record(record(rowid))?;
+    }
+    let after = appender.into_bytes()?;
+    assert!(after.len() > before.len());
+    // the header, the last leaf page and the interior pages above it
+    assert!(changed_pages(&before, &after).len() <= 4);
+
+    // nothing changes without records
+    let mut appender = DatabaseAppender::new(&before)?;
+    appender.table("foo")?;
+    assert_eq!(appender.into_bytes()?, before);
+    Ok(())
+}
+
+#[test]
+fn test_write_changes() -> Result<(), Error> {
+    let before = database(1..=1000)?;
+    let append = |appender: &mut DatabaseAppender| -> Result<(), Error> {
+        appender.table("other")?;
+        appender.add_record(record(2))?;
+        appender.table("foo")?;
+        for rowid in 1001..=3000 {
+            appender.add_record(record(rowid))?;
+        }
+        Ok(())
+    };
+    let mut appender = DatabaseAppender::new(&before)?;
+    append(&mut appender)?;
+    let after = appender.into_bytes()?;
+
+    let mut file = Cursor::new(before.clone());
+    let mut appender = DatabaseAppender::new(&before)?;
+    append(&mut appender)?;
+    appender.write_changes(&mut file)?;
+    assert_eq!(file.into_inner(), after);
+    Ok(())
+}
+
+#[test]
+fn test_rowid_alias() -> Result<(), Error> {
+    let mut builder = DatabaseBuilder::new();
+    builder.schema("foo", "create table foo(id integer primary key, bar)")?;
+    let database = builder.build()?;
+    let before = database.to_bytes()?;
+
+    let mut appender = DatabaseAppender::new(&before)?;
+    appender.table("foo")?;
+    let mut record = Record::new(0);
+    record.add_value(values::integer(5));
+    record.add_value(values::string("bar"));
+    appender.add_record(record)?;
+    let after = appender.into_bytes()?;
+    let records = DatabaseReader::new(&after)?.records("foo")?;
+    assert_eq!(records[0].rowid, 5);
+    assert!(records[0].values()[0].is_null());
+    Ok(())
+}
+
+#[test]
+fn test_append_mistakes() -> Result<(), Error> {
+    let before = database(1..=10)?;
+    let mut appender = DatabaseAppender::new(&before)?;
+    assert!(matches!(
+        appender.add_record(record(11)),
+        Err(Error::InvalidSchema(_))
+    ));
+    assert!(matches!(
+        appender.table("nothing"),
+        Err(Error::InvalidSchema(_))
+    ));
+    appender.table("foo")?;
+    assert!(matches!(
+        appender.add_record(record(10)),
+        Err(Error::NonMonotonicRowid {
+            previous: 10,
+            rowid: 10,
+            ..
+        })
+    ));
+    appender.table("other")?;
+    assert!(matches!(
+        appender.table("FOO"),
+        Err(Error::InvalidSchema(_))
+    ));
+
+    // new index entries don't go at the end
+    let mut appender = DatabaseAppender::new(SQLITE_FILE)?;
+    assert!(matches!(
+        appender.table("people"),
+        Err(Error::InvalidSchema(_))
+    ));
+
+    let mut auto_vacuum = SQLITE_FILE.to_vec();
+    auto_vacuum[55] = 3;
+    assert!(matches!(
+        DatabaseAppender::new(&auto_vacuum),
+        Err(Error::Input(_))
+    ));
+    Ok(())
+}
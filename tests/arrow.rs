@@ -0,0 +1,166 @@
+#![cfg(feature = "arrow")]
+
+use arrow_array::types::Int8Type;
+use arrow_array::{
+    ArrayRef, BinaryArray, BooleanArray, Date32Array, Decimal128Array, DictionaryArray,
+    Float64Array, Int64Array, RecordBatch, StringArray, TimestampMillisecondArray, UInt64Array,
+};
+use arrow_schema::{DataType, Field, Schema, TimeUnit};
+use sqlighters::{
+    validate, values, write_sqlite, DatabaseBuilder, DatabaseReader, Error,
+    StreamingDatabaseBuilder,
+};
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::sync::Arc;
+
+fn batch(first_id: i64, n_rows: usize) -> RecordBatch {
+    let ids: Vec<i64> = (first_id..).take(n_rows).collect();
+    let names: Vec<Option<String>> = ids
+        .iter()
+        .map(|id| (id % 10 != 0).then(|| format!("name {id}")))
+        .collect();
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(Int64Array::from(ids.clone())),
+        Arc::new(StringArray::from(names)),
+        Arc::new(Float64Array::from_iter(
+            ids.iter().map(|id| Some(*id as f64 / 2.0)),
+        )),
+        Arc::new(BooleanArray::from_iter(
+            ids.iter().map(|id| Some(id % 2 == 0)),
+        )),
+        Arc::new(BinaryArray::from_iter(
+            ids.iter().map(|id| Some(id.to_le_bytes())),
+        )),
+        Arc::new(Date32Array::from_iter(
+            ids.iter().map(|id| Some(*id as i32)),
+        )),
+        Arc::new(TimestampMillisecondArray::from_iter(
+            ids.iter().map(|id| Some(id * 1000)),
+        )),
+        Arc::new(DictionaryArray::<Int8Type>::from_iter(ids.iter().map(
+            |id| (id % 3 != 0).then_some(["a", "b", "c"][*id as usize % 3]),
+        ))),
+        Arc::new(
+            Decimal128Array::from_iter(
+                ids.iter()
+                    .map(|id| (id % 7 != 0).then_some(*id as i128 * 25)),
+            )
+            .with_precision_and_scale(10, 2)
+            .unwrap(),
+        ),
+    ];
+    RecordBatch::try_new(Arc::new(schema()), columns).unwrap()
+}
+
+fn schema() -> Schema {
+    Schema::new(vec![
+        Field::new("id", DataType::Int64, false),
+        Field::new("name", DataType::Utf8, true),
+        Field::new("score", DataType::Float64, true),
+        Field::new("even", DataType::Boolean, true),
+        Field::new("bytes", DataType::Binary, true),
+        Field::new("day", DataType::Date32, true),
+        Field::new(
+            "time",
+            DataType::Timestamp(TimeUnit::Millisecond, None),
+            true,
+        ),
+        Field::new(
+            "letter",
+            DataType::Dictionary(Box::new(DataType::Int8), Box::new(DataType::Utf8)),
+            true,
+        ),
+        Field::new("price", DataType::Decimal128(10, 2), true),
+    ])
+}
+
+const CREATE_TABLE: &[u8] = br#"CREATE TABLE "items"("id" INTEGER NOT NULL, "name" TEXT, "score" REAL, "even" INTEGER, "bytes" BLOB, "day" TEXT, "time" TEXT, "letter" TEXT, "price" REAL)"#;
+
+/// Reads the rows of `batch` back, with rowids from 1
+fn check_items(bytes: &[u8], n_rows: i64) -> Result<(), Error> {
+    assert!(validate(bytes).is_empty());
+    let records = DatabaseReader::new(bytes)?.records("items")?;
+    assert_eq!(records.len() as i64, n_rows);
+    for (id, record) in (1..).zip(&records) {
+        assert_eq!(record.rowid, id);
+        let values = record.values();
+        assert_eq!(values[0], values::integer(id));
+        if id % 10 == 0 {
+            assert!(values[1].is_null());
+        } else {
+            assert_eq!(values[1], values::string(&format!("name {id}")));
+        }
+        assert_eq!(values[2], values::float(id as f64 / 2.0));
+        assert_eq!(values[3], values::integer(i64::from(id % 2 == 0)));
+        assert_eq!(values[4], values::blob(id.to_le_bytes().to_vec()));
+        if id % 3 == 0 {
+            assert!(values[7].is_null());
+        } else {
+            assert_eq!(values[7], values::string(["a", "b", "c"][id as usize % 3]));
+        }
+        if id % 7 == 0 {
+            assert!(values[8].is_null());
+        } else {
+            assert_eq!(values[8], values::float(id as f64 * 0.25));
+        }
+    }
+    assert_eq!(records[0].values()[5], values::string("1970-01-02"));
+    assert_eq!(
+        records[0].values()[6],
+        values::string("1970-01-01 00:00:01")
+    );
+    Ok(())
+}
+
+#[test]
+fn test_add_record_batch() -> Result<(), Error> {
+    let mut builder = DatabaseBuilder::new();
+    builder.schema_from_arrow("items", &schema())?;
+    builder.add_record_batch(&batch(1, 5_000))?;
+    builder.add_record_batch(&batch(5_001, 5_000))?;
+    let database = builder.build()?;
+    let path = std::env::temp_dir().join("arrow.db");
+    write_sqlite(database, BufWriter::new(File::create(&path)?))?;
+
+    let bytes = fs::read(&path)?;
+    assert!(bytes.windows(CREATE_TABLE.len()).any(|w| w == CREATE_TABLE));
+    check_items(&bytes, 10_000)
+}
+
+#[test]
+fn test_streaming_add_record_batch() -> Result<(), Error> {
+    let path = std::env::temp_dir().join("arrow_streaming.db");
+    let mut builder = StreamingDatabaseBuilder::new(BufWriter::new(File::create(&path)?));
+    builder.schema_from_arrow("items", &schema())?;
+    builder.add_record_batch(&batch(1, 10_000))?;
+    builder.finish()?;
+
+    let bytes = fs::read(&path)?;
+    assert!(bytes.windows(CREATE_TABLE.len()).any(|w| w == CREATE_TABLE));
+    check_items(&bytes, 10_000)
+}
+
+#[test]
+fn test_unsigned_out_of_range() -> Result<(), Error> {
+    let schema = Schema::new(vec![Field::new("n", DataType::UInt64, false)]);
+    let column: ArrayRef = Arc::new(UInt64Array::from(vec![1, u64::MAX]));
+    let batch = RecordBatch::try_new(Arc::new(schema.clone()), vec![column]).unwrap();
+
+    let mut builder = DatabaseBuilder::new();
+    builder.schema_from_arrow("foo", &schema)?;
+    assert!(matches!(
+        builder.add_record_batch(&batch),
+        Err(Error::Input(_))
+    ));
+    Ok(())
+}
+
+#[test]
+fn test_record_batch_without_table() {
+    let mut builder = DatabaseBuilder::new();
+    assert!(matches!(
+        builder.add_record_batch(&batch(1, 1)),
+        Err(Error::InvalidSchema(_))
+    ));
+}
@@ -0,0 +1,63 @@
+#![cfg(feature = "axum")]
+
+use axum_core::response::IntoResponse;
+use http::header::{CONTENT_DISPOSITION, CONTENT_LENGTH, CONTENT_TYPE};
+use http::StatusCode;
+use http_body::Body;
+use sqlighters::{values, Database, DatabaseBuilder, Error, Record};
+use std::pin::Pin;
+use std::task::{Context, Poll, Waker};
+
+fn database(unique: bool) -> Result<Database, Error> {
+    let mut builder = DatabaseBuilder::new();
+    builder.schema("foo", "create table foo(bar text)")?;
+    let unique = if unique { "unique" } else { "" };
+    builder.index(&format!("create {unique} index foo_bar on foo(bar)"))?;
+    for i in 0..1000 {
+        let mut record = Record::new(i);
+        record.add_value(values::string(&"helloworld".repeat(i as usize % 10)));
+        builder.add_record(record)?;
+    }
+    builder.build()
+}
+
+/// The body is ready right away, so it doesn't need a runtime
+fn body_bytes(mut body: impl Body<Data = bytes::Bytes> + Unpin) -> Vec<u8> {
+    let mut context = Context::from_waker(Waker::noop());
+    let mut bytes = Vec::new();
+    while let Poll::Ready(Some(frame)) = Pin::new(&mut body).poll_frame(&mut context) {
+        let Ok(frame) = frame else {
+            panic!("body error")
+        };
+        bytes.extend_from_slice(frame.data_ref().unwrap());
+    }
+    bytes
+}
+
+#[test]
+fn test_into_response() -> Result<(), Error> {
+    let expected = database(false)?.to_bytes()?;
+    let response = database(false)?.attachment("foo.db").into_response();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let headers = response.headers();
+    assert_eq!(headers[CONTENT_TYPE], "application/vnd.sqlite3");
+    assert_eq!(headers[CONTENT_LENGTH], expected.len().to_string());
+    assert_eq!(
+        headers[CONTENT_DISPOSITION],
+        "attachment; filename=\"foo.db\""
+    );
+    assert_eq!(
+        response.body().size_hint().exact(),
+        Some(expected.len() as u64)
+    );
+
+    assert_eq!(body_bytes(response.into_body()), expected);
+    Ok(())
+}
+
+#[test]
+fn test_duplicate_key() {
+    // a database that can't be written is never a response
+    assert!(matches!(database(true), Err(Error::DuplicateKey { .. })));
+}
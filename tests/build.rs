@@ -0,0 +1,585 @@
+use sqlighters::{
+    validate, values, write_sqlite, DatabaseBuilder, DatabaseReader, Error, Record, TextEncoding,
+    Value,
+};
+use std::fs::{self, File};
+use std::io::BufWriter;
+
+const MAGIC_HEADER: &[u8] = b"SQLite format 3\0";
+
+#[test]
+fn test_build() -> Result<(), Error> {
+    let mut builder = DatabaseBuilder::new();
+    builder.schema("foo", "create table foo(bar varchar(10))")?;
+    for i in 0..10000 {
+        let mut record = Record::new(i);
+        record.add_value(values::string("helloworld"));
+        builder.add_record(record)?;
+    }
+    let database = builder.build()?;
+    let path = std::env::temp_dir().join("foo.db");
+    let writer = BufWriter::new(File::create(&path)?);
+    write_sqlite(database, writer)?;
+
+    let bytes = fs::read(&path)?;
+    assert_eq!(&bytes[0..16], MAGIC_HEADER);
+    assert_eq!(bytes.len() % 4096, 0);
+    Ok(())
+}
+
+#[test]
+fn test_build_multiple_tables() -> Result<(), Error> {
+    let mut builder = DatabaseBuilder::new();
+    builder.schema("foo", "create table foo(bar varchar(10))")?;
+    for i in 0..200_000 {
+        let mut record = Record::new(i);
+        record.add_value(values::string("helloworld"));
+        builder.add_record(record)?;
+    }
+    builder.schema("baz", "create table baz(qux integer)")?;
+    for i in 0..10 {
+        let mut record = Record::new(i);
+        record.add_value(values::integer(i));
+        builder.add_record(record)?;
+    }
+    builder.schema("empty", "create table empty(id integer)")?;
+    let database = builder.build()?;
+    let path = std::env::temp_dir().join("foo_baz.db");
+    let writer = BufWriter::new(File::create(&path)?);
+    write_sqlite(database, writer)?;
+
+    let bytes = fs::read(&path)?;
+    assert_eq!(&bytes[0..16], MAGIC_HEADER);
+    assert_eq!(bytes[103..105], [0, 3]); // 3 tables in sqlite_master
+    Ok(())
+}
+
+#[test]
+fn test_build_overflow() -> Result<(), Error> {
+    let mut builder = DatabaseBuilder::new();
+    builder.schema("foo", "create table foo(bar text, baz blob)")?;
+    for i in 0..100 {
+        let mut record = Record::new(i);
+        record.add_value(values::string(&"helloworld".repeat(i as usize * 50)));
+        record.add_value(values::blob(vec![i as u8; 10_000]));
+        builder.add_record(record)?;
+    }
+    let database = builder.build()?;
+    let path = std::env::temp_dir().join("foo_overflow.db");
+    let writer = BufWriter::new(File::create(&path)?);
+    write_sqlite(database, writer)?;
+
+    let bytes = fs::read(&path)?;
+    let n_pages = u32::from_be_bytes(bytes[28..32].try_into().unwrap());
+    assert_eq!(bytes.len(), n_pages as usize * 4096);
+    assert!(validate(&bytes).is_empty());
+
+    // the overflow chains hold the rest of the values
+    let records = DatabaseReader::new(&bytes)?.records("foo")?;
+    assert_eq!(records.len(), 100);
+    for (i, record) in records.iter().enumerate() {
+        assert_eq!(record.rowid, i as i64);
+        let text = "helloworld".repeat(i * 50);
+        assert_eq!(record.values()[0].as_text(), Some(text.as_str()));
+        assert_eq!(record.values()[1].as_blob(), Some(&[i as u8; 10_000][..]));
+    }
+    Ok(())
+}
+
+#[test]
+fn test_build_index() -> Result<(), Error> {
+    let mut builder = DatabaseBuilder::new();
+    builder.schema(
+        "foo",
+        "create table foo(id integer, name text, description text)",
+    )?;
+    builder.index("create index foo_name on foo(name, id desc)")?;
+    builder.index("create unique index foo_id on foo(id)")?;
+    builder.index("create index foo_description on foo(description)")?;
+    for i in 0..10_000 {
+        let mut record = Record::new(i);
+        record.add_value(values::integer(i * 7919 % 10_000));
+        record.add_value(values::string(&format!("name{}", i % 1000)));
+        record.add_value(values::string(&"description".repeat(i as usize % 400)));
+        builder.add_record(record)?;
+    }
+    let database = builder.build()?;
+    let path = std::env::temp_dir().join("foo_index.db");
+    let writer = BufWriter::new(File::create(&path)?);
+    write_sqlite(database, writer)?;
+
+    let bytes = fs::read(&path)?;
+    assert_eq!(bytes[103..105], [0, 4]); // table and 3 indexes in sqlite_master
+    let n_pages = u32::from_be_bytes(bytes[28..32].try_into().unwrap());
+    assert_eq!(bytes.len(), n_pages as usize * 4096);
+    Ok(())
+}
+
+#[test]
+fn test_index_after_records() -> Result<(), Error> {
+    let mut builder = DatabaseBuilder::new();
+    builder.schema("foo", "create table foo(bar text)")?;
+    let mut record = Record::new(1);
+    record.add_value(values::string("helloworld"));
+    builder.add_record(record)?;
+    assert!(matches!(
+        builder.index("create index foo_bar on foo(bar)"),
+        Err(Error::InvalidSchema(_))
+    ));
+    Ok(())
+}
+
+#[test]
+fn test_invalid_schema() -> Result<(), Error> {
+    let mut builder = DatabaseBuilder::new();
+    assert!(matches!(
+        builder.add_record(Record::new(1)),
+        Err(Error::InvalidSchema(_))
+    ));
+    builder.schema("foo", "create table foo(bar text)")?;
+    assert!(matches!(
+        builder.schema("FOO", "create table FOO(bar text)"),
+        Err(Error::InvalidSchema(_))
+    ));
+    assert!(matches!(
+        builder.index("create index foo_baz on foo(baz)"),
+        Err(Error::InvalidSchema(_))
+    ));
+    assert!(matches!(
+        builder.index("create index bar_baz on bar(baz)"),
+        Err(Error::InvalidSchema(_))
+    ));
+    assert!(matches!(
+        builder.encoding(TextEncoding::Utf16le),
+        Err(Error::InvalidSchema(_))
+    ));
+    assert!(matches!(
+        builder.schema(
+            "bar",
+            "create table bar(a text primary key, b int) without rowid"
+        ),
+        Err(Error::InvalidSchema(_))
+    ));
+    Ok(())
+}
+
+#[test]
+fn test_non_monotonic_rowid() -> Result<(), Error> {
+    let mut builder = DatabaseBuilder::new();
+    builder.schema("foo", "create table foo(bar text)")?;
+    builder.add_record(Record::new(2))?;
+    assert!(matches!(
+        builder.add_record(Record::new(2)),
+        Err(Error::NonMonotonicRowid {
+            previous: 2,
+            rowid: 2,
+            ..
+        })
+    ));
+    Ok(())
+}
+
+#[test]
+fn test_duplicate_key() -> Result<(), Error> {
+    let mut builder = DatabaseBuilder::new();
+    builder.schema("foo", "create table foo(bar text)")?;
+    builder.index("create unique index foo_bar on foo(bar)")?;
+    for i in 1..=3 {
+        let mut record = Record::new(i);
+        record.add_value(values::string("helloworld"));
+        builder.add_record(record)?;
+    }
+    let result = builder.build();
+    assert!(matches!(result, Err(Error::DuplicateKey { index }) if index == "foo_bar"));
+    Ok(())
+}
+
+#[test]
+fn test_build_autoindexes() -> Result<(), Error> {
+    let mut builder = DatabaseBuilder::new();
+    builder.schema(
+        "foo",
+        "create table foo(id integer primary key, code text unique, a int, b int, unique(a, b))",
+    )?;
+    for i in 1..=1000 {
+        let mut record = Record::new(i);
+        record.add_value(values::null());
+        record.add_value(values::string(&format!("code{i}")));
+        record.add_value(values::integer(i % 10));
+        record.add_value(values::integer(i / 10));
+        builder.add_record(record)?;
+    }
+    let database = builder.build()?;
+    let path = std::env::temp_dir().join("foo_autoindex.db");
+    write_sqlite(database, BufWriter::new(File::create(&path)?))?;
+
+    let bytes = fs::read(&path)?;
+    // the table and the indexes for code and (a, b), but not for the rowid alias
+    assert_eq!(bytes[103..105], [0, 3]);
+
+    let mut builder = DatabaseBuilder::new();
+    builder.schema("foo", "create table foo(bar text primary key)")?;
+    for i in 1..=2 {
+        let mut record = Record::new(i);
+        record.add_value(values::string("helloworld"));
+        builder.add_record(record)?;
+    }
+    let result = builder.build();
+    assert!(
+        matches!(result, Err(Error::DuplicateKey { index }) if index == "sqlite_autoindex_foo_1")
+    );
+    Ok(())
+}
+
+#[test]
+fn test_build_collations() -> Result<(), Error> {
+    let mut builder = DatabaseBuilder::new();
+    builder.schema(
+        "foo",
+        "create table foo(name text collate nocase unique, code text collate rtrim)",
+    )?;
+    builder.index("create index foo_code on foo(code)")?;
+    builder.index("create index foo_name on foo(name collate binary)")?;
+    for (i, (name, code)) in [("apple", "b "), ("Banana", "a"), ("cherry", "b")]
+        .into_iter()
+        .enumerate()
+    {
+        let mut record = Record::new(i as i64 + 1);
+        record.add_value(values::string(name));
+        record.add_value(values::string(code));
+        builder.add_record(record)?;
+    }
+    let database = builder.build()?;
+    let path = std::env::temp_dir().join("foo_collations.db");
+    write_sqlite(database, BufWriter::new(File::create(&path)?))?;
+
+    let bytes = fs::read(&path)?;
+    let reader = DatabaseReader::new(&bytes)?;
+    let rowids = |index_name: &str| -> Result<Vec<i64>, Error> {
+        Ok(reader
+            .index_entries(index_name)?
+            .iter()
+            .map(|entry| entry.last().and_then(Value::as_integer).unwrap())
+            .collect())
+    };
+    assert_eq!(rowids("sqlite_autoindex_foo_1")?, [1, 2, 3]);
+    assert_eq!(rowids("foo_code")?, [2, 1, 3]);
+    assert_eq!(rowids("foo_name")?, [2, 1, 3]);
+
+    // equal without regard to case
+    let mut builder = DatabaseBuilder::new();
+    builder.schema("foo", "create table foo(name text collate nocase unique)")?;
+    for (i, name) in ["abc", "ABC"].into_iter().enumerate() {
+        let mut record = Record::new(i as i64 + 1);
+        record.add_value(values::string(name));
+        builder.add_record(record)?;
+    }
+    let result = builder.build();
+    assert!(
+        matches!(result, Err(Error::DuplicateKey { index }) if index == "sqlite_autoindex_foo_1")
+    );
+
+    let mut builder = DatabaseBuilder::new();
+    builder.schema("foo", "create table foo(name text collate unicode)")?;
+    assert!(matches!(
+        builder.index("create index foo_name on foo(name)"),
+        Err(Error::InvalidSchema(_))
+    ));
+    Ok(())
+}
+
+#[test]
+fn test_large_schema() -> Result<(), Error> {
+    for encoding in [TextEncoding::Utf8, TextEncoding::Utf16le] {
+        let mut builder = DatabaseBuilder::new();
+        builder.encoding(encoding)?;
+        builder.page_size(512)?;
+        // a statement that needs overflow pages
+        let columns: Vec<String> = (0..200)
+            .map(|i| format!("a_rather_long_column_name_{i} text"))
+            .collect();
+        let columns = columns.join(", ");
+        builder.schema("wide", &format!("create table wide({columns})"))?;
+        for table in 0..100 {
+            let name = format!("table_{table}");
+            builder.schema(
+                &name,
+                &format!("create table {name}(id integer, name text)"),
+            )?;
+            builder.index(&format!("create index {name}_name on {name}(name)"))?;
+            let mut record = Record::new(1);
+            record.add_value(values::integer(table));
+            record.add_value(values::string(&name));
+            builder.add_record(record)?;
+        }
+        let bytes = builder.build()?.to_bytes()?;
+        assert!(validate(&bytes).is_empty());
+
+        let reader = DatabaseReader::new(&bytes)?;
+        let schema = reader.schema()?;
+        assert_eq!(schema.len(), 201);
+        assert_eq!(schema[0].sql, Some(format!("create table wide({columns})")));
+        for table in 0..100 {
+            let name = format!("table_{table}");
+            assert_eq!(schema[1 + table * 2].name, name);
+            assert_eq!(schema[2 + table * 2].name, format!("{name}_name"));
+            let records = reader.records(&name)?;
+            assert_eq!(records[0].values()[1], values::string(&name));
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_schema_just_too_large_for_the_first_page() -> Result<(), Error> {
+    // fits on a leaf page, but not on the first page, after the file header
+    let mut builder = DatabaseBuilder::new();
+    builder.page_size(512)?;
+    let columns: Vec<String> = (0..25).map(|i| format!("column_{i} text")).collect();
+    let columns = columns.join(", ");
+    builder.schema("foo", &format!("create table foo({columns})"))?;
+    builder.add_record(Record::new(1))?;
+    let bytes = builder.build()?.to_bytes()?;
+    assert!(validate(&bytes).is_empty());
+
+    let reader = DatabaseReader::new(&bytes)?;
+    assert_eq!(reader.schema()?[0].name, "foo");
+    assert_eq!(reader.records("foo")?.len(), 1);
+    Ok(())
+}
+
+#[test]
+fn test_build_utf16() -> Result<(), Error> {
+    let mut builder = DatabaseBuilder::new();
+    builder.encoding(TextEncoding::Utf16le)?;
+    builder.schema("foo", "create table foo(bar text)")?;
+    builder.index("create index foo_bar on foo(bar)")?;
+    for (i, name) in ["Łódź", "Αθήνα", "東京"].iter().enumerate() {
+        let mut record = Record::new(i as i64 + 1);
+        record.add_value(values::string(name));
+        builder.add_record(record)?;
+    }
+    let database = builder.build()?;
+    let path = std::env::temp_dir().join("foo_utf16.db");
+    let writer = BufWriter::new(File::create(&path)?);
+    write_sqlite(database, writer)?;
+
+    let bytes = fs::read(&path)?;
+    assert_eq!(bytes[56..60], [0, 0, 0, 2]); // UTF-16le
+    let tokyo: Vec<u8> = "東京".encode_utf16().flat_map(u16::to_le_bytes).collect();
+    assert!(bytes.windows(tokyo.len()).any(|w| w == tokyo));
+    Ok(())
+}
+
+#[test]
+fn test_page_size() -> Result<(), Error> {
+    for page_size in [512, 65536] {
+        let mut builder = DatabaseBuilder::new();
+        builder.page_size(page_size)?;
+        builder.schema("foo", "create table foo(bar text)")?;
+        builder.index("create index foo_bar on foo(bar)")?;
+        for i in 0..1000 {
+            let mut record = Record::new(i);
+            record.add_value(values::string(&"helloworld".repeat(i as usize % 100)));
+            builder.add_record(record)?;
+        }
+        builder.schema("empty", "create table empty(id integer)")?;
+        let database = builder.build()?;
+        let path = std::env::temp_dir().join(format!("foo_{page_size}.db"));
+        write_sqlite(database, BufWriter::new(File::create(&path)?))?;
+
+        let bytes = fs::read(&path)?;
+        let header_value = if page_size == 65536 {
+            1
+        } else {
+            page_size as u16
+        };
+        assert_eq!(bytes[16..18], header_value.to_be_bytes());
+        let n_pages = u32::from_be_bytes(bytes[28..32].try_into().unwrap());
+        assert_eq!(bytes.len(), n_pages as usize * page_size as usize);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_invalid_page_size() {
+    let mut builder = DatabaseBuilder::new();
+    for page_size in [0, 256, 1000, 131072] {
+        assert!(matches!(
+            builder.page_size(page_size),
+            Err(Error::InvalidPageSize(_))
+        ));
+    }
+}
+
+#[test]
+fn test_to_bytes() -> Result<(), Error> {
+    let mut builder = DatabaseBuilder::new();
+    builder.schema("foo", "create table foo(bar text)")?;
+    builder.index("create index foo_bar on foo(bar)")?;
+    for i in 0..1000 {
+        let mut record = Record::new(i);
+        // long values need overflow pages
+        record.add_value(values::string(&"helloworld".repeat(i as usize)));
+        builder.add_record(record)?;
+    }
+    let database = builder.build()?;
+    let bytes = database.to_bytes()?;
+    assert_eq!(bytes.len() as u64, database.size());
+    assert_eq!(&bytes[0..16], MAGIC_HEADER);
+
+    let path = std::env::temp_dir().join("foo_to_bytes.db");
+    write_sqlite(database, File::create(&path)?)?;
+    assert_eq!(fs::read(&path)?, bytes);
+    Ok(())
+}
+
+#[cfg(feature = "bytes")]
+#[test]
+fn test_into_shared_bytes() -> Result<(), Error> {
+    let mut builder = DatabaseBuilder::new();
+    builder.schema("foo", "create table foo(bar text)")?;
+    let database = builder.build()?;
+    let bytes = database.to_bytes_bytes()?;
+    assert_eq!(bytes.len() as u64, database.size());
+    assert_eq!(bytes::Bytes::try_from(&database)?, bytes);
+    Ok(())
+}
+
+#[test]
+fn test_auto_rowid() -> Result<(), Error> {
+    let mut builder = DatabaseBuilder::new();
+    builder.auto_rowid(true);
+    for table_name in ["foo", "bar"] {
+        builder.schema(table_name, &format!("create table {table_name}(baz text)"))?;
+        for _ in 0..1000 {
+            let mut record = Record::new(0);
+            record.add_value(values::string("helloworld"));
+            builder.add_record(record)?;
+        }
+    }
+    // explicit rowids are checked again once automatic numbering is off
+    builder.auto_rowid(false);
+    assert!(matches!(
+        builder.add_record(Record::new(1000)),
+        Err(Error::NonMonotonicRowid { previous: 1000, .. })
+    ));
+    builder.add_record(Record::new(1001))?;
+
+    let bytes = builder.build()?.to_bytes()?;
+    assert!(validate(&bytes).is_empty());
+    let reader = DatabaseReader::new(&bytes)?;
+    let rowids = |table_name| -> Result<Vec<i64>, Error> {
+        Ok(reader
+            .records(table_name)?
+            .iter()
+            .map(|record| record.rowid)
+            .collect())
+    };
+    assert_eq!(rowids("foo")?, (1..=1000).collect::<Vec<_>>());
+    assert_eq!(rowids("bar")?, (1..=1001).collect::<Vec<_>>());
+    Ok(())
+}
+
+#[test]
+fn test_negative_rowids() -> Result<(), Error> {
+    let mut builder = DatabaseBuilder::new();
+    builder.schema("foo", "create table foo(bar integer)")?;
+    builder.index("create index foo_bar on foo(bar)")?;
+    let rowids: Vec<i64> = [i64::MIN]
+        .into_iter()
+        .chain(-10_000..10_000)
+        .chain([i64::MAX])
+        .collect();
+    for &rowid in &rowids {
+        let mut record = Record::new(rowid);
+        record.add_value(values::integer(rowid));
+        builder.add_record(record)?;
+    }
+    // there is no rowid after the largest one
+    builder.auto_rowid(true);
+    assert!(matches!(
+        builder.add_record(Record::new(0)),
+        Err(Error::NonMonotonicRowid {
+            previous: i64::MAX,
+            ..
+        })
+    ));
+
+    let bytes = builder.build()?.to_bytes()?;
+    assert!(validate(&bytes).is_empty());
+    let records = DatabaseReader::new(&bytes)?.records("foo")?;
+    let stored: Vec<i64> = records.iter().map(|record| record.rowid).collect();
+    assert_eq!(stored, rowids);
+    for record in &records {
+        assert_eq!(record.values(), [values::integer(record.rowid)]);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_rowid_alias() -> Result<(), Error> {
+    let mut builder = DatabaseBuilder::new();
+    builder.schema("foo", "create table foo(id integer primary key, bar text)")?;
+    builder.index("create index foo_id_bar on foo(id, bar)")?;
+    for id in 1..=1000 {
+        // the rowid comes from the id column
+        let mut record = Record::new(0);
+        record.add_value(values::integer(id * 2));
+        record.add_value(values::string("helloworld"));
+        builder.add_record(record)?;
+    }
+    // a NULL id keeps the rowid of the record
+    let mut record = Record::new(3000);
+    record.add_value(values::null());
+    builder.add_record(record)?;
+    let mut record = Record::new(5000);
+    record.add_value(values::integer(2000));
+    assert!(matches!(
+        builder.add_record(record),
+        Err(Error::NonMonotonicRowid {
+            previous: 3000,
+            rowid: 2000,
+            ..
+        })
+    ));
+
+    let mut record = Record::new(4000);
+    record.add_value(values::string("4001"));
+    assert!(matches!(builder.add_record(record), Err(Error::Input(_))));
+
+    let bytes = builder.build()?.to_bytes()?;
+    assert!(validate(&bytes).is_empty());
+    let reader = DatabaseReader::new(&bytes)?;
+    let records = reader.records("foo")?;
+    assert_eq!(records.len(), 1001);
+    for (id, record) in (1..=1000).zip(&records) {
+        // the alias is stored as NULL, because the value is the rowid
+        assert_eq!(record.rowid, id * 2);
+        assert_eq!(
+            record.values(),
+            [values::null(), values::string("helloworld")]
+        );
+    }
+    assert_eq!(records[1000].rowid, 3000);
+    assert_eq!(records[1000].values(), [values::null()]);
+
+    // indexes hold the rowid as the value of the alias
+    let entries = reader.index_entries("foo_id_bar")?;
+    assert_eq!(entries.len(), 1001);
+    for (id, entry) in (1..=1000).zip(&entries) {
+        assert_eq!(
+            entry,
+            &[
+                values::integer(id * 2),
+                values::string("helloworld"),
+                values::integer(id * 2)
+            ]
+        );
+    }
+    assert_eq!(
+        entries[1000],
+        [values::integer(3000), values::null(), values::integer(3000)]
+    );
+    Ok(())
+}
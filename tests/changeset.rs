@@ -0,0 +1,126 @@
+use sqlighters::{create_changeset, values, Error, Record, Value};
+
+fn record(rowid: i64, values: Vec<Value>) -> Record {
+    let mut record = Record::new(rowid);
+    for value in values {
+        record.add_value(value);
+    }
+    record
+}
+
+fn bytes(hex: &str) -> Vec<u8> {
+    hex.split_whitespace()
+        .map(|byte| u8::from_str_radix(byte, 16).unwrap())
+        .collect()
+}
+
+/// The changesets are the ones that the session extension of `SQLite` 3.40 records for the same changes
+#[test]
+fn test_changeset() -> Result<(), Error> {
+    let sql = "create table t(a integer primary key, b text, c)";
+    let old = [
+        record(
+            1,
+            vec![values::null(), values::string("one"), values::float(1.5)],
+        ),
+        record(
+            2,
+            vec![
+                values::null(),
+                values::string("two"),
+                values::blob(vec![1, 2]),
+            ],
+        ),
+    ];
+    let new = [
+        record(
+            1,
+            vec![values::null(), values::string("uno"), values::float(1.5)],
+        ),
+        record(3, vec![values::integer(3), values::string("three")]),
+    ];
+    assert_eq!(
+        create_changeset("t", sql, &old, &new)?,
+        bytes(
+            "54 03 01 00 00 74 00
+            17 00 01 00 00 00 00 00 00 00 01 03 03 6f 6e 65 00 00 03 03 75 6e 6f 00
+            09 00 01 00 00 00 00 00 00 00 02 03 03 74 77 6f 04 02 01 02
+            12 00 01 00 00 00 00 00 00 00 03 03 05 74 68 72 65 65 05"
+        )
+    );
+
+    // the key is q, then p
+    let sql = "create table k(p, q, r, primary key(q, p))";
+    let integers = |integers: [i64; 3]| integers.into_iter().map(values::integer).collect();
+    let old = [record(1, integers([1, 2, 3]))];
+    let new = [
+        record(1, integers([1, 2, 9])),
+        record(2, integers([4, 5, 6])),
+    ];
+    assert_eq!(
+        create_changeset("k", sql, &old, &new)?,
+        bytes(
+            "54 03 02 01 00 6b 00
+            17 00 01 00 00 00 00 00 00 00 01 01 00 00 00 00 00 00 00 02 01 00 00 00 00 00 00 00 03
+            00 00 01 00 00 00 00 00 00 00 09
+            12 00 01 00 00 00 00 00 00 00 04 01 00 00 00 00 00 00 00 05 01 00 00 00 00 00 00 00 06"
+        )
+    );
+    Ok(())
+}
+
+#[test]
+fn test_changeset_without_changes() -> Result<(), Error> {
+    let sql = "create table foo(name text primary key, value)";
+    let records = [
+        record(1, vec![values::string("b"), values::integer(1)]),
+        record(2, vec![values::string("a")]),
+        // left out, like the session extension does
+        record(3, vec![values::null(), values::integer(3)]),
+    ];
+    let mut reordered = [
+        record(5, vec![values::string("a"), values::null()]),
+        record(6, vec![values::string("b"), values::integer(1)]),
+    ];
+    let header = bytes("54 02 01 00 66 6f 6f 00");
+    assert_eq!(create_changeset("foo", sql, &records, &reordered)?, header);
+
+    reordered[1] = record(6, vec![values::string("b"), values::float(1.0)]);
+    let changeset = create_changeset("foo", sql, &records, &reordered)?;
+    assert_eq!(changeset[header.len()], 23);
+    Ok(())
+}
+
+#[test]
+fn test_changeset_mistakes() {
+    let records = [
+        record(1, vec![values::integer(1)]),
+        record(2, vec![values::integer(1)]),
+    ];
+    assert!(matches!(
+        create_changeset("foo", "create table foo(bar primary key)", &records, &[]),
+        Err(Error::Input(_))
+    ));
+    assert!(matches!(
+        create_changeset("foo", "create table foo(bar)", &[], &[]),
+        Err(Error::InvalidSchema(_))
+    ));
+    assert!(matches!(
+        create_changeset(
+            "foo",
+            "create table foo(id integer primary key)",
+            &[record(1, vec![values::string("one")])],
+            &[]
+        ),
+        Err(Error::Input(_))
+    ));
+    assert!(matches!(
+        create_changeset(
+            "foo",
+            "create table foo(bar primary key)",
+            &[record(1, vec![values::integer(1), values::integer(2)])],
+            &[]
+        ),
+        Err(Error::Input(_))
+    ));
+}
@@ -0,0 +1,82 @@
+#![cfg(feature = "csv")]
+
+use sqlighters::{
+    validate, values, write_sqlite, ColumnType, CsvOptions, DatabaseBuilder, DatabaseReader, Error,
+    Inference, StreamingDatabaseBuilder,
+};
+use std::fs::{self, File};
+use std::io::{BufWriter, Cursor};
+
+#[test]
+fn test_add_csv() -> Result<(), Error> {
+    let mut csv = String::from("id,name,score,zip\n");
+    for i in 0..10_000 {
+        csv.push_str(&format!(
+            "{i},\"person, {i}\",{}.5,0{}\n",
+            i % 100,
+            i % 9000
+        ));
+    }
+    csv.push_str("10000,last,,1234\n");
+
+    let mut builder = DatabaseBuilder::new();
+    builder.add_csv("people", csv.as_bytes(), &CsvOptions::new())?;
+    let database = builder.build()?;
+    let path = std::env::temp_dir().join("csv.db");
+    write_sqlite(database, BufWriter::new(File::create(&path)?))?;
+
+    let bytes = fs::read(&path)?;
+    let sql = br#"CREATE TABLE "people"("id" INTEGER, "name" TEXT, "score" REAL, "zip" TEXT)"#;
+    assert!(bytes.windows(sql.len()).any(|w| w == sql));
+    Ok(())
+}
+
+#[test]
+fn test_add_csv_with_options() -> Result<(), Error> {
+    let csv = "1;'a;b';3\n2;c\n3;'d';x\n";
+    let mut options = CsvOptions::new();
+    options
+        .has_headers(false)
+        .delimiter(b';')
+        .quote(b'\'')
+        .inference(Inference::FullPass)
+        .column_type("column1", ColumnType::Text);
+
+    let mut builder = DatabaseBuilder::new();
+    builder.add_csv("foo", csv.as_bytes(), &options)?;
+    let database = builder.build()?;
+    let path = std::env::temp_dir().join("csv_options.db");
+    write_sqlite(database, BufWriter::new(File::create(&path)?))?;
+
+    let bytes = fs::read(&path)?;
+    let sql = br#"CREATE TABLE "foo"("column1" TEXT, "column2" TEXT, "column3" TEXT)"#;
+    assert!(bytes.windows(sql.len()).any(|w| w == sql));
+    Ok(())
+}
+
+#[test]
+fn test_invalid_csv() {
+    let mut builder = DatabaseBuilder::new();
+    let result = builder.add_csv("foo", &[b'a', b'\n', 0xff, b'\n'][..], &CsvOptions::new());
+    assert!(matches!(result, Err(Error::Input(_))));
+}
+
+#[test]
+fn test_streaming_add_csv() -> Result<(), Error> {
+    let csv = "id,name\n1,foo\n2,bar\n";
+    let mut builder = StreamingDatabaseBuilder::new(Cursor::new(Vec::new()));
+    builder.add_csv("first", csv.as_bytes(), &CsvOptions::new())?;
+    builder.add_csv("second", csv.as_bytes(), &CsvOptions::new())?;
+    let bytes = builder.finish()?.into_inner();
+    assert!(validate(&bytes).is_empty());
+
+    let reader = DatabaseReader::new(&bytes)?;
+    for table_name in ["first", "second"] {
+        let records = reader.records(table_name)?;
+        assert_eq!(
+            records[1].values(),
+            [values::integer(2), values::string("bar")]
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,141 @@
+#![cfg(feature = "derive")]
+
+use sqlighters::{
+    validate, values, write_sqlite, DatabaseBuilder, DatabaseReader, Error, SqliteRow,
+};
+use std::fs::{self, File};
+use std::io::BufWriter;
+
+#[derive(SqliteRow)]
+#[sqlite(table = "people")]
+struct Person {
+    #[sqlite(primary_key)]
+    id: i64,
+    #[sqlite(rename = "full_name")]
+    name: String,
+    #[sqlite(type = "VARCHAR(2)")]
+    country: Option<String>,
+    photo: Option<Vec<u8>>,
+}
+
+#[derive(SqliteRow)]
+struct OrderLine<'a> {
+    #[sqlite(primary_key)]
+    order_id: i32,
+    #[sqlite(primary_key)]
+    line: u16,
+    product: &'a str,
+    price: f64,
+}
+
+#[derive(SqliteRow)]
+struct Counter {
+    #[sqlite(primary_key, type = "INTEGER")]
+    id: Option<u64>,
+    count: Option<u32>,
+}
+
+#[test]
+fn test_create_table() {
+    assert_eq!(
+        Person::create_table(),
+        "CREATE TABLE \"people\"(\"id\" INTEGER PRIMARY KEY NOT NULL, \"full_name\" TEXT NOT NULL, \"country\" VARCHAR(2), \"photo\" BLOB)"
+    );
+    assert_eq!(OrderLine::table_name(), "order_line");
+    assert_eq!(
+        OrderLine::create_table(),
+        "CREATE TABLE \"order_line\"(\"order_id\" INTEGER NOT NULL, \"line\" INTEGER NOT NULL, \"product\" TEXT NOT NULL, \"price\" REAL NOT NULL, PRIMARY KEY(\"order_id\", \"line\"))"
+    );
+}
+
+#[test]
+fn test_into_record() -> Result<(), Error> {
+    let person = Person {
+        id: 42,
+        name: "Ford Prefect".to_owned(),
+        country: None,
+        photo: None,
+    };
+    // the integer primary key is the rowid
+    assert_eq!(person.into_record(1)?.rowid, 42);
+
+    let line = OrderLine {
+        order_id: 1,
+        line: 2,
+        product: "towel",
+        price: 9.99,
+    };
+    assert_eq!(line.into_record(7)?.rowid, 7);
+
+    // too large for a rowid
+    let counter = Counter {
+        id: Some(u64::MAX),
+        count: None,
+    };
+    assert!(matches!(counter.into_record(1), Err(Error::Serialize(_))));
+    let counter = Counter {
+        id: None,
+        count: Some(1),
+    };
+    assert_eq!(counter.into_record(3)?.rowid, 3);
+    Ok(())
+}
+
+#[test]
+fn test_build_rows() -> Result<(), Error> {
+    let mut builder = DatabaseBuilder::new();
+    builder.schema_for::<Person>()?;
+    for id in 1..=1000 {
+        builder.add_row(Person {
+            id: id * 2,
+            name: format!("person{id}"),
+            country: (id % 2 == 0).then(|| "NL".to_owned()),
+            photo: None,
+        })?;
+    }
+    builder.schema_for::<OrderLine>()?;
+    for line in 1..=1000 {
+        builder.add_row(OrderLine {
+            order_id: line as i32 / 10,
+            line: line % 10,
+            product: "towel",
+            price: 9.99,
+        })?;
+    }
+    let database = builder.build()?;
+    let path = std::env::temp_dir().join("derive.db");
+    write_sqlite(database, BufWriter::new(File::create(&path)?))?;
+
+    let bytes = fs::read(&path)?;
+    assert_eq!(bytes[103..105], [0, 3]); // 2 tables and the index for the primary key of order_line
+    assert!(validate(&bytes).is_empty());
+
+    let reader = DatabaseReader::new(&bytes)?;
+    let people = reader.records("people")?;
+    assert_eq!(people.len(), 1000);
+    for (id, person) in (1..=1000).zip(&people) {
+        // the id is the rowid, and stored as NULL
+        assert_eq!(person.rowid, id * 2);
+        let country = if id % 2 == 0 {
+            values::string("NL")
+        } else {
+            values::null()
+        };
+        assert_eq!(
+            person.values(),
+            [
+                values::null(),
+                values::string(&format!("person{id}")),
+                country,
+                values::null()
+            ]
+        );
+    }
+    let entries = reader.index_entries("sqlite_autoindex_order_line_1")?;
+    assert_eq!(entries.len(), 1000);
+    assert_eq!(
+        entries[0],
+        [values::integer(0), values::integer(1), values::integer(1)]
+    );
+    Ok(())
+}
@@ -0,0 +1,81 @@
+#![cfg(feature = "json")]
+
+use sqlighters::{
+    validate, values, write_sqlite, DatabaseBuilder, DatabaseReader, Error,
+    StreamingDatabaseBuilder,
+};
+use std::fs::{self, File};
+use std::io::{BufWriter, Cursor};
+
+fn write(builder: DatabaseBuilder, name: &str) -> Result<Vec<u8>, Error> {
+    let database = builder.build()?;
+    let path = std::env::temp_dir().join(name);
+    write_sqlite(database, BufWriter::new(File::create(&path)?))?;
+    Ok(fs::read(&path)?)
+}
+
+#[test]
+fn test_add_json_array() -> Result<(), Error> {
+    let json = r#"[
+        {"id": 1, "name": "foo", "address": {"city": "Amsterdam"}},
+        {"id": 2, "score": 1.5, "tags": ["a", "b"]},
+        {"id": 3, "name": null, "active": true}
+    ]"#;
+    let mut builder = DatabaseBuilder::new();
+    builder.add_json("people", json.as_bytes())?;
+
+    let bytes = write(builder, "json.db")?;
+    let sql = br#"CREATE TABLE "people"("id" integer, "name" text, "address" text, "score" real, "tags" text, "active" integer)"#;
+    assert!(bytes.windows(sql.len()).any(|w| w == sql));
+    assert!(bytes.windows(20).any(|w| w == br#"{"city":"Amsterdam"}"#));
+    Ok(())
+}
+
+#[test]
+fn test_add_ndjson() -> Result<(), Error> {
+    let mut ndjson = String::new();
+    for i in 0..10_000 {
+        ndjson.push_str(&format!("{{\"id\": {i}, \"name\": \"person {i}\"}}\n"));
+    }
+    ndjson.push_str("{\"id\": 10000, \"last\": true}\n");
+    let mut builder = DatabaseBuilder::new();
+    builder.add_json("people", ndjson.as_bytes())?;
+
+    let bytes = write(builder, "ndjson.db")?;
+    let sql = br#"CREATE TABLE "people"("id" integer, "name" text, "last" integer)"#;
+    assert!(bytes.windows(sql.len()).any(|w| w == sql));
+    Ok(())
+}
+
+#[test]
+fn test_invalid_json() {
+    let mut builder = DatabaseBuilder::new();
+    let result = builder.add_json("foo", &b"{\"id\": 1}\n{\"id\": "[..]);
+    assert!(matches!(result, Err(Error::Input(_))));
+
+    let mut builder = DatabaseBuilder::new();
+    let result = builder.add_json("foo", &b"[{}, {}]"[..]);
+    assert!(matches!(result, Err(Error::InvalidSchema(_))));
+}
+
+#[test]
+fn test_streaming_add_json() -> Result<(), Error> {
+    let ndjson = "{\"id\": 1}\n{\"id\": 2, \"name\": \"foo\"}\n";
+    let mut builder = StreamingDatabaseBuilder::new(Cursor::new(Vec::new()));
+    builder.add_json("people", ndjson.as_bytes())?;
+    let bytes = builder.finish()?.into_inner();
+    assert!(validate(&bytes).is_empty());
+
+    let reader = DatabaseReader::new(&bytes)?;
+    assert_eq!(
+        reader.schema()?[0].sql.as_deref(),
+        Some(r#"CREATE TABLE "people"("id" integer, "name" text)"#)
+    );
+    let records = reader.records("people")?;
+    assert_eq!(records[0].values(), [values::integer(1)]);
+    assert_eq!(
+        records[1].values(),
+        [values::integer(2), values::string("foo")]
+    );
+    Ok(())
+}
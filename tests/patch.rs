@@ -0,0 +1,137 @@
+use sqlighters::{
+    apply_patch, create_patch, values, DatabaseAppender, DatabaseBuilder, Error, Patch, Record,
+};
+
+const SQLITE_FILE: &[u8] = include_bytes!("data/sqlite.db");
+
+/// A daily export: the prices of 2000 products, with one price that differs per day
+fn export(day: i64) -> Result<Vec<u8>, Error> {
+    let mut builder = DatabaseBuilder::new();
+    builder.page_size(1024)?;
+    builder.schema("products", "create table products(name text, price real)")?;
+    for rowid in 1..=2000 {
+        let mut record = Record::new(rowid);
+        record.add_value(values::string(&format!("product {rowid}")));
+        let price = if rowid == 1000 { day } else { rowid };
+        record.add_value(values::float(price as f64 / 100.0));
+        builder.add_record(record)?;
+    }
+    let database = builder.build()?;
+    database.to_bytes()
+}
+
+#[test]
+fn test_patch() -> Result<(), Error> {
+    let (old, new) = (export(1)?, export(2)?);
+    let patch = create_patch(&old, &new)?;
+    assert_eq!(patch.page_numbers().len(), 1);
+    assert!(patch.to_bytes().len() < 1300);
+    assert_eq!(apply_patch(&old, &patch)?, new);
+
+    // nothing but the header for the same file
+    let patch = create_patch(&new, &new)?;
+    assert!(patch.page_numbers().is_empty());
+    assert_eq!(apply_patch(&new, &patch)?, new);
+
+    // everything for a new client
+    let patch = create_patch(&[], &new)?;
+    assert_eq!(patch.page_count() as usize, new.len() / 1024);
+    assert_eq!(patch.page_numbers().len(), new.len() / 1024);
+    assert_eq!(apply_patch(&[], &patch)?, new);
+
+    // a smaller file, with another page size
+    let patch = create_patch(&new, SQLITE_FILE)?;
+    assert_eq!(apply_patch(&new, &patch)?, SQLITE_FILE);
+    Ok(())
+}
+
+#[test]
+fn test_patch_appended_records() -> Result<(), Error> {
+    let old = export(1)?;
+    let mut appender = DatabaseAppender::new(&old)?;
+    appender.table("products")?;
+    for rowid in 2001..=2100 {
+        let mut record = Record::new(rowid);
+        record.add_value(values::string("new product"));
+        appender.add_record(record)?;
+    }
+    let new = appender.into_bytes()?;
+    let patch = create_patch(&old, &new)?;
+    assert!(patch.page_numbers().len() < 10);
+    assert_eq!(apply_patch(&old, &patch)?, new);
+    Ok(())
+}
+
+#[test]
+fn test_patch_bytes() -> Result<(), Error> {
+    let (old, new) = (export(1)?, export(2)?);
+    let patch = create_patch(&old, &new)?;
+    let bytes = patch.to_bytes();
+    assert_eq!(Patch::from_bytes(&bytes)?, patch);
+
+    assert!(matches!(
+        Patch::from_bytes(&bytes[..bytes.len() - 1]),
+        Err(Error::Input(_))
+    ));
+    assert!(matches!(Patch::from_bytes(&new), Err(Error::Input(_))));
+    let mut out_of_range = bytes.clone();
+    let last_page = out_of_range.len() - 1024 - 4;
+    out_of_range[last_page..last_page + 4].copy_from_slice(&u32::MAX.to_be_bytes());
+    assert!(matches!(
+        Patch::from_bytes(&out_of_range),
+        Err(Error::Input(_))
+    ));
+    Ok(())
+}
+
+#[test]
+fn test_truncated_new_file() {
+    assert!(matches!(
+        create_patch(&[], &SQLITE_FILE[..200]),
+        Err(Error::Input(_))
+    ));
+    // without a valid page count in the header, the pages are counted from the length
+    let mut new = SQLITE_FILE[..200].to_vec();
+    new[92] ^= 0xff;
+    assert!(matches!(
+        create_patch(&[], &new),
+        Err(Error::Corrupt { page: 1, .. })
+    ));
+}
+
+#[test]
+fn test_patch_header() -> Result<(), Error> {
+    let new = export(2)?;
+    let bytes = create_patch(&new, &new)?.to_bytes();
+    let with = |position: usize, value: u32| {
+        let mut bytes = bytes.clone();
+        bytes[position..position + 4].copy_from_slice(&value.to_be_bytes());
+        Patch::from_bytes(&bytes)
+    };
+
+    // page sizes are powers of two from 512 to 65536, like in a database file
+    for page_size in [100, 1000, 131072] {
+        assert!(matches!(with(32, page_size), Err(Error::Input(_))));
+    }
+    assert!(matches!(with(36, u32::MAX), Err(Error::Input(_))));
+
+    // the new file can't be larger than the old one and the pages in the patch
+    let too_many_pages = with(36, u32::MAX - 1)?;
+    assert!(matches!(
+        apply_patch(&new, &too_many_pages),
+        Err(Error::Input(_))
+    ));
+    Ok(())
+}
+
+#[test]
+fn test_patch_for_another_file() -> Result<(), Error> {
+    let (old, new) = (export(1)?, export(2)?);
+    let patch = create_patch(&old, &new)?;
+    assert!(matches!(apply_patch(&new, &patch), Err(Error::Input(_))));
+    assert!(matches!(
+        apply_patch(&export(3)?, &patch),
+        Err(Error::Input(_))
+    ));
+    Ok(())
+}
@@ -0,0 +1,205 @@
+use sqlighters::{
+    values, DatabaseBuilder, DatabaseReader, Error, Record, SchemaEntry, TextEncoding, Value,
+};
+
+/// Made by SQLite with 512 byte pages: 200 people with ids 3, 6, 9..., minus the ones with an id
+/// that is a multiple of 11. Every 50th person has a photo that overflows.
+const SQLITE_FILE: &[u8] = include_bytes!("data/sqlite.db");
+
+#[test]
+fn test_read_sqlite_file() -> Result<(), Error> {
+    let reader = DatabaseReader::new(SQLITE_FILE)?;
+    assert_eq!(reader.page_size(), 512);
+    assert_eq!(reader.encoding(), TextEncoding::Utf8);
+
+    let schema = reader.schema()?;
+    let names: Vec<&str> = schema.iter().map(|entry| entry.name.as_str()).collect();
+    assert_eq!(
+        names,
+        ["people", "people_name", "notes", "sqlite_autoindex_notes_1"]
+    );
+    assert_eq!(schema[3].sql, None);
+
+    let people = reader.records("people")?;
+    assert_eq!(people.len(), 182);
+    assert!(people.windows(2).all(|w| w[0].rowid < w[1].rowid));
+    let person = people.iter().find(|person| person.rowid == 150).unwrap();
+    // the INTEGER PRIMARY KEY is stored as NULL
+    assert!(person.values()[0].is_null());
+    assert_eq!(person.values()[1].as_text(), Some("person 50"));
+    assert_eq!(person.values()[2].as_integer(), Some(50));
+    assert_eq!(person.values()[3].as_float(), Some(12.5));
+    assert_eq!(person.values()[4].as_blob(), Some(&[0; 1500][..]));
+
+    let names = reader.index_entries("people_name")?;
+    assert_eq!(names.len(), 182);
+    assert!(names
+        .windows(2)
+        .all(|w| w[0][0].as_text() < w[1][0].as_text()));
+    assert_eq!(names[0][1].as_integer(), Some(3));
+
+    let notes = reader.records("notes")?;
+    assert_eq!(notes[1].values()[0].as_text().map(str::len), Some(2000));
+    assert_eq!(reader.index_entries("sqlite_autoindex_notes_1")?.len(), 2);
+    Ok(())
+}
+
+/// xorshift, so that the tests are reproducible
+struct Random(u64);
+
+impl Random {
+    fn next(&mut self, bound: u64) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0 % bound
+    }
+
+    fn value(&mut self) -> Value {
+        match self.next(6) {
+            0 => values::null(),
+            1 => values::integer(self.next(u64::MAX) as i64 >> self.next(64)),
+            2 => values::float(self.next(1_000_000) as f64 / 7.0),
+            3 => values::string(&"ä€x".repeat(self.next(20) as usize)),
+            4 => values::string(&"y".repeat(self.next(10_000) as usize)),
+            _ => values::blob(vec![self.next(256) as u8; self.next(3_000) as usize]),
+        }
+    }
+}
+
+#[test]
+fn test_round_trip() -> Result<(), Error> {
+    let mut random = Random(0x2545_f491_4f6c_dd1d);
+    for (page_size, encoding) in [
+        (512, TextEncoding::Utf8),
+        (1024, TextEncoding::Utf16le),
+        (4096, TextEncoding::Utf16be),
+        (65536, TextEncoding::Utf8),
+    ] {
+        let mut builder = DatabaseBuilder::new();
+        builder.page_size(page_size)?;
+        builder.encoding(encoding)?;
+        let mut tables = Vec::new();
+        for table in 0..3 {
+            let table_name = format!("t{table}");
+            builder.schema(&table_name, &format!("create table {table_name}(a, b, c)"))?;
+            builder.index(&format!("create index {table_name}_b on {table_name}(b)"))?;
+            let mut rows = Vec::new();
+            let mut rowid = -(random.next(1000) as i64);
+            for _ in 0..random.next(1_000) {
+                rowid += 1 + random.next(100) as i64;
+                let values: Vec<Value> = (0..1 + random.next(3)).map(|_| random.value()).collect();
+                let mut record = Record::new(rowid);
+                for value in &values {
+                    record.add_value(value.clone());
+                }
+                builder.add_record(record)?;
+                rows.push((rowid, values));
+            }
+            tables.push((table_name, rows));
+        }
+        let database = builder.build()?;
+        let bytes = database.to_bytes()?;
+
+        let reader = DatabaseReader::new(&bytes)?;
+        assert_eq!(reader.page_size(), page_size as usize);
+        assert_eq!(reader.encoding(), encoding);
+        assert_eq!(
+            u64::from(reader.page_count()),
+            database.size() / page_size as u64
+        );
+        for (table_name, rows) in tables {
+            let records = reader.records(&table_name)?;
+            assert_eq!(records.len(), rows.len());
+            for (record, (rowid, values)) in records.iter().zip(&rows) {
+                assert_eq!(record.rowid, *rowid);
+                assert_eq!(record.values(), &values[..]);
+            }
+            // missing columns are NULL in the index
+            let entries = reader.index_entries(&format!("{table_name}_b"))?;
+            assert_eq!(entries.len(), rows.len());
+            for entry in entries {
+                let rowid = entry[1].as_integer().unwrap();
+                let (_, values) = rows.iter().find(|(r, _)| *r == rowid).unwrap();
+                assert_eq!(
+                    entry[0],
+                    values.get(1).cloned().unwrap_or_else(values::null)
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_schema() -> Result<(), Error> {
+    let mut builder = DatabaseBuilder::new();
+    builder.schema(
+        "foo",
+        "create table foo(id integer primary key, bar text unique)",
+    )?;
+    let database = builder.build()?;
+    let bytes = database.to_bytes()?;
+    assert_eq!(
+        DatabaseReader::new(&bytes)?.schema()?,
+        [
+            SchemaEntry {
+                object_type: "table".to_owned(),
+                name: "foo".to_owned(),
+                table_name: "foo".to_owned(),
+                root_page: 2,
+                sql: Some("create table foo(id integer primary key, bar text unique)".to_owned()),
+            },
+            SchemaEntry {
+                object_type: "index".to_owned(),
+                name: "sqlite_autoindex_foo_1".to_owned(),
+                table_name: "foo".to_owned(),
+                root_page: 3,
+                sql: None,
+            }
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn test_corrupt_file() {
+    assert!(matches!(
+        DatabaseReader::new(&SQLITE_FILE[..99]),
+        Err(Error::Corrupt { page: 1, .. })
+    ));
+    // the header page count doesn't count when the version-valid-for number is off
+    let mut short = SQLITE_FILE[..200].to_vec();
+    short[92] ^= 0xff;
+    assert!(matches!(
+        DatabaseReader::new(&short),
+        Err(Error::Corrupt { page: 1, .. })
+    ));
+
+    let mut bytes = SQLITE_FILE.to_vec();
+    bytes[512] = 7; // the type of page 2
+    let reader = DatabaseReader::new(&bytes).unwrap();
+    assert!(matches!(
+        reader.records("people"),
+        Err(Error::Corrupt { page: 2, .. })
+    ));
+    assert!(matches!(
+        reader.records("nobody"),
+        Err(Error::InvalidSchema(_))
+    ));
+}
+
+#[test]
+fn test_damaged_bytes_do_not_panic() {
+    let mut bytes = SQLITE_FILE.to_vec();
+    for position in (0..bytes.len()).step_by(11) {
+        bytes[position] ^= 0xff;
+        if let Ok(reader) = DatabaseReader::new(&bytes) {
+            for table_name in ["people", "notes"] {
+                let _ = reader.records(table_name);
+            }
+            let _ = reader.index_entries("people_name");
+        }
+        bytes[position] ^= 0xff;
+    }
+}
@@ -0,0 +1,71 @@
+#![cfg(feature = "serde")]
+
+use serde::Serialize;
+use sqlighters::{write_sqlite, DatabaseBuilder, Error, StreamingDatabaseBuilder};
+use std::fs::{self, File};
+use std::io::{BufWriter, Cursor};
+
+#[derive(Serialize)]
+struct Person {
+    name: String,
+    age: Option<u8>,
+    height: f64,
+    photo: Option<Photo>,
+}
+
+/// serializes as bytes instead of a sequence
+struct Photo(Vec<u8>);
+
+impl Serialize for Photo {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(&self.0)
+    }
+}
+
+fn people() -> impl Iterator<Item = Person> {
+    (0..10_000).map(|i| Person {
+        name: format!("person{i}"),
+        age: if i % 3 == 0 {
+            None
+        } else {
+            Some((i % 100) as u8)
+        },
+        height: 1.5 + (i % 50) as f64 / 100.0,
+        photo: (i % 1000 == 1).then(|| Photo(vec![1; 5000])),
+    })
+}
+
+#[test]
+fn test_add_rows() -> Result<(), Error> {
+    let mut builder = DatabaseBuilder::new();
+    builder.add_rows("people", people())?;
+    let database = builder.build()?;
+    let path = std::env::temp_dir().join("people.db");
+    write_sqlite(database, BufWriter::new(File::create(&path)?))?;
+
+    let bytes = fs::read(&path)?;
+    let sql =
+        b"CREATE TABLE \"people\"(\"name\" text, \"age\" integer, \"height\" real, \"photo\" blob)";
+    assert!(bytes.windows(sql.len()).any(|w| w == sql));
+    Ok(())
+}
+
+#[test]
+fn test_streaming_add_rows() -> Result<(), Error> {
+    let mut builder = StreamingDatabaseBuilder::new(Cursor::new(Vec::new()));
+    builder.add_rows("people", people())?;
+    let bytes = builder.finish()?.into_inner();
+
+    let n_pages = u32::from_be_bytes(bytes[28..32].try_into().unwrap());
+    assert_eq!(bytes.len(), n_pages as usize * 4096);
+    Ok(())
+}
+
+#[test]
+fn test_add_no_rows() {
+    let mut builder = DatabaseBuilder::new();
+    assert!(matches!(
+        builder.add_rows("people", Vec::<Person>::new()),
+        Err(Error::InvalidSchema(_))
+    ));
+}
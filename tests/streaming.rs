@@ -0,0 +1,161 @@
+use sqlighters::{validate, values, DatabaseReader, Error, Record, StreamingDatabaseBuilder};
+use std::fs::File;
+use std::io::Cursor;
+
+#[test]
+fn test_streaming_build() -> Result<(), Error> {
+    let mut builder = StreamingDatabaseBuilder::new(Cursor::new(Vec::new()));
+    builder.schema("foo", "create table foo(bar text, baz blob)")?;
+    builder.index("create index foo_bar on foo(bar)")?;
+    for i in 0..100_000 {
+        let mut record = Record::new(i);
+        record.add_value(values::string(&format!("helloworld{}", i % 1000)));
+        if i % 1000 == 0 {
+            record.add_value(values::blob(vec![1; 10_000]));
+        }
+        builder.add_record(record)?;
+    }
+    builder.schema("empty", "create table empty(id integer)")?;
+    let bytes = builder.finish()?.into_inner();
+
+    assert_eq!(&bytes[0..16], b"SQLite format 3\0");
+    assert_eq!(bytes[103..105], [0, 3]); // 2 tables and an index
+    let n_pages = u32::from_be_bytes(bytes[28..32].try_into().unwrap());
+    assert_eq!(bytes.len(), n_pages as usize * 4096);
+    assert!(validate(&bytes).is_empty());
+
+    let reader = DatabaseReader::new(&bytes)?;
+    let records = reader.records("foo")?;
+    assert_eq!(records.len(), 100_000);
+    for (i, record) in (0..).zip(&records) {
+        assert_eq!(record.rowid, i);
+        let bar = format!("helloworld{}", i % 1000);
+        assert_eq!(record.values()[0].as_text(), Some(bar.as_str()));
+        if i % 1000 == 0 {
+            assert_eq!(record.values()[1].as_blob(), Some(&[1; 10_000][..]));
+        }
+    }
+    assert_eq!(reader.index_entries("foo_bar")?.len(), 100_000);
+    assert!(reader.records("empty")?.is_empty());
+    Ok(())
+}
+
+#[test]
+fn test_streaming_to_file() -> Result<(), Error> {
+    let path = std::env::temp_dir().join("foo_streaming.db");
+    let mut builder = StreamingDatabaseBuilder::new(File::create(&path)?);
+    builder.schema("foo", "create table foo(bar text)")?;
+    for i in 0..1000 {
+        let mut record = Record::new(i);
+        record.add_value(values::string("helloworld"));
+        builder.add_record(record)?;
+    }
+    let file = builder.finish()?;
+
+    assert_eq!(file.metadata()?.len(), 7 * 4096);
+    let bytes = std::fs::read(&path)?;
+    assert!(validate(&bytes).is_empty());
+    let records = DatabaseReader::new(&bytes)?.records("foo")?;
+    assert_eq!(records.len(), 1000);
+    assert!(records
+        .iter()
+        .all(|record| record.values() == [values::string("helloworld")]));
+    Ok(())
+}
+
+#[test]
+fn test_streaming_page_size() -> Result<(), Error> {
+    let mut builder = StreamingDatabaseBuilder::new(Cursor::new(Vec::new()));
+    builder.page_size(1024)?;
+    builder.schema("foo", "create table foo(bar text)")?;
+    for i in 0..1000 {
+        let mut record = Record::new(i);
+        record.add_value(values::string("helloworld"));
+        builder.add_record(record)?;
+    }
+    let bytes = builder.finish()?.into_inner();
+
+    assert_eq!(bytes[16..18], [4, 0]);
+    let n_pages = u32::from_be_bytes(bytes[28..32].try_into().unwrap());
+    assert_eq!(bytes.len(), n_pages as usize * 1024);
+    assert!(validate(&bytes).is_empty());
+    let rowids: Vec<i64> = DatabaseReader::new(&bytes)?
+        .records("foo")?
+        .iter()
+        .map(|record| record.rowid)
+        .collect();
+    assert_eq!(rowids, (0..1000).collect::<Vec<_>>());
+    Ok(())
+}
+
+#[test]
+fn test_streaming_auto_rowid() -> Result<(), Error> {
+    let path = std::env::temp_dir().join("foo_streaming_auto_rowid.db");
+    let mut builder = StreamingDatabaseBuilder::new(File::create(&path)?);
+    builder.auto_rowid(true);
+    builder.schema("foo", "create table foo(bar text)")?;
+    for _ in 0..1000 {
+        let mut record = Record::new(0);
+        record.add_value(values::string("helloworld"));
+        builder.add_record(record)?;
+    }
+    builder.finish()?;
+
+    let bytes = std::fs::read(&path)?;
+    assert!(validate(&bytes).is_empty());
+    let rowids: Vec<i64> = DatabaseReader::new(&bytes)?
+        .records("foo")?
+        .iter()
+        .map(|record| record.rowid)
+        .collect();
+    assert_eq!(rowids, (1..=1000).collect::<Vec<_>>());
+    Ok(())
+}
+
+#[test]
+fn test_streaming_after_prefix() -> Result<(), Error> {
+    let mut sink = Cursor::new(b"PREFIX".to_vec());
+    sink.set_position(6);
+    let mut builder = StreamingDatabaseBuilder::new(sink);
+    builder.schema("foo", "create table foo(bar text)")?;
+    for i in 1..=1000 {
+        let mut record = Record::new(i);
+        record.add_value(values::string("helloworld"));
+        builder.add_record(record)?;
+    }
+    let sink = builder.finish()?;
+    assert_eq!(sink.position(), sink.get_ref().len() as u64);
+
+    let bytes = sink.into_inner();
+    assert_eq!(&bytes[..6], b"PREFIX");
+    let database = &bytes[6..];
+    assert!(validate(database).is_empty());
+    assert_eq!(DatabaseReader::new(database)?.records("foo")?.len(), 1000);
+    Ok(())
+}
+
+#[test]
+fn test_streaming_large_schema() -> Result<(), Error> {
+    let mut builder = StreamingDatabaseBuilder::new(Cursor::new(Vec::new()));
+    builder.page_size(512)?;
+    for table in 0..100 {
+        let name = format!("table_{table}");
+        builder.schema(
+            &name,
+            &format!("create table {name}(id integer, name text)"),
+        )?;
+        builder.index(&format!("create index {name}_name on {name}(name)"))?;
+        let mut record = Record::new(1);
+        record.add_value(values::integer(table));
+        record.add_value(values::string(&name));
+        builder.add_record(record)?;
+    }
+    let bytes = builder.finish()?.into_inner();
+    assert!(validate(&bytes).is_empty());
+
+    let reader = DatabaseReader::new(&bytes)?;
+    assert_eq!(reader.schema()?.len(), 200);
+    let records = reader.records("table_99")?;
+    assert_eq!(records[0].values()[1], values::string("table_99"));
+    Ok(())
+}
@@ -0,0 +1,39 @@
+#![cfg(feature = "tokio")]
+
+use sqlighters::{values, write_sqlite_async, Database, DatabaseBuilder, Error, Record};
+use tokio::io::AsyncReadExt;
+
+fn database() -> Result<Database, Error> {
+    let mut builder = DatabaseBuilder::new();
+    builder.schema("foo", "create table foo(bar text)")?;
+    builder.index("create index foo_bar on foo(bar)")?;
+    for i in 0..10_000 {
+        let mut record = Record::new(i);
+        record.add_value(values::string(&"helloworld".repeat(i as usize % 100)));
+        builder.add_record(record)?;
+    }
+    builder.build()
+}
+
+#[tokio::test]
+async fn test_write_sqlite_async() -> Result<(), Error> {
+    let path = std::env::temp_dir().join("foo_async.db");
+    write_sqlite_async(database()?, tokio::fs::File::create(&path).await?).await?;
+    assert_eq!(tokio::fs::read(&path).await?, database()?.to_bytes()?);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_write_sqlite_async_with_backpressure() -> Result<(), Error> {
+    // the writer has to wait for the reader, because the pipe holds less than a page
+    let (writer, mut reader) = tokio::io::duplex(1024);
+    let mut bytes = Vec::new();
+    let (written, read) = tokio::join!(
+        write_sqlite_async(database()?, writer),
+        reader.read_to_end(&mut bytes)
+    );
+    written?;
+    read?;
+    assert_eq!(bytes, database()?.to_bytes()?);
+    Ok(())
+}
@@ -0,0 +1,306 @@
+use byteorder::{BigEndian, ByteOrder};
+use sqlighters::{
+    validate, values, DatabaseBuilder, DatabaseReader, DiagnosticKind, Error, Record,
+    StreamingDatabaseBuilder, TextEncoding,
+};
+use std::io::Cursor;
+
+const SQLITE_FILE: &[u8] = include_bytes!("data/sqlite.db");
+/// NOCASE and RTRIM columns and indexes, made by SQLite
+const COLLATIONS_FILE: &[u8] = include_bytes!("data/collations.db");
+/// a UTF-16le WITHOUT ROWID table with a NOCASE column in its PRIMARY KEY, and indexes on it, made by SQLite
+const WITHOUT_ROWID_FILE: &[u8] = include_bytes!("data/without_rowid.db");
+const PAGE_SIZE: usize = 512;
+
+/// 1000 records on 512 byte pages, with a root page that is an interior page
+fn database() -> Result<Vec<u8>, Error> {
+    let mut builder = DatabaseBuilder::new();
+    builder.page_size(PAGE_SIZE as u32)?;
+    builder.schema("foo", "create table foo(id integer primary key, bar text)")?;
+    builder.index("create index foo_bar on foo(bar desc)")?;
+    for id in 1..=1000 {
+        let mut record = Record::new(0);
+        record.add_value(values::integer(id));
+        record.add_value(values::string(&format!("bar {id}")));
+        builder.add_record(record)?;
+    }
+    let database = builder.build()?;
+    database.to_bytes()
+}
+
+fn kinds(bytes: &[u8]) -> Vec<DiagnosticKind> {
+    validate(bytes).into_iter().map(|d| d.kind).collect()
+}
+
+fn page(bytes: &mut [u8], page_number: u32) -> &mut [u8] {
+    let start = (page_number as usize - 1) * PAGE_SIZE;
+    &mut bytes[start..start + PAGE_SIZE]
+}
+
+fn root_page(bytes: &[u8], name: &str) -> u32 {
+    let reader = DatabaseReader::new(bytes).unwrap();
+    let schema = reader.schema().unwrap();
+    schema.iter().find(|e| e.name == name).unwrap().root_page
+}
+
+#[test]
+fn test_valid_files() -> Result<(), Error> {
+    assert_eq!(validate(SQLITE_FILE), []);
+    assert_eq!(validate(&database()?), []);
+
+    for (page_size, encoding) in [(1024, TextEncoding::Utf16le), (65536, TextEncoding::Utf8)] {
+        let mut builder = DatabaseBuilder::new();
+        builder.page_size(page_size)?;
+        builder.encoding(encoding)?;
+        builder.schema("foo", "create table foo(a text unique, b)")?;
+        for rowid in -500..500 {
+            let mut record = Record::new(rowid);
+            let text = "é".repeat(rowid.unsigned_abs() as usize * 10);
+            record.add_value(values::string(&format!("{rowid}{text}")));
+            record.add_value(values::blob(vec![1; 100]));
+            builder.add_record(record)?;
+        }
+        let database = builder.build()?;
+        assert_eq!(validate(&database.to_bytes()?), []);
+    }
+
+    let mut builder = StreamingDatabaseBuilder::new(Cursor::new(Vec::new()));
+    builder.page_size(PAGE_SIZE as u32)?;
+    builder.schema("foo", "create table foo(bar)")?;
+    builder.index("create index foo_bar on foo(bar)")?;
+    for rowid in 1..=5000 {
+        let mut record = Record::new(rowid);
+        record.add_value(values::integer(rowid % 7));
+        builder.add_record(record)?;
+    }
+    assert_eq!(validate(builder.finish()?.get_ref()), []);
+    Ok(())
+}
+
+fn first_leaf(bytes: &mut [u8], name: &str) -> u32 {
+    let mut page_number = root_page(bytes, name);
+    loop {
+        let page = page(bytes, page_number);
+        if page[0] & 0x08 != 0 {
+            return page_number;
+        }
+        let first_cell = usize::from(BigEndian::read_u16(&page[12..]));
+        page_number = BigEndian::read_u32(&page[first_cell..]);
+    }
+}
+
+fn swap_first_cells(page: &mut [u8]) {
+    let (first, second) = (page[8..10].to_vec(), page[10..12].to_vec());
+    page[8..10].copy_from_slice(&second);
+    page[10..12].copy_from_slice(&first);
+}
+
+#[test]
+fn test_collations() -> Result<(), Error> {
+    assert_eq!(validate(COLLATIONS_FILE), []);
+
+    let database = |index_sql: &str| -> Result<Vec<u8>, Error> {
+        let mut builder = DatabaseBuilder::new();
+        builder.schema("foo", "create table foo(bar text collate nocase unique)")?;
+        builder.index(index_sql)?;
+        for (rowid, bar) in ["b", "A", "c", "B "].into_iter().enumerate() {
+            let mut record = Record::new(rowid as i64 + 1);
+            record.add_value(values::string(bar));
+            builder.add_record(record)?;
+        }
+        let database = builder.build()?;
+        database.to_bytes()
+    };
+    assert_eq!(
+        validate(&database("create index foo_bar on foo(bar collate rtrim)")?),
+        []
+    );
+
+    // an index that says NOCASE, but is sorted with BINARY
+    let mut bytes = database("create index foo_bar on foo(bar collate binary)")?;
+    let position = bytes.windows(6).position(|w| w == b"binary").unwrap();
+    bytes[position..position + 6].copy_from_slice(b"nocase");
+    assert_eq!(kinds(&bytes), [DiagnosticKind::KeyOrder]);
+
+    // the order of an unknown collation isn't checked
+    bytes[position..position + 6].copy_from_slice(b"french");
+    assert_eq!(validate(&bytes), []);
+    Ok(())
+}
+
+#[test]
+fn test_without_rowid() -> Result<(), Error> {
+    assert_eq!(validate(WITHOUT_ROWID_FILE), []);
+
+    let mut bytes = WITHOUT_ROWID_FILE.to_vec();
+    let leaf = first_leaf(&mut bytes, "stock");
+    swap_first_cells(page(&mut bytes, leaf));
+    assert_eq!(kinds(&bytes), [DiagnosticKind::KeyOrder]);
+
+    // a rowid table that says it is WITHOUT ROWID
+    let mut builder = DatabaseBuilder::new();
+    builder.schema(
+        "foo",
+        "create table foo(bar text primary key)              ",
+    )?;
+    let mut record = Record::new(1);
+    record.add_value(values::string("bar"));
+    builder.add_record(record)?;
+    let database = builder.build()?;
+    let mut bytes = database.to_bytes()?;
+    let position = bytes
+        .windows(12)
+        .position(|w| w == b"primary key)")
+        .unwrap()
+        + 12;
+    bytes[position..position + 14].copy_from_slice(b" without rowid");
+    assert!(kinds(&bytes).contains(&DiagnosticKind::PageHeader));
+    Ok(())
+}
+
+#[test]
+fn test_header() -> Result<(), Error> {
+    let mut bytes = database()?;
+    let page_count = BigEndian::read_u32(&bytes[28..]);
+    BigEndian::write_u32(&mut bytes[28..], page_count + 1);
+    assert_eq!(kinds(&bytes), [DiagnosticKind::Header]);
+
+    assert_eq!(kinds(&bytes[..50]), [DiagnosticKind::Header]);
+    Ok(())
+}
+
+#[test]
+fn test_unused_page() -> Result<(), Error> {
+    let mut bytes = database()?;
+    let page_count = BigEndian::read_u32(&bytes[28..]);
+    BigEndian::write_u32(&mut bytes[28..], page_count + 1);
+    bytes.extend_from_slice(&[0; PAGE_SIZE]);
+    let diagnostics = validate(&bytes);
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].kind, DiagnosticKind::UnusedPage);
+    assert_eq!(diagnostics[0].page, page_count + 1);
+    Ok(())
+}
+
+#[test]
+fn test_child_page_out_of_range() -> Result<(), Error> {
+    let mut bytes = database()?;
+    let root_page = root_page(&bytes, "foo");
+    BigEndian::write_u32(&mut page(&mut bytes, root_page)[8..], 9999);
+    let kinds = kinds(&bytes);
+    assert!(kinds.contains(&DiagnosticKind::ChildPages));
+    // the pages under the old rightmost child are never used now
+    assert!(kinds.contains(&DiagnosticKind::UnusedPage));
+    Ok(())
+}
+
+#[test]
+fn test_key_order() -> Result<(), Error> {
+    let mut bytes = database()?;
+    let leaf = first_leaf(&mut bytes, "foo");
+    swap_first_cells(page(&mut bytes, leaf));
+    assert_eq!(kinds(&bytes), [DiagnosticKind::KeyOrder]);
+    Ok(())
+}
+
+#[test]
+fn test_cell_pointer_out_of_bounds() -> Result<(), Error> {
+    let mut bytes = database()?;
+    let index_root = root_page(&bytes, "foo_bar");
+    let root = page(&mut bytes, index_root);
+    BigEndian::write_u16(&mut root[12..], 4);
+    let kinds = kinds(&bytes);
+    assert!(kinds.contains(&DiagnosticKind::CellPointers));
+    Ok(())
+}
+
+#[test]
+fn test_freeblocks() -> Result<(), Error> {
+    let mut bytes = database()?;
+    let root_page = root_page(&bytes, "foo");
+    let root = page(&mut bytes, root_page);
+    // the first free block is where the cells start
+    let content_start = BigEndian::read_u16(&root[5..]);
+    BigEndian::write_u16(&mut root[1..], content_start);
+    assert_eq!(kinds(&bytes)[0], DiagnosticKind::Freeblocks);
+    Ok(())
+}
+
+#[test]
+fn test_overflow() -> Result<(), Error> {
+    let mut builder = DatabaseBuilder::new();
+    builder.page_size(PAGE_SIZE as u32)?;
+    builder.schema("foo", "create table foo(bar)")?;
+    let mut record = Record::new(1);
+    record.add_value(values::blob(vec![1; 2000]));
+    builder.add_record(record)?;
+    let database = builder.build()?;
+    let mut bytes = database.to_bytes()?;
+
+    // the last overflow page points back to the table
+    let last_page = (bytes.len() / PAGE_SIZE) as u32;
+    BigEndian::write_u32(page(&mut bytes, last_page), 2);
+    assert_eq!(kinds(&bytes), [DiagnosticKind::Overflow]);
+    Ok(())
+}
+
+#[test]
+fn test_index_without_all_rows() -> Result<(), Error> {
+    let mut builder = DatabaseBuilder::new();
+    builder.page_size(PAGE_SIZE as u32)?;
+    builder.schema("foo", "create table foo(bar)")?;
+    builder.index("create index foo_bar on foo(bar)")?;
+    for rowid in 1..=10 {
+        let mut record = Record::new(rowid);
+        record.add_value(values::integer(rowid));
+        builder.add_record(record)?;
+    }
+    let database = builder.build()?;
+    let mut bytes = database.to_bytes()?;
+
+    // the index leaf forgets its last cell
+    let index_root = root_page(&bytes, "foo_bar");
+    BigEndian::write_u16(&mut page(&mut bytes, index_root)[3..], 9);
+    let kinds = kinds(&bytes);
+    assert!(kinds.contains(&DiagnosticKind::Schema));
+    assert!(kinds.contains(&DiagnosticKind::Freeblocks));
+    Ok(())
+}
+
+#[test]
+fn test_index_contents() {
+    let mut bytes = SQLITE_FILE.to_vec();
+    let position = bytes
+        .windows(9)
+        .position(|window| window == b"person 50")
+        .unwrap();
+    bytes[position] = b'P';
+    let diagnostics = validate(&bytes);
+    let messages: Vec<String> = diagnostics.iter().map(ToString::to_string).collect();
+    assert_eq!(
+        messages,
+        [
+            "page 3: row 150 is missing from index people_name",
+            "page 3: index people_name has an entry ['person 50', 150] for row 150, which is not in table people",
+        ]
+    );
+    assert!(diagnostics
+        .iter()
+        .all(|diagnostic| diagnostic.kind == DiagnosticKind::Schema));
+}
+
+#[test]
+fn test_damaged_bytes() {
+    let mut bytes = SQLITE_FILE.to_vec();
+    for position in (0..bytes.len()).step_by(23) {
+        bytes[position] ^= 0xff;
+        let diagnostics = validate(&bytes);
+        // whatever keeps the file from being read is found
+        if let Ok(reader) = DatabaseReader::new(&bytes) {
+            if reader.records("people").is_err() || reader.index_entries("people_name").is_err() {
+                assert!(!diagnostics.is_empty(), "damage at {position} is not found");
+            }
+        }
+        bytes[position] ^= 0xff;
+    }
+}